@@ -0,0 +1,39 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use wikitools::pipeline::{Pipeline, Stage};
+use wikitools::settings::Settings;
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("anchors")
+        .about("Count anchors in the dump and build a FST of surface form/entity counts")
+        .arg(args::dump())
+        .arg(args::index())
+        .arg(args::indices())
+        .arg(args::filter())
+        .arg(args::fst())
+        .arg(args::redirect_map())
+        .arg(
+            Arg::with_name("memory-budget")
+                .long("memory-budget")
+                .takes_value(true)
+                .help("Spill counts to disk to stay within this many bytes; overrides anchors.memory_budget"),
+        )
+        .arg(
+            Arg::with_name("resolve-redirects")
+                .long("resolve-redirects")
+                .help("Count anchors against the page their target redirects to; overrides anchors.resolve_redirects"),
+        )
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let mut settings = settings.clone();
+    if matches.is_present("resolve-redirects") {
+        settings.anchors.resolve_redirects = true;
+    }
+    if let Some(budget) = matches.value_of("memory-budget") {
+        settings.anchors.memory_budget = Some(budget.parse::<usize>()?);
+    }
+    Pipeline::new(&settings)?.build(&[Stage::Anchors], &[Stage::Anchors])
+}
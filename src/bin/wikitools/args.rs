@@ -0,0 +1,155 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, ArgMatches};
+use config::ConfigError;
+
+use wikitools::settings::Settings;
+
+/// Config file used when `--config` is not given.
+static DEFAULT_CONFIG: &str = "config.toml";
+
+/// Load settings from the `--config` path, falling back to `config.toml` if it
+/// exists and the default settings otherwise.
+pub fn load_settings(matches: &ArgMatches) -> Result<Settings, ConfigError> {
+    let mut settings = match matches.value_of("config") {
+        Some(path) => Settings::new(path)?,
+        None if Path::new(DEFAULT_CONFIG).exists() => Settings::new(DEFAULT_CONFIG)?,
+        None => Settings::default(),
+    };
+    apply_overrides(&mut settings, matches);
+    if let Some(name) = matches.value_of("filter") {
+        settings.pages = settings
+            .filters
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ConfigError::NotFound(format!("filters.{}", name)))?;
+    }
+    Ok(settings)
+}
+
+/// Overwrite settings with any path overrides passed on the command line.
+pub fn apply_overrides(settings: &mut Settings, matches: &ArgMatches) {
+    if let Some(path) = matches.value_of("dump") {
+        settings.data.dump = path.into();
+    }
+    if let Some(path) = matches.value_of("index") {
+        settings.data.index = path.into();
+    }
+    if let Some(path) = matches.value_of("indices") {
+        settings.indices.pages = path.into();
+    }
+    if let Some(path) = matches.value_of("template-indices") {
+        settings.indices.templates = path.into();
+    }
+    if let Some(path) = matches.value_of("index-dir") {
+        settings.search_index.index_dir = path.into();
+    }
+    if let Some(path) = matches.value_of("fst") {
+        settings.anchors.fst = path.into();
+    }
+    if let Some(path) = matches.value_of("titles") {
+        settings.anchors.titles = path.into();
+    }
+    if let Some(path) = matches.value_of("redirect-map") {
+        settings.redirect_map = path.into();
+    }
+}
+
+/// Fetch an output path, falling back to the given default.
+pub fn output_or(matches: &ArgMatches, default: &Path) -> PathBuf {
+    matches
+        .value_of("output")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| default.to_path_buf())
+}
+
+/// Path to the settings file.
+pub fn config<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("config")
+        .short("c")
+        .long("config")
+        .takes_value(true)
+        .global(true)
+        .help("Path to the settings file [default: config.toml]")
+}
+
+/// Path to the dump multistream.
+pub fn dump<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("dump")
+        .long("dump")
+        .takes_value(true)
+        .help("Path to the Wikipedia dump multistream; overrides data.dump")
+}
+
+/// Path to the dump multistream's index.
+pub fn index<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("index")
+        .long("index")
+        .takes_value(true)
+        .help("Path to the Wikipedia dump multistream index; overrides data.index")
+}
+
+/// Path to the page indices file.
+pub fn indices<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("indices")
+        .long("indices")
+        .takes_value(true)
+        .help("Path to the page indices file; overrides indices.pages")
+}
+
+/// Path to the template indices file.
+pub fn template_indices<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("template-indices")
+        .long("template-indices")
+        .takes_value(true)
+        .help("Path to the template indices file; overrides indices.templates")
+}
+
+/// Path to the search index directory.
+pub fn index_dir<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("index-dir")
+        .long("index-dir")
+        .takes_value(true)
+        .help("Path to the search index directory; overrides search_index.index_dir")
+}
+
+/// Path to the anchor FST.
+pub fn fst<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("fst")
+        .long("fst")
+        .takes_value(true)
+        .help("Path to the anchor FST; overrides anchors.fst")
+}
+
+/// Path to the title surface forms FST.
+pub fn titles<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("titles")
+        .long("titles")
+        .takes_value(true)
+        .help("Path to the title surface forms FST; overrides anchors.titles")
+}
+
+/// Path to the resolved redirect map.
+pub fn redirect_map<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("redirect-map")
+        .long("redirect-map")
+        .takes_value(true)
+        .help("Path to the resolved redirect map; overrides redirect_map")
+}
+
+/// Named page filter.
+pub fn filter<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("filter")
+        .long("filter")
+        .takes_value(true)
+        .help("Name of the page filter to use from [filters]; overrides [pages]")
+}
+
+/// Output path.
+pub fn output<'a, 'b>(help: &'b str) -> Arg<'a, 'b> {
+    Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .takes_value(true)
+        .help(help)
+}
@@ -0,0 +1,110 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use wikitools::pipeline::{Pipeline, Stage};
+use wikitools::settings::Settings;
+
+use crate::args;
+
+/// Validate stage name args.
+fn is_stage(name: String) -> Result<(), String> {
+    if name == "all" {
+        return Ok(());
+    }
+    name.parse::<Stage>().map(|_| ())
+}
+
+/// Parse the values of a stage list argument, expanding `all`.
+fn stages(matches: &ArgMatches, name: &str) -> Vec<Stage> {
+    match matches.values_of(name) {
+        None => vec![],
+        Some(names) => {
+            let names = names.collect::<Vec<_>>();
+            if names.contains(&"all") {
+                return Stage::all().to_vec();
+            }
+            names.iter().map(|name| name.parse().unwrap()).collect()
+        }
+    }
+}
+
+/// Arguments shared by `build` and `plan`.
+fn with_stage_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(args::dump())
+        .arg(args::index())
+        .arg(args::indices())
+        .arg(args::template_indices())
+        .arg(args::index_dir())
+        .arg(args::fst())
+        .arg(
+            Arg::with_name("stages")
+                .index(1)
+                .multiple(true)
+                .validator(is_stage)
+                .help("Stages to bring up to date [default: all]"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_stage)
+                .help("Rebuild a stage even if it is up to date; may be repeated"),
+        )
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    with_stage_args(
+        SubCommand::with_name("build")
+            .about("Rebuild all out of date pipeline stages")
+            .arg(
+                Arg::with_name("dry-run")
+                    .short("n")
+                    .long("dry-run")
+                    .help("Print the stages that would be rebuilt without building them"),
+            ),
+    )
+}
+
+pub fn plan_subcommand<'a, 'b>() -> App<'a, 'b> {
+    with_stage_args(
+        SubCommand::with_name("plan").about("Print the pipeline stages that would be rebuilt"),
+    )
+}
+
+/// Print the status of every stage.
+fn print_plan(
+    pipeline: &Pipeline,
+    targets: &[Stage],
+    forced: &[Stage],
+) -> Result<(), Box<std::error::Error>> {
+    let plan = pipeline.plan(targets, forced)?;
+    for stage in Stage::all() {
+        match plan.iter().find(|(planned, _)| planned == stage) {
+            Some((_, reason)) => println!("{:<18}rebuild ({})", stage.name(), reason),
+            None if targets.contains(stage) => println!("{:<18}up to date", stage.name()),
+            None => (),
+        }
+    }
+    Ok(())
+}
+
+/// Build the requested stages, or print the plan if `dry_run` is set or the
+/// `--dry-run` flag is present.
+pub fn run(
+    settings: &Settings,
+    matches: &ArgMatches,
+    dry_run: bool,
+) -> Result<(), Box<std::error::Error>> {
+    let mut targets = stages(matches, "stages");
+    if targets.is_empty() {
+        targets = Stage::all().to_vec();
+    }
+    let forced = stages(matches, "force");
+    let mut pipeline = Pipeline::new(settings)?;
+
+    if dry_run || matches.is_present("dry-run") {
+        return print_plan(&pipeline, &targets, &forced);
+    }
+    pipeline.build(&targets, &forced)
+}
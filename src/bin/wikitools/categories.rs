@@ -0,0 +1,84 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use log::info;
+
+use storage::page::TitleNormaliser;
+use wikitools::category::{extract_categories, CategoryExtractor};
+use wikitools::loaders::{
+    build_or_load_page_indices, load_category_filter, load_link_parser, load_site_info,
+    load_templates,
+};
+use wikitools::settings::Settings;
+use wikitools::utils::{mutex_bufwriter, Timer};
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("categories")
+        .about("Extract the categories of pages and the parents of categories with their sort keys")
+        .arg(args::dump())
+        .arg(args::index())
+        .arg(args::indices())
+        .arg(args::filter())
+        .arg(args::output("Output path; overrides categories.links"))
+        .arg(
+            Arg::with_name("hidden")
+                .long("hidden")
+                .takes_value(true)
+                .help("Path to the list of hidden categories; overrides categories.hidden"),
+        )
+        .arg(
+            Arg::with_name("no-templates")
+                .long("no-templates")
+                .help("Only find categories in page text, without expanding templates"),
+        )
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let page_indices = build_or_load_page_indices(settings)?;
+    let site = load_site_info(settings);
+    let filter = load_category_filter(settings, &site)?;
+    let titles = TitleNormaliser::from_site_info(&site);
+    let templates = if settings.categories.expand_templates && !matches.is_present("no-templates") {
+        Some(load_templates(settings, &site)?)
+    } else {
+        None
+    };
+    let mut extractor = CategoryExtractor::new(load_link_parser(settings, &site)?, titles);
+    if let Some(ref templates) = templates {
+        extractor = extractor.with_templates(templates);
+    }
+
+    let mut timer = Timer::new();
+    let output = args::output_or(matches, &settings.categories.links);
+    info!("Writing categories to {:?}", output);
+    let writer = mutex_bufwriter(&output, 8192 * 16)?;
+    let hidden = extract_categories(
+        &page_indices,
+        &settings.data.dump,
+        &writer,
+        &filter,
+        &extractor,
+    );
+    writer.into_inner().unwrap().flush()?;
+
+    let hidden_path = matches
+        .value_of("hidden")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| settings.categories.hidden.clone());
+    info!(
+        "Writing {} hidden categories to {:?}",
+        hidden.len(),
+        hidden_path
+    );
+    let mut writer = BufWriter::new(File::create(hidden_path)?);
+    for name in hidden {
+        writeln!(writer, "{}", name)?;
+    }
+    writer.flush()?;
+    timer.finish();
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use storage::page::{namespace::CATEGORY, TitleNormaliser};
+use wikitools::category::{CategoryGraph, Node, Order, Visit};
+use wikitools::loaders::{load_category_graph, load_site_info};
+use wikitools::settings::Settings;
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("category-tree")
+        .about("Export a subtree of the category hierarchy, or the ancestors of a page")
+        .arg(
+            Arg::with_name("category")
+                .index(1)
+                .required_unless_one(&["ancestors", "cycles"])
+                .help("Category to export, without its namespace prefix"),
+        )
+        .arg(
+            Arg::with_name("ancestors")
+                .long("ancestors")
+                .takes_value(true)
+                .conflicts_with_all(&["category", "cycles", "pages"])
+                .help("Export the ancestors of this page or category instead"),
+        )
+        .arg(
+            Arg::with_name("root")
+                .long("root")
+                .takes_value(true)
+                .multiple(true)
+                .requires("ancestors")
+                .help("Export only a shortest path from the page to one of these categories"),
+        )
+        .arg(
+            Arg::with_name("cycles")
+                .long("cycles")
+                .help("Export the cycles in the hierarchy, one per line"),
+        )
+        .arg(
+            Arg::with_name("depth")
+                .long("depth")
+                .takes_value(true)
+                .help("Maximum number of levels to follow"),
+        )
+        .arg(
+            Arg::with_name("pages")
+                .long("pages")
+                .help("Also export the pages in each category"),
+        )
+        .arg(
+            Arg::with_name("dfs")
+                .long("dfs")
+                .help("Export categories depth first, each followed by its subcategories"),
+        )
+        .arg(
+            Arg::with_name("no-hidden")
+                .long("no-hidden")
+                .help("Skip hidden categories, such as maintenance categories"),
+        )
+        .arg(args::output("Output path; writes to stdout if absent"))
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let graph = load_category_graph(settings)?;
+    match matches.value_of("output") {
+        Some(output) => {
+            let mut writer = BufWriter::new(File::create(output)?);
+            export(&graph, settings, matches, &mut writer)?;
+            writer.flush()?;
+        }
+        None => {
+            let stdout = io::stdout();
+            export(&graph, settings, matches, &mut stdout.lock())?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the requested part of the graph as tab-separated `kind, depth, name,
+/// via` rows, where `kind` is `category` or `page` and `via` is the category
+/// through which it was reached.
+fn export<W: Write>(
+    graph: &CategoryGraph,
+    settings: &Settings,
+    matches: &ArgMatches,
+    writer: &mut W,
+) -> Result<(), Box<std::error::Error>> {
+    if matches.is_present("cycles") {
+        for cycle in graph.cycles() {
+            writeln!(writer, "{}", cycle.join("\t"))?;
+        }
+        return Ok(());
+    }
+
+    let mut traversal = graph.traverse();
+    if let Some(depth) = matches.value_of("depth") {
+        traversal = traversal.with_max_depth(depth.parse::<usize>()?);
+    }
+    if matches.is_present("dfs") {
+        traversal = traversal.with_order(Order::DepthFirst);
+    }
+    if matches.is_present("no-hidden") {
+        traversal = traversal.without_hidden();
+    }
+
+    if let Some(title) = matches.value_of("ancestors") {
+        // Category titles are written with their namespace prefix.
+        let titles = TitleNormaliser::from_site_info(&load_site_info(settings));
+        let title = titles.parse(title).ok_or("invalid title")?;
+        let node = if title.ns == CATEGORY {
+            Node::Category(title.name())
+        } else {
+            Node::Page(title.as_str())
+        };
+        let visits = match matches.values_of("root") {
+            Some(roots) => {
+                let roots = roots.collect::<Vec<_>>();
+                traversal
+                    .path(node, &roots)
+                    .ok_or("no path to a root category")?
+            }
+            None => traversal.ancestors(node),
+        };
+        return write_visits(writer, "category", &visits);
+    }
+
+    let category = matches.value_of("category").unwrap();
+    write_visits(writer, "category", &traversal.descendants(category))?;
+    if matches.is_present("pages") {
+        write_visits(writer, "page", &traversal.pages(category))?;
+    }
+    Ok(())
+}
+
+fn write_visits<W: Write>(
+    writer: &mut W,
+    kind: &str,
+    visits: &[Visit],
+) -> Result<(), Box<std::error::Error>> {
+    for visit in visits {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}",
+            kind,
+            visit.depth,
+            visit.name,
+            visit.via.unwrap_or("")
+        )?;
+    }
+    Ok(())
+}
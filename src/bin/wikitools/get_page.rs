@@ -0,0 +1,82 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use storage::page::TitleNormaliser;
+use wikitools::loaders::{load_site_info, load_templates};
+use wikitools::lookup::{fetch_page, PageLookup};
+use wikitools::pipeline::{Pipeline, Stage};
+use wikitools::settings::Settings;
+use wikitools::template::Expander;
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("get-page")
+        .about("Fetch a single page from the dump by title or page ID")
+        .arg(args::dump())
+        .arg(args::index())
+        .arg(
+            Arg::with_name("lookup")
+                .long("lookup")
+                .takes_value(true)
+                .help("Path to the lookup directory; overrides lookup"),
+        )
+        .arg(
+            Arg::with_name("id")
+                .long("id")
+                .takes_value(true)
+                .conflicts_with("title")
+                .required_unless("title")
+                .help("Page ID to fetch"),
+        )
+        .arg(
+            Arg::with_name("raw")
+                .long("raw")
+                .help("Print the page wikitext rather than extracted page data as JSON"),
+        )
+        .arg(
+            Arg::with_name("expand")
+                .long("expand")
+                .help("Expand templates in the page text, compiling templates first if needed"),
+        )
+        .arg(
+            Arg::with_name("title")
+                .index(1)
+                .help("Title of the page to fetch"),
+        )
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let mut settings = settings.clone();
+    if let Some(path) = matches.value_of("lookup") {
+        settings.lookup = path.into();
+    }
+    let site = load_site_info(&settings);
+    // Build the lookup on first use.
+    Pipeline::new(&settings)?
+        .with_site_info(&site)
+        .build(&[Stage::Lookup], &[])?;
+    let lookup = PageLookup::new(&settings.lookup, TitleNormaliser::from_site_info(&site))?;
+
+    let location = match matches.value_of("id") {
+        Some(id) => lookup.by_id(id.parse::<u64>()?),
+        None => lookup.by_title(matches.value_of("title").unwrap()),
+    };
+    let location = location.ok_or("no such page in the index")?;
+    let mut page = fetch_page(&settings.data.dump, &location)?.ok_or_else(|| {
+        format!(
+            "page {} not found in stream at {}",
+            location.id, location.offset
+        )
+    })?;
+    if matches.is_present("expand") {
+        let templates = load_templates(&settings, &site)?;
+        page.text = Expander::new(&templates).expand(&page.title, &page.text);
+    }
+
+    if matches.is_present("raw") {
+        println!("{}", page.text);
+    } else {
+        println!("{}", serde_json::to_string_pretty(&page.to_page())?);
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use wikitools::pipeline::{Pipeline, Stage};
+use wikitools::settings::Settings;
+
+use crate::args;
+
+/// Validate a number of dump streams per commit.
+fn is_chunk_size(size: String) -> Result<(), String> {
+    match size.parse::<usize>() {
+        Ok(0) => Err("chunk size must be at least 1".to_owned()),
+        Ok(_) => Ok(()),
+        Err(error) => Err(format!("{} is not a valid chunk size: {}", size, error)),
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("index")
+        .about("Index page content and links from the dump with tantivy")
+        .arg(args::dump())
+        .arg(args::index())
+        .arg(args::indices())
+        .arg(args::filter())
+        .arg(args::index_dir())
+        .arg(
+            Arg::with_name("buffer-size")
+                .long("buffer-size")
+                .takes_value(true)
+                .help("Index writer heap size in bytes; overrides search_index.writer_heap"),
+        )
+        .arg(
+            Arg::with_name("chunk-size")
+                .long("chunk-size")
+                .takes_value(true)
+                .validator(is_chunk_size)
+                .help("Number of dump streams to index between commits; overrides search_index.chunk_size"),
+        )
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let mut settings = settings.clone();
+    if let Some(size) = matches.value_of("buffer-size") {
+        settings.search_index.writer_heap = size.parse::<usize>()?;
+    }
+    if let Some(size) = matches.value_of("chunk-size") {
+        settings.search_index.chunk_size = size.parse::<usize>()?;
+    }
+    Pipeline::new(&settings)?.build(&[Stage::SearchIndex], &[Stage::SearchIndex])
+}
@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use wikitools::indices::convert_indices;
+use wikitools::pipeline::{Pipeline, Stage};
+use wikitools::settings::Settings;
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("indices")
+        .about("Build the page indices file from the dump multistream index")
+        .arg(args::index())
+        .arg(args::indices())
+        .subcommand(
+            SubCommand::with_name("convert")
+                .about("Convert an indices file between the text and binary formats")
+                .arg(
+                    Arg::with_name("input")
+                        .index(1)
+                        .required(true)
+                        .help("Indices file in either format"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .index(2)
+                        .required(true)
+                        .help("Path to write converted indices to"),
+                )
+                .arg(
+                    Arg::with_name("text")
+                        .long("text")
+                        .help("Write the text format rather than the binary format"),
+                ),
+        )
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    if let Some(matches) = matches.subcommand_matches("convert") {
+        let count = convert_indices(
+            Path::new(matches.value_of("input").unwrap()),
+            Path::new(matches.value_of("output").unwrap()),
+            matches.is_present("text"),
+        )?;
+        println!("Converted indices for {} streams", count);
+        return Ok(());
+    }
+    Pipeline::new(settings)?.build(&[Stage::PageIndices], &[Stage::PageIndices])
+}
@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use log::info;
+
+use wikitools::infobox::extract_infoboxes;
+use wikitools::loaders::{
+    build_or_load_page_indices, load_infobox_extractor, load_page_filter, load_site_info,
+};
+use wikitools::settings::Settings;
+use wikitools::utils::{mutex_bufwriter, Timer};
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("infoboxes")
+        .about("Extract infoboxes from the dump as JSONL and report the frequency of their fields")
+        .arg(args::dump())
+        .arg(args::index())
+        .arg(args::indices())
+        .arg(args::filter())
+        .arg(args::output("Output path; writes to stdout if absent"))
+        .arg(
+            Arg::with_name("report")
+                .long("report")
+                .takes_value(true)
+                .help("Path to the field frequency report; overrides infoboxes.report"),
+        )
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let page_indices = build_or_load_page_indices(settings)?;
+    let site = load_site_info(settings);
+    let filter = load_page_filter(settings, &site)?;
+    let extractor = load_infobox_extractor(settings, &site)?;
+
+    let mut timer = Timer::new();
+    let report = match matches.value_of("output") {
+        Some(output) => {
+            info!("Writing infoboxes to {}", output);
+            let writer = mutex_bufwriter(output, 8192 * 16)?;
+            let report = extract_infoboxes(
+                &page_indices,
+                &settings.data.dump,
+                &writer,
+                &filter,
+                &extractor,
+            );
+            writer.into_inner().unwrap().flush()?;
+            report
+        }
+        None => {
+            let writer = Mutex::new(io::stdout());
+            extract_infoboxes(
+                &page_indices,
+                &settings.data.dump,
+                &writer,
+                &filter,
+                &extractor,
+            )
+        }
+    };
+    let report_path = matches
+        .value_of("report")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| settings.infoboxes.report.clone());
+    info!("Writing infobox field frequencies to {:?}", report_path);
+    let mut writer = BufWriter::new(File::create(report_path)?);
+    report.write_tsv(&mut writer)?;
+    writer.flush()?;
+    timer.finish();
+    Ok(())
+}
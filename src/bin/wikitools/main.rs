@@ -0,0 +1,70 @@
+mod anchors;
+mod args;
+mod build;
+mod categories;
+mod category_tree;
+mod get_page;
+mod index;
+mod indices;
+mod infoboxes;
+mod pages;
+mod query;
+mod redirects;
+mod repl;
+mod tag;
+mod template_usage;
+mod templates;
+
+use clap::{App, AppSettings};
+use env_logger;
+use log::debug;
+
+fn main() -> Result<(), Box<std::error::Error>> {
+    env_logger::init();
+
+    let app = App::new("wikitools")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Tools for extracting data from Wikipedia dumps")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(args::config())
+        .subcommand(build::subcommand())
+        .subcommand(build::plan_subcommand())
+        .subcommand(indices::subcommand())
+        .subcommand(templates::subcommand())
+        .subcommand(anchors::subcommand())
+        .subcommand(redirects::subcommand())
+        .subcommand(pages::subcommand())
+        .subcommand(infoboxes::subcommand())
+        .subcommand(categories::subcommand())
+        .subcommand(category_tree::subcommand())
+        .subcommand(template_usage::subcommand())
+        .subcommand(get_page::subcommand())
+        .subcommand(index::subcommand())
+        .subcommand(query::subcommand())
+        .subcommand(tag::subcommand())
+        .get_matches();
+
+    let (name, matches) = app.subcommand();
+    let matches = matches.expect("subcommand is required");
+    let settings = args::load_settings(matches)?;
+    debug!("settings: {:#?}", settings);
+
+    match name {
+        "build" => build::run(&settings, matches, false),
+        "plan" => build::run(&settings, matches, true),
+        "indices" => indices::run(&settings, matches),
+        "templates" => templates::run(&settings, matches),
+        "anchors" => anchors::run(&settings, matches),
+        "redirects" => redirects::run(&settings, matches),
+        "pages" => pages::run(&settings, matches),
+        "infoboxes" => infoboxes::run(&settings, matches),
+        "categories" => categories::run(&settings, matches),
+        "category-tree" => category_tree::run(&settings, matches),
+        "template-usage" => template_usage::run(&settings, matches),
+        "get-page" => get_page::run(&settings, matches),
+        "index" => index::run(&settings, matches),
+        "query" => query::run(&settings, matches),
+        "tag" => tag::run(&settings, matches),
+        _ => unreachable!(),
+    }
+}
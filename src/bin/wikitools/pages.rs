@@ -0,0 +1,136 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use log::info;
+
+use storage::page::writer::{
+    AnchorWriterJSONL, AnchorWriterTSV, CategoryWriterJSONL, CategoryWriterTSV,
+};
+use storage::page::{LinkParser, PageFilter};
+use wikitools::extract::{
+    extract_annotated, extract_pages_json, extract_plaintext, extract_with_writer,
+};
+use wikitools::indices::Indices;
+use wikitools::loaders::{
+    build_or_load_page_indices, load_link_parser, load_page_filter, load_site_info,
+};
+use wikitools::pipeline::{Pipeline, Stage};
+use wikitools::redirect::RedirectMap;
+use wikitools::settings::Settings;
+use wikitools::utils::{mutex_bufwriter, Timer};
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("pages")
+        .about("Extract page data from the dump")
+        .arg(args::dump())
+        .arg(args::index())
+        .arg(args::indices())
+        .arg(args::filter())
+        .arg(args::redirect_map())
+        .arg(args::output("Output path; writes to stdout if absent"))
+        .arg(
+            Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&[
+                    "json",
+                    "anchors-tsv",
+                    "anchors-jsonl",
+                    "categories-tsv",
+                    "categories-jsonl",
+                    "text-jsonl",
+                    "annotated-jsonl",
+                ])
+                .default_value("json")
+                .help("Output format"),
+        )
+        .arg(
+            Arg::with_name("resolve-redirects")
+                .long("resolve-redirects")
+                .help("Resolve link targets through the redirect map; annotated-jsonl only"),
+        )
+}
+
+/// Write pages in the given format.
+fn write_pages<W: Write + Send + Sync>(
+    format: &str,
+    indices: &Indices,
+    data: &Path,
+    writer: &Mutex<W>,
+    filter: &Arc<PageFilter>,
+    links: &Arc<LinkParser>,
+    redirects: Option<&RedirectMap>,
+) {
+    match format {
+        "json" => extract_pages_json(indices, data, writer, filter, links),
+        "anchors-tsv" => extract_with_writer(AnchorWriterTSV, indices, data, writer, filter, links),
+        "anchors-jsonl" => {
+            extract_with_writer(AnchorWriterJSONL, indices, data, writer, filter, links)
+        }
+        "categories-tsv" => {
+            extract_with_writer(CategoryWriterTSV, indices, data, writer, filter, links)
+        }
+        "categories-jsonl" => {
+            extract_with_writer(CategoryWriterJSONL, indices, data, writer, filter, links)
+        }
+        "text-jsonl" => extract_plaintext(indices, data, writer, filter, links),
+        "annotated-jsonl" => extract_annotated(indices, data, writer, filter, links, redirects),
+        _ => unreachable!(),
+    }
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let format = matches.value_of("format").unwrap();
+    let page_indices = build_or_load_page_indices(settings)?;
+    let site = load_site_info(settings);
+    let filter = load_page_filter(settings, &site)?;
+    let links = load_link_parser(settings, &site)?;
+    let redirects = if matches.is_present("resolve-redirects") {
+        Pipeline::new(settings)?
+            .with_site_info(&site)
+            .build(&[Stage::RedirectMap], &[])?;
+        Some(RedirectMap::new(
+            &settings.redirect_map,
+            links.titles().clone(),
+        )?)
+    } else {
+        None
+    };
+
+    let mut timer = Timer::new();
+    match matches.value_of("output") {
+        Some(output) => {
+            info!("Writing pages as {} to {}", format, output);
+            let writer = mutex_bufwriter(output, 8192 * 16)?;
+            write_pages(
+                format,
+                &page_indices,
+                &settings.data.dump,
+                &writer,
+                &filter,
+                &links,
+                redirects.as_ref(),
+            );
+            writer.into_inner().unwrap().flush()?;
+        }
+        None => {
+            let writer = Mutex::new(io::stdout());
+            write_pages(
+                format,
+                &page_indices,
+                &settings.data.dump,
+                &writer,
+                &filter,
+                &links,
+                redirects.as_ref(),
+            );
+        }
+    }
+    timer.finish();
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use std::path::Path;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use qp_trie::wrapper::BStr;
+
+use storage::fst::WikiAnchors;
+use storage::link_probability::LinkProbabilities;
+use storage::page::TitleNormaliser;
+use storage::qpt::read_from_qpt;
+use storage::tantivy::TantivyWikiIndex;
+use wikitools::loaders::{load_anchors, load_site_info};
+use wikitools::redirect::RedirectMap;
+use wikitools::settings::Settings;
+
+use crate::args;
+use crate::repl::Repl;
+
+/// Validate path args.
+fn is_path(path: String) -> Result<(), String> {
+    if Path::new(&path).exists() {
+        return Ok(());
+    }
+    Err(format!("{} is not a valid path", path))
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("query")
+        .about("Run queries over built artefacts")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("fst")
+                .about("Run queries over the anchor FST; starts an interactive session if no query is given")
+                .arg(args::fst().validator(is_path))
+                .arg(args::titles())
+                .arg(
+                    Arg::with_name("query")
+                        .short("q")
+                        .long("query")
+                        .takes_value(true)
+                        .help("Query to return results for"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tantivy")
+                .about("Run keyphrase-count queries over the search index")
+                .arg(args::index_dir())
+                .arg(
+                    Arg::with_name("query")
+                        .index(1)
+                        .required(true)
+                        .help("Query to return results for"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("lp")
+                .about("Fetch link counts and link probability for surface forms")
+                .arg(
+                    Arg::with_name("surface_forms")
+                        .index(1)
+                        .required(true)
+                        .multiple(true)
+                        .help("Surface forms to return results for"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("redirect")
+                .about("Resolve titles through the redirect map")
+                .arg(args::redirect_map())
+                .arg(
+                    Arg::with_name("titles")
+                        .index(1)
+                        .required(true)
+                        .multiple(true)
+                        .help("Titles to resolve"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("qpt")
+                .about("Fetch entity counts for a surface form from a .qpt anchor trie")
+                .arg(
+                    Arg::with_name("qpt_path")
+                        .index(1)
+                        .required(true)
+                        .validator(is_path)
+                        .help("Path to .qpt file"),
+                )
+                .arg(
+                    Arg::with_name("query")
+                        .index(2)
+                        .required(true)
+                        .help("Surface form to return results for"),
+                ),
+        )
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let (name, matches) = matches.subcommand();
+    let matches = matches.expect("subcommand is required");
+    let mut settings = settings.clone();
+    args::apply_overrides(&mut settings, matches);
+
+    match name {
+        "fst" => query_fst(&settings, matches),
+        "tantivy" => query_tantivy(&settings, matches),
+        "lp" => query_link_probability(&settings, matches),
+        "redirect" => query_redirect(&settings, matches),
+        "qpt" => query_qpt(matches),
+        _ => unreachable!(),
+    }
+}
+
+/// Fetch the result of one query from the FST.
+fn fetch_one(anchors: &WikiAnchors, query: &str) -> Result<(), Box<std::error::Error>> {
+    println!("searching...");
+    for (key, count) in anchors.search(&format!("{}\t.*", query))? {
+        println!("{}\t{}", key, count);
+    }
+    Ok(())
+}
+
+/// Start an interactive session over the FST.
+fn fetch_interactive(anchors: &WikiAnchors) -> Result<(), Box<std::error::Error>> {
+    Repl::new(anchors).run()
+}
+
+fn query_fst(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let anchors = load_anchors(settings)?;
+    match matches.value_of("query") {
+        Some(query) => fetch_one(&anchors, query),
+        None => fetch_interactive(&anchors),
+    }
+}
+
+fn query_tantivy(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let query = matches.value_of("query").unwrap();
+    let titles = TitleNormaliser::from_site_info(&load_site_info(settings));
+    let index = TantivyWikiIndex::new(&settings.search_index.index_dir, titles);
+    let count = index.count_matches_for_query(query);
+    println!("Got {} matches", count);
+    Ok(())
+}
+
+fn query_link_probability(
+    settings: &Settings,
+    matches: &ArgMatches,
+) -> Result<(), Box<std::error::Error>> {
+    let table = LinkProbabilities::new(&settings.anchors.link_probability)?;
+    for surface in matches.values_of("surface_forms").unwrap() {
+        let surface = surface.to_lowercase();
+        let counts = table.get(&surface).unwrap_or_default();
+        println!(
+            "{}\t{}\t{}\t{:.4}",
+            surface,
+            counts.links,
+            counts.docs,
+            counts.link_probability()
+        );
+    }
+    Ok(())
+}
+
+fn query_redirect(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let titles = TitleNormaliser::from_site_info(&load_site_info(settings));
+    let redirects = RedirectMap::new(&settings.redirect_map, titles)?;
+    for title in matches.values_of("titles").unwrap() {
+        match redirects.get(title) {
+            Some(target) => println!("{}\t{}", title, target),
+            None => println!("{}\t-", title),
+        }
+    }
+    Ok(())
+}
+
+fn query_qpt(matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let anchor_counts = read_from_qpt(matches.value_of("qpt_path").unwrap(), None)?;
+    let query = format!("{}\t", matches.value_of("query").unwrap().to_lowercase());
+
+    let prefix = AsRef::<BStr>::as_ref(query.as_str());
+    let mut ret = anchor_counts
+        .iter_prefix(prefix)
+        .map(|(key, value)| {
+            let key = key.as_str();
+            (key[key.find('\t').unwrap_or(0) + 1..].to_string(), value)
+        })
+        .collect::<Vec<_>>();
+    ret.sort_by(|(_, v1), (_, v2)| v1.partial_cmp(v2).unwrap());
+    for (key, val) in ret {
+        println!("{} . {}", val, key);
+    }
+    Ok(())
+}
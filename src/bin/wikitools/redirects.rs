@@ -0,0 +1,41 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use storage::page::TitleNormaliser;
+use wikitools::indices::read_titles;
+use wikitools::loaders::load_site_info;
+use wikitools::pipeline::{Pipeline, Stage};
+use wikitools::redirect::{read_redirects, resolve_redirects};
+use wikitools::settings::Settings;
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("redirects")
+        .about("Write all redirects in the dump as tab-separated pairs, and resolve them to a FST")
+        .arg(args::dump())
+        .arg(args::index())
+        .arg(args::indices())
+        .arg(args::redirect_map())
+        .arg(args::output(
+            "Path to the redirects file; overrides redirects",
+        ))
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .help("Print counts of double, broken and cyclic redirects instead of building"),
+        )
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let mut settings = settings.clone();
+    settings.redirects = args::output_or(matches, &settings.redirects);
+    if matches.is_present("stats") {
+        let normaliser = TitleNormaliser::from_site_info(&load_site_info(&settings));
+        let redirects = read_redirects(&settings.redirects, normaliser)?;
+        let titles = read_titles(&settings.data.index)?;
+        println!("{}", resolve_redirects(&redirects, Some(&titles)).stats);
+        return Ok(());
+    }
+    let stages = [Stage::Redirects, Stage::RedirectMap];
+    Pipeline::new(&settings)?.build(&stages, &stages)
+}
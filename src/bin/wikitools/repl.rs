@@ -0,0 +1,167 @@
+use std::io::{self, BufRead, Write};
+
+use storage::fst::WikiAnchors;
+use storage::surface_form::Source;
+
+static HELP: &str = "\
+Commands:
+  <surface form>              entities linked by a surface form; same as :exact
+  :exact <surface form>       entities linked by a surface form, with counts
+  :top <k> <surface form>     the k most frequently linked entities
+  :common <surface form>      entities with their commonness
+  :prefix <prefix>            surface forms beginning with a prefix
+  :regex <pattern>            surface forms matching a regular expression
+  :fuzzy <d> <surface form>   surface forms within Levenshtein distance d
+  :limit <n>                  show at most n surface forms for searches
+  :help                       show this message
+  :quit                       exit";
+
+/// Interactive session over an anchor FST.
+pub struct Repl<'a> {
+    anchors: &'a WikiAnchors,
+    limit: usize,
+}
+
+/// Split a leading numeric argument from the rest of a command.
+fn split_count(args: &str) -> Result<(usize, &str), String> {
+    let args = args.trim();
+    let (count, rest) = match args.find(char::is_whitespace) {
+        Some(index) => (&args[..index], args[index..].trim()),
+        None => (args, ""),
+    };
+    let count = count
+        .parse::<usize>()
+        .map_err(|_| format!("expected a number, got `{}`", count))?;
+    if rest.is_empty() {
+        return Err("expected a surface form".to_owned());
+    }
+    Ok((count, rest))
+}
+
+impl<'a> Repl<'a> {
+    pub fn new(anchors: &'a WikiAnchors) -> Self {
+        Repl { anchors, limit: 20 }
+    }
+
+    /// Read and evaluate commands from stdin until EOF or `:quit`.
+    pub fn run(&mut self) -> Result<(), Box<std::error::Error>> {
+        println!("Type :help for a list of commands.");
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            print!("> ");
+            io::stdout().flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => break,
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == ":quit" || line == ":q" {
+                break;
+            }
+            if let Err(e) = self.eval(line) {
+                println!("error: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluate one command.
+    fn eval(&mut self, line: &str) -> Result<(), Box<std::error::Error>> {
+        if !line.starts_with(':') {
+            return self.top(usize::max_value(), line);
+        }
+        let (command, args) = match line.find(char::is_whitespace) {
+            Some(index) => (&line[..index], line[index..].trim()),
+            None => (line, ""),
+        };
+        match command {
+            ":help" | ":h" => println!("{}", HELP),
+            ":exact" => self.top(usize::max_value(), args)?,
+            ":top" => {
+                let (k, surface) = split_count(args)?;
+                self.top(k, surface)?;
+            }
+            ":common" => self.commonness(args)?,
+            ":prefix" => {
+                let found = self
+                    .anchors
+                    .surface_forms_with_prefix(&args.to_lowercase(), self.limit);
+                self.print_surface_forms(&found);
+            }
+            ":regex" => {
+                let found = self.anchors.surface_forms_matching(args, self.limit)?;
+                self.print_surface_forms(&found);
+            }
+            ":fuzzy" => {
+                let (distance, surface) = split_count(args)?;
+                let found = self.anchors.surface_forms_within(
+                    &surface.to_lowercase(),
+                    distance as u32,
+                    self.limit,
+                )?;
+                self.print_surface_forms(&found);
+            }
+            ":limit" => {
+                self.limit = args.parse::<usize>()?;
+                println!("Showing at most {} surface forms", self.limit);
+            }
+            _ => println!(
+                "Unknown command `{}`; type :help for a list of commands.",
+                command
+            ),
+        }
+        Ok(())
+    }
+
+    /// Print up to `k` entities for a surface form, by descending count.
+    fn top(&self, k: usize, surface: &str) -> Result<(), Box<std::error::Error>> {
+        let found = self.anchors.entities_for_query(&surface.to_lowercase())?;
+        if found.is_empty() {
+            println!("No entities for `{}`", surface);
+            return Ok(());
+        }
+        let mut entities = found.anchors.iter().collect::<Vec<_>>();
+        entities.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+        for (entity, count) in entities.into_iter().take(k) {
+            println!("{}\t{}", count, entity);
+        }
+        println!("-- {} links in total", found.wiki_occurrences());
+        for source in Source::title_sources() {
+            if let Some(matches) = found.matches.get(source) {
+                let mut entities = matches.keys().map(String::as_str).collect::<Vec<_>>();
+                entities.sort();
+                println!("-- {}: {}", source.name(), entities.join(", "));
+            }
+        }
+        Ok(())
+    }
+
+    /// Print the commonness of every entity for a surface form, combined over
+    /// all sources.
+    fn commonness(&self, surface: &str) -> Result<(), Box<std::error::Error>> {
+        let found = self.anchors.entities_for_query(&surface.to_lowercase())?;
+        if found.is_empty() {
+            println!("No entities for `{}`", surface);
+            return Ok(());
+        }
+        let mut entities = found.get_all_wiki_matches().into_iter().collect::<Vec<_>>();
+        entities.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+        for (entity, commonness) in entities {
+            println!("{:.4}\t{}", commonness, entity);
+        }
+        Ok(())
+    }
+
+    fn print_surface_forms(&self, found: &[(String, u64)]) {
+        for (surface, count) in found {
+            println!("{}\t{}", count, surface);
+        }
+        if found.len() == self.limit {
+            println!("-- showing first {}; use :limit to show more", self.limit);
+        }
+    }
+}
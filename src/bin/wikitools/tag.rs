@@ -0,0 +1,64 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use log::info;
+
+use storage::page::TitleNormaliser;
+use storage::tantivy::TantivyWikiIndex;
+use tagme::{SurfaceFormSource, TagMeQuery};
+use wikitools::loaders::{load_anchors, load_site_info};
+use wikitools::settings::Settings;
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("tag")
+        .about("Annotate text with Wikipedia entities using TagMe")
+        .arg(args::fst())
+        .arg(args::titles())
+        .arg(args::index_dir())
+        .arg(
+            Arg::with_name("epsilon")
+                .short("e")
+                .long("epsilon")
+                .takes_value(true)
+                .default_value("0.3")
+                .help("Disambiguation tolerance; candidates within this fraction of the best vote are ranked by commonness"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Write annotations as JSON"),
+        )
+        .arg(
+            Arg::with_name("text")
+                .index(1)
+                .required(true)
+                .help("Text to annotate"),
+        )
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let text = matches.value_of("text").unwrap();
+    let epsilon = matches.value_of("epsilon").unwrap().parse::<f32>()?;
+
+    let map = load_anchors(settings)?;
+    info!(
+        "Loading search index from {:?}",
+        settings.search_index.index_dir
+    );
+    let titles = TitleNormaliser::from_site_info(&load_site_info(settings));
+    let index = TantivyWikiIndex::new(&settings.search_index.index_dir, titles);
+
+    let mut qry = TagMeQuery::new(text, epsilon, SurfaceFormSource::Wiki);
+    let annotations = qry.parse(&map, &index)?;
+    if matches.is_present("json") {
+        println!("{}", serde_json::to_string(annotations)?);
+        return Ok(());
+    }
+    for annotation in annotations {
+        println!(
+            "{}\t{}\t{}\t{}\t{:.3}",
+            annotation.start, annotation.end, annotation.spot, annotation.entity, annotation.rho
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use log::info;
+
+use storage::page::TemplateParser;
+use wikitools::loaders::{
+    build_or_load_page_indices, load_page_filter, load_site_info, load_templates,
+};
+use wikitools::settings::Settings;
+use wikitools::template::{count_template_usage, TemplateGraph};
+use wikitools::utils::{mutex_bufwriter, Timer};
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("template-usage")
+        .about("Count the pages transcluding each template and graph the transclusions between templates")
+        .arg(args::dump())
+        .arg(args::index())
+        .arg(args::indices())
+        .arg(args::filter())
+        .arg(
+            Arg::with_name("usage")
+                .long("usage")
+                .takes_value(true)
+                .help("Path to the usage counts; overrides template_usage.usage"),
+        )
+        .arg(
+            Arg::with_name("graph")
+                .long("graph")
+                .takes_value(true)
+                .help("Path to the transclusion edge list; overrides template_usage.graph"),
+        )
+        .arg(
+            Arg::with_name("pages")
+                .long("pages")
+                .takes_value(true)
+                .help("Path to the templates used by each page; overrides template_usage.pages"),
+        )
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let page_indices = build_or_load_page_indices(settings)?;
+    let site = load_site_info(settings);
+    let filter = load_page_filter(settings, &site)?;
+    let templates = load_templates(settings, &site)?;
+    let parser = TemplateParser::new(templates.titles().clone());
+
+    let mut timer = Timer::new();
+    let graph = TemplateGraph::build(&templates, &parser);
+    let graph_path = matches
+        .value_of("graph")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| settings.template_usage.graph.clone());
+    info!("Writing template transclusions to {:?}", graph_path);
+    let mut writer = BufWriter::new(File::create(graph_path)?);
+    graph.write_edges(&mut writer)?;
+    writer.flush()?;
+
+    let pages_path = matches
+        .value_of("pages")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| settings.template_usage.pages.clone());
+    info!("Writing templates used by each page to {:?}", pages_path);
+    let writer = mutex_bufwriter(&pages_path, 8192 * 16)?;
+    let usage = count_template_usage(
+        &page_indices,
+        &settings.data.dump,
+        &writer,
+        &filter,
+        &templates,
+        &parser,
+    );
+    writer.into_inner().unwrap().flush()?;
+    let usage_path = matches
+        .value_of("usage")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| settings.template_usage.usage.clone());
+    info!("Writing template usage to {:?}", usage_path);
+    let mut writer = BufWriter::new(File::create(usage_path)?);
+    usage.write_tsv(&mut writer, &graph)?;
+    writer.flush()?;
+    timer.finish();
+    Ok(())
+}
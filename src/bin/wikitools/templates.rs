@@ -0,0 +1,23 @@
+use clap::{App, ArgMatches, SubCommand};
+
+use wikitools::pipeline::{Pipeline, Stage};
+use wikitools::settings::Settings;
+
+use crate::args;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("templates")
+        .about("Compile all template pages in the dump to a pseudo-XML file")
+        .arg(args::dump())
+        .arg(args::index())
+        .arg(args::template_indices())
+        .arg(args::output(
+            "Path to the templates file; overrides templates",
+        ))
+}
+
+pub fn run(settings: &Settings, matches: &ArgMatches) -> Result<(), Box<std::error::Error>> {
+    let mut settings = settings.clone();
+    settings.templates = args::output_or(matches, &settings.templates);
+    Pipeline::new(&settings)?.build(&[Stage::Templates], &[Stage::Templates])
+}
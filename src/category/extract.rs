@@ -0,0 +1,243 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use pbr;
+use rayon::prelude::*;
+
+use storage::page::{
+    category::{default_sort_key, is_hidden},
+    namespace::{CATEGORY, MAIN},
+    Category, LinkParser, PageFilter, PageIterator, TantivyPageIterator, TitleNormaliser,
+};
+
+use crate::indices::Indices;
+use crate::template::{Expander, Templates};
+use crate::utils::open_seek_bzip;
+
+/// Categories of a page, or the parent categories of a category page.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PageCategories {
+    pub id: String,
+    pub title: String,
+    /// Namespace ID.
+    pub ns: i32,
+    pub categories: Vec<Category>,
+    /// Whether the page is a category hidden with `__HIDDENCAT__`.
+    pub hidden: bool,
+}
+
+impl PageCategories {
+    /// Name of the category, without its namespace prefix, if the page is a
+    /// category page.
+    pub fn category_name(&self) -> Option<&str> {
+        category_name(self.ns, &self.title)
+    }
+
+    /// Write tab-separated `id, ns, title, category, sort key, template` rows,
+    /// one per category. The sort key is empty for pages sorted by title, and
+    /// `template` is `true` for categories added by templates.
+    pub fn write_tsv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for category in &self.categories {
+            let sort_key = category.sort_key.as_ref().map_or("", |key| key.as_str());
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                self.id,
+                self.ns,
+                self.title,
+                category.name,
+                sort_key.replace(&['\t', '\n'][..], " "),
+                category.from_template
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Name of a category page, without its namespace prefix.
+pub(crate) fn category_name(ns: i32, title: &str) -> Option<&str> {
+    if ns != CATEGORY {
+        return None;
+    }
+    title.find(':').map(|index| &title[index + 1..])
+}
+
+/// Finds the categories of pages, and whether category pages are hidden.
+pub struct CategoryExtractor<'a> {
+    links: Arc<LinkParser>,
+    titles: TitleNormaliser,
+    /// Expander for the templates of pages, if categories added by templates
+    /// are to be found.
+    expander: Option<Expander<'a>>,
+}
+
+impl<'a> CategoryExtractor<'a> {
+    /// Create an extractor finding the categories in the text of pages only.
+    ///
+    /// # Arguments
+    /// * `links` - Parser for category links.
+    /// * `titles` - Normaliser determining the namespace of page titles.
+    pub fn new(links: Arc<LinkParser>, titles: TitleNormaliser) -> Self {
+        CategoryExtractor {
+            links,
+            titles,
+            expander: None,
+        }
+    }
+
+    /// Also find the categories added by templates, and categories hidden by
+    /// templates such as `{{Hidden category}}`, by expanding the templates of
+    /// each page.
+    pub fn with_templates(mut self, templates: &'a Templates) -> Self {
+        self.expander = Some(Expander::new(templates));
+        self
+    }
+
+    /// Extract the categories of a page.
+    pub fn extract(&self, id: &str, title: &str, text: &str) -> PageCategories {
+        let ns = self.titles.parse(title).map_or(MAIN, |title| title.ns);
+        let mut categories = Category::parse(text, &self.links);
+        let mut hidden = is_hidden(text);
+        if let Some(ref expander) = self.expander {
+            let expanded = expander.expand(title, text);
+            hidden = hidden || is_hidden(&expanded);
+            // `{{DEFAULTSORT:...}}` expands to nothing, so is read from the page.
+            let default_key = default_sort_key(text);
+            for category in Category::parse(&expanded, &self.links) {
+                if categories.iter().any(|known| known.name == category.name) {
+                    continue;
+                }
+                categories.push(Category {
+                    sort_key: category
+                        .sort_key
+                        .or_else(|| default_key.map(|key| key.to_owned())),
+                    from_template: true,
+                    ..category
+                });
+            }
+        }
+        PageCategories {
+            id: id.to_owned(),
+            title: title.to_owned(),
+            ns,
+            categories,
+            hidden: hidden && ns == CATEGORY,
+        }
+    }
+}
+
+/// Extract the categories of pages and the parent categories of category
+/// pages, writing them as tab-separated rows, and return the names of hidden
+/// categories.
+///
+/// Category pages are read along with the pages accepted by `filter`.
+pub fn extract_categories<W: Write + Send + Sync>(
+    indices: &Indices,
+    data: &Path,
+    writer: &Mutex<W>,
+    filter: &Arc<PageFilter>,
+    extractor: &CategoryExtractor,
+) -> BTreeSet<String> {
+    let indices = indices.offsets();
+    let pbar = Mutex::new(pbr::ProgressBar::new(indices.len() as u64));
+    let hidden = Mutex::new(BTreeSet::new());
+
+    indices.par_iter().for_each(|index| {
+        let store = open_seek_bzip(data, *index).unwrap();
+        let pages = TantivyPageIterator(PageIterator::new(store).with_filter(filter.clone()))
+            .map(|(id, title, text)| extractor.extract(&id, &title, &text))
+            .collect::<Vec<_>>();
+        {
+            let mut w = writer.lock().unwrap();
+            pages.iter().for_each(|page| {
+                page.write_tsv(&mut *w).unwrap();
+            });
+        }
+        {
+            let mut hidden = hidden.lock().unwrap();
+            for page in pages.iter().filter(|page| page.hidden) {
+                hidden.extend(page.category_name().map(|name| name.to_owned()));
+            }
+        }
+        {
+            let mut prog_bar = pbar.lock().unwrap();
+            prog_bar.inc();
+        }
+    });
+    hidden.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_extract_categories() {
+        let mut templates = Templates::new(TitleNormaliser::default());
+        templates.insert(
+            "Template:Hidden category",
+            "<includeonly>__HIDDENCAT__[[Category:Hidden categories]]</includeonly>",
+        );
+        templates.insert(
+            "Template:Citation needed",
+            "<sup>citation needed</sup>[[Category:Articles with unsourced statements from {{{date|}}}]]",
+        );
+        let extractor =
+            CategoryExtractor::new(Arc::new(LinkParser::default()), TitleNormaliser::default())
+                .with_templates(&templates);
+
+        let page = extractor.extract(
+            "1",
+            "Orpheus",
+            "Orpheus{{citation needed|date=May 2019}} played the lyre.\
+             {{DEFAULTSORT:Orpheus, legendary}}[[Category:Greek gods|Orfeo]]",
+        );
+        assert_eq!(page.ns, MAIN);
+        assert!(!page.hidden);
+        assert_eq!(
+            page.categories,
+            vec![
+                Category {
+                    name: "Greek gods".to_owned(),
+                    sort_key: Some("Orfeo".to_owned()),
+                    from_template: false,
+                },
+                Category {
+                    name: "Articles with unsourced statements from May 2019".to_owned(),
+                    sort_key: Some("Orpheus, legendary".to_owned()),
+                    from_template: true,
+                },
+            ]
+        );
+
+        let page = extractor.extract(
+            "2",
+            "Category:Articles with unsourced statements",
+            "{{Hidden category}}[[Category:Wikipedia maintenance]]",
+        );
+        assert_eq!(
+            page.category_name(),
+            Some("Articles with unsourced statements")
+        );
+        assert!(page.hidden);
+        let mut tsv = vec![];
+        page.write_tsv(&mut tsv).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "2\t14\tCategory:Articles with unsourced statements\tWikipedia maintenance\t\tfalse\n\
+             2\t14\tCategory:Articles with unsourced statements\tHidden categories\t\ttrue\n"
+        );
+
+        let page =
+            CategoryExtractor::new(Arc::new(LinkParser::default()), TitleNormaliser::default())
+                .extract(
+                    "2",
+                    "Category:Articles with unsourced statements",
+                    "{{Hidden category}}",
+                );
+        assert!(!page.hidden);
+        assert!(page.categories.is_empty());
+    }
+}
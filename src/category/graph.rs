@@ -0,0 +1,294 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use storage::page::Category;
+
+use crate::category::extract::{category_name, PageCategories};
+
+/// Category memberships of pages, and the hierarchy of categories.
+///
+/// Pages are identified by title and categories by name, without their
+/// namespace prefix, so that `Category:Physics` is the category `Physics`.
+#[derive(Clone, Debug, Default)]
+pub struct CategoryGraph {
+    /// Categories of each page which is not a category.
+    page_categories: HashMap<String, Vec<Category>>,
+    /// Parent categories of each category.
+    parents: HashMap<String, Vec<Category>>,
+    /// Titles of the pages in each category, excluding subcategories.
+    pages: HashMap<String, Vec<String>>,
+    /// Names of the subcategories of each category.
+    subcategories: HashMap<String, Vec<String>>,
+    hidden: HashSet<String>,
+}
+
+impl CategoryGraph {
+    pub fn new() -> Self {
+        CategoryGraph::default()
+    }
+
+    /// Read a graph from the rows written by `PageCategories::write_tsv`, and
+    /// optionally a list of hidden categories, one name per line.
+    pub fn read<R: BufRead, H: BufRead>(rows: R, hidden: Option<H>) -> io::Result<Self> {
+        let mut graph = CategoryGraph::new();
+        for line in rows.lines() {
+            let line = line?;
+            let fields = line.split('\t').collect::<Vec<_>>();
+            if fields.len() < 6 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("expected 6 fields in category row: {:?}", line),
+                ));
+            }
+            let ns = fields[1]
+                .parse::<i32>()
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+            let category = Category {
+                name: fields[3].to_owned(),
+                sort_key: if fields[4].is_empty() {
+                    None
+                } else {
+                    Some(fields[4].to_owned())
+                },
+                from_template: fields[5] == "true",
+            };
+            graph.insert(ns, fields[2], category);
+        }
+        if let Some(hidden) = hidden {
+            for line in hidden.lines() {
+                let line = line?;
+                if !line.is_empty() {
+                    graph.hidden.insert(line);
+                }
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Open a graph from the files written by `wikitools categories`. The list
+    /// of hidden categories is optional, but must exist if given.
+    pub fn open<P: AsRef<Path>>(rows: P, hidden: Option<P>) -> io::Result<Self> {
+        let hidden = match hidden {
+            Some(path) => Some(BufReader::new(File::open(path)?)),
+            None => None,
+        };
+        CategoryGraph::read(BufReader::new(File::open(rows)?), hidden)
+    }
+
+    /// Add the categories of a page.
+    pub fn add(&mut self, page: &PageCategories) {
+        for category in &page.categories {
+            self.insert(page.ns, &page.title, category.clone());
+        }
+        if page.hidden {
+            if let Some(name) = page.category_name() {
+                self.hidden.insert(name.to_owned());
+            }
+        }
+    }
+
+    /// Add an edge from a page, or a category page, to one of its categories.
+    fn insert(&mut self, ns: i32, title: &str, category: Category) {
+        match category_name(ns, title) {
+            Some(name) => {
+                self.subcategories
+                    .entry(category.name.clone())
+                    .or_default()
+                    .push(name.to_owned());
+                self.parents
+                    .entry(name.to_owned())
+                    .or_default()
+                    .push(category);
+            }
+            None => {
+                self.pages
+                    .entry(category.name.clone())
+                    .or_default()
+                    .push(title.to_owned());
+                self.page_categories
+                    .entry(title.to_owned())
+                    .or_default()
+                    .push(category);
+            }
+        }
+    }
+
+    /// Mark a category as hidden.
+    pub fn hide(&mut self, name: &str) {
+        self.hidden.insert(name.to_owned());
+    }
+
+    /// Get the categories of a page.
+    pub fn categories(&self, title: &str) -> &[Category] {
+        self.page_categories
+            .get(title)
+            .map_or(&[], |categories| categories.as_slice())
+    }
+
+    /// Get the parent categories of a category.
+    pub fn parents(&self, name: &str) -> &[Category] {
+        self.parents
+            .get(name)
+            .map_or(&[], |parents| parents.as_slice())
+    }
+
+    /// Get the titles of the pages in a category, excluding subcategories.
+    pub fn pages(&self, name: &str) -> &[String] {
+        self.pages.get(name).map_or(&[], |pages| pages.as_slice())
+    }
+
+    /// Get the names of the subcategories of a category.
+    pub fn subcategories(&self, name: &str) -> &[String] {
+        self.subcategories
+            .get(name)
+            .map_or(&[], |subcategories| subcategories.as_slice())
+    }
+
+    /// Check if a category is hidden with `__HIDDENCAT__`.
+    pub fn is_hidden(&self, name: &str) -> bool {
+        self.hidden.contains(name)
+    }
+
+    /// Iterate over the names of hidden categories.
+    pub fn hidden(&self) -> impl Iterator<Item = &str> {
+        self.hidden.iter().map(|name| name.as_str())
+    }
+
+    /// Check if a category has pages, subcategories or parents.
+    pub fn contains(&self, name: &str) -> bool {
+        self.pages.contains_key(name)
+            || self.subcategories.contains_key(name)
+            || self.parents.contains_key(name)
+    }
+
+    /// Find the cycles in the hierarchy, as the sets of categories which are
+    /// each other's ancestors, with their names sorted.
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        // Tarjan's strongly connected components, iteratively, as the
+        // hierarchy is too deep to recurse through.
+        let mut names = self
+            .parents
+            .keys()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        let mut low: HashMap<&str, usize> = HashMap::new();
+        let mut stack = vec![];
+        let mut on_stack = HashSet::new();
+        let mut cycles = vec![];
+        for start in names {
+            if index.contains_key(start) {
+                continue;
+            }
+            // Categories being visited, with the position of the next parent.
+            let mut frames = vec![(start, 0)];
+            while let Some(&(name, position)) = frames.last() {
+                if position == 0 && !index.contains_key(name) {
+                    index.insert(name, index.len());
+                    low.insert(name, index[name]);
+                    stack.push(name);
+                    on_stack.insert(name);
+                }
+                let parents = self.parents(name);
+                if position < parents.len() {
+                    frames.last_mut().unwrap().1 += 1;
+                    let parent = parents[position].name.as_str();
+                    if !index.contains_key(parent) {
+                        frames.push((parent, 0));
+                    } else if on_stack.contains(parent) {
+                        let lowest = low[name].min(index[parent]);
+                        low.insert(name, lowest);
+                    }
+                    continue;
+                }
+                frames.pop();
+                if let Some(&(child, _)) = frames.last() {
+                    let lowest = low[child].min(low[name]);
+                    low.insert(child, lowest);
+                }
+                if low[name] == index[name] {
+                    let mut component = vec![];
+                    loop {
+                        let member = stack.pop().unwrap();
+                        on_stack.remove(member);
+                        component.push(member);
+                        if member == name {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 || parents.iter().any(|parent| parent.name == name) {
+                        component.sort();
+                        cycles.push(component);
+                    }
+                }
+            }
+        }
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use storage::page::namespace::{CATEGORY, MAIN};
+
+    #[test]
+    fn test_category_graph() {
+        let category = |name: &str, sort_key: Option<&str>, from_template| Category {
+            name: name.to_owned(),
+            sort_key: sort_key.map(|key| key.to_owned()),
+            from_template,
+        };
+        let pages = vec![
+            PageCategories {
+                id: "1".to_owned(),
+                title: "Orpheus".to_owned(),
+                ns: MAIN,
+                categories: vec![
+                    category("Greek gods", Some("Orfeo"), false),
+                    category("Articles with unsourced statements", None, true),
+                ],
+                hidden: false,
+            },
+            PageCategories {
+                id: "2".to_owned(),
+                title: "Category:Greek gods".to_owned(),
+                ns: CATEGORY,
+                categories: vec![category("Greek mythology", Some(" Gods"), false)],
+                hidden: false,
+            },
+            PageCategories {
+                id: "3".to_owned(),
+                title: "Category:Articles with unsourced statements".to_owned(),
+                ns: CATEGORY,
+                categories: vec![category("Hidden categories", None, true)],
+                hidden: true,
+            },
+        ];
+        let mut tsv = vec![];
+        for page in &pages {
+            page.write_tsv(&mut tsv).unwrap();
+        }
+        let hidden = "Articles with unsourced statements\n";
+        let read = CategoryGraph::read(&tsv[..], Some(hidden.as_bytes())).unwrap();
+        let mut added = CategoryGraph::new();
+        pages.iter().for_each(|page| added.add(page));
+
+        for graph in &[read, added] {
+            assert_eq!(graph.categories("Orpheus"), &pages[0].categories[..]);
+            assert_eq!(graph.pages("Greek gods"), &["Orpheus".to_owned()]);
+            assert_eq!(
+                graph.subcategories("Greek mythology"),
+                &["Greek gods".to_owned()]
+            );
+            assert_eq!(graph.parents("Greek gods"), &pages[1].categories[..]);
+            assert!(graph.is_hidden("Articles with unsourced statements"));
+            assert!(!graph.is_hidden("Greek gods"));
+            assert!(graph.contains("Hidden categories"));
+            assert!(graph.categories("Greek gods").is_empty());
+        }
+    }
+}
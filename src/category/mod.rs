@@ -0,0 +1,9 @@
+pub mod extract;
+pub mod graph;
+pub mod traverse;
+
+pub use self::{
+    extract::{extract_categories, CategoryExtractor, PageCategories},
+    graph::CategoryGraph,
+    traverse::{Node, Order, Traversal, Visit},
+};
@@ -0,0 +1,472 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::category::graph::CategoryGraph;
+
+/// Order in which a traversal visits categories.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Order {
+    /// Nearer categories first, so that depths are shortest distances.
+    BreadthFirst,
+    /// Each category followed by those reached through it, as in a tree view.
+    DepthFirst,
+}
+
+/// A page or category from which to find ancestors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Node<'a> {
+    /// A page, by title.
+    Page(&'a str),
+    /// A category, by name without its namespace prefix.
+    Category(&'a str),
+}
+
+/// A category reached by a traversal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Visit<'a> {
+    pub name: &'a str,
+    /// Number of edges followed from the start: 0 for the root of a subtree,
+    /// and 1 for the categories of a page or the parents of a category.
+    pub depth: usize,
+    /// Category through which this one was reached, if any.
+    pub via: Option<&'a str>,
+}
+
+/// Bounded traversal of the category hierarchy.
+///
+/// The hierarchy has cycles, so each category is visited once, at the first
+/// depth at which it is found.
+#[derive(Clone, Copy, Debug)]
+pub struct Traversal<'g> {
+    graph: &'g CategoryGraph,
+    max_depth: Option<usize>,
+    order: Order,
+    hidden: bool,
+}
+
+impl CategoryGraph {
+    /// Traverse the graph, breadth first and without a depth limit unless
+    /// configured otherwise.
+    pub fn traverse(&self) -> Traversal {
+        Traversal {
+            graph: self,
+            max_depth: None,
+            order: Order::BreadthFirst,
+            hidden: true,
+        }
+    }
+}
+
+impl<'g> Traversal<'g> {
+    /// Follow at most `max_depth` edges from the start.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Visit categories in the given order.
+    pub fn with_order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Skip categories hidden with `__HIDDENCAT__`, such as maintenance
+    /// categories, and those reached only through them.
+    pub fn without_hidden(mut self) -> Self {
+        self.hidden = false;
+        self
+    }
+
+    /// Visit a category and its subcategories.
+    pub fn descendants<'a>(&self, root: &'a str) -> Vec<Visit<'a>>
+    where
+        'g: 'a,
+    {
+        let graph = self.graph;
+        let start = Visit {
+            name: root,
+            depth: 0,
+            via: None,
+        };
+        self.walk(vec![start], |name| {
+            graph
+                .subcategories(name)
+                .iter()
+                .map(|name| name.as_str())
+                .collect()
+        })
+    }
+
+    /// Find the pages in a category and its subcategories, e.g. all articles
+    /// under `Physics` to depth 3, with the category through which each page
+    /// is nearest the root. Pages are one level deeper than their category.
+    pub fn pages<'a>(&self, root: &'a str) -> Vec<Visit<'a>>
+    where
+        'g: 'a,
+    {
+        let graph = self.graph;
+        // Depth first, a page may be found through a deeper category before a
+        // shallower one, so keep the shallowest visit in place of the first.
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        let mut pages: Vec<Visit> = vec![];
+        for category in self.descendants(root) {
+            if self.at_limit(category.depth) {
+                continue;
+            }
+            for title in graph.pages(category.name) {
+                let visit = Visit {
+                    name: title,
+                    depth: category.depth + 1,
+                    via: Some(category.name),
+                };
+                match positions.get(title.as_str()) {
+                    Some(&position) if pages[position].depth <= visit.depth => {}
+                    Some(&position) => pages[position] = visit,
+                    None => {
+                        positions.insert(title, pages.len());
+                        pages.push(visit);
+                    }
+                }
+            }
+        }
+        pages
+    }
+
+    /// Visit the categories of a page, or the parents of a category, and their
+    /// ancestors.
+    pub fn ancestors<'a>(&self, node: Node<'a>) -> Vec<Visit<'a>>
+    where
+        'g: 'a,
+    {
+        let graph = self.graph;
+        let (categories, via) = match node {
+            Node::Page(title) => (graph.categories(title), None),
+            Node::Category(name) => (graph.parents(name), Some(name)),
+        };
+        let starts = categories
+            .iter()
+            .map(|category| Visit {
+                name: &category.name,
+                depth: 1,
+                via,
+            })
+            .collect();
+        self.walk(starts, |name| {
+            graph
+                .parents(name)
+                .iter()
+                .map(|parent| parent.name.as_str())
+                .collect()
+        })
+    }
+
+    /// Find a shortest path from a page or category up to any of `roots`,
+    /// returning the categories along it, ending with the root reached. The
+    /// path from a category starts with the category itself.
+    pub fn path<'a>(&self, node: Node<'a>, roots: &[&str]) -> Option<Vec<Visit<'a>>>
+    where
+        'g: 'a,
+    {
+        if let Node::Category(name) = node {
+            if roots.contains(&name) {
+                return Some(vec![Visit {
+                    name,
+                    depth: 0,
+                    via: None,
+                }]);
+            }
+        }
+        // Depths are only shortest distances when visited breadth first.
+        let visits = self.with_order(Order::BreadthFirst).ancestors(node);
+        let end = *visits.iter().find(|visit| roots.contains(&visit.name))?;
+        let vias = visits
+            .iter()
+            .map(|visit| (visit.name, *visit))
+            .collect::<HashMap<_, _>>();
+        let mut path = vec![end];
+        let mut visit = end;
+        while let Some(via) = visit.via {
+            visit = match vias.get(via) {
+                Some(previous) if previous.depth < visit.depth => *previous,
+                // The category the path started from.
+                _ => Visit {
+                    name: via,
+                    depth: 0,
+                    via: None,
+                },
+            };
+            path.push(visit);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn accepts(&self, name: &str) -> bool {
+        self.hidden || !self.graph.is_hidden(name)
+    }
+
+    fn at_limit(&self, depth: usize) -> bool {
+        self.max_depth.map_or(false, |max| depth >= max)
+    }
+
+    /// Visit the categories reachable from `starts` through the edges given by
+    /// `next`, each once.
+    fn walk<'a, F>(&self, starts: Vec<Visit<'a>>, next: F) -> Vec<Visit<'a>>
+    where
+        F: Fn(&'a str) -> Vec<&'a str>,
+    {
+        let starts = starts
+            .into_iter()
+            .filter(|start| self.max_depth.map_or(true, |max| start.depth <= max))
+            .filter(|start| self.accepts(start.name));
+        let mut visits = vec![];
+        match self.order {
+            Order::BreadthFirst => {
+                let mut seen = HashSet::new();
+                let mut queue = VecDeque::new();
+                for start in starts {
+                    if seen.insert(start.name) {
+                        queue.push_back(start);
+                    }
+                }
+                while let Some(visit) = queue.pop_front() {
+                    visits.push(visit);
+                    if self.at_limit(visit.depth) {
+                        continue;
+                    }
+                    for name in next(visit.name) {
+                        if self.accepts(name) && seen.insert(name) {
+                            queue.push_back(Visit {
+                                name,
+                                depth: visit.depth + 1,
+                                via: Some(visit.name),
+                            });
+                        }
+                    }
+                }
+            }
+            Order::DepthFirst => {
+                // Categories first found through a longer path are expanded
+                // again if a shorter one is found, updating their depth and
+                // the category they were reached through, so that none within
+                // the depth limit are missed.
+                let mut positions: HashMap<&str, usize> = HashMap::new();
+                let mut stack = starts.collect::<Vec<_>>();
+                stack.reverse();
+                while let Some(visit) = stack.pop() {
+                    match positions.get(visit.name) {
+                        Some(&position) if visits[position].depth <= visit.depth => continue,
+                        Some(&position) => visits[position] = visit,
+                        None => {
+                            positions.insert(visit.name, visits.len());
+                            visits.push(visit);
+                        }
+                    }
+                    if self.at_limit(visit.depth) {
+                        continue;
+                    }
+                    for name in next(visit.name).into_iter().rev() {
+                        if self.accepts(name) {
+                            stack.push(Visit {
+                                name,
+                                depth: visit.depth + 1,
+                                via: Some(visit.name),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        visits
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use storage::page::namespace::{CATEGORY, MAIN};
+    use storage::page::Category;
+
+    use crate::category::PageCategories;
+
+    #[test]
+    fn test_traversal() {
+        let page = |title: &str, ns, categories: &[&str]| PageCategories {
+            title: title.to_owned(),
+            ns,
+            categories: categories
+                .iter()
+                .map(|name| Category {
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let mut graph = CategoryGraph::new();
+        for page in &[
+            page("Category:Physics", CATEGORY, &["Natural sciences"]),
+            page("Category:Natural sciences", CATEGORY, &["Science"]),
+            page(
+                "Category:Science",
+                CATEGORY,
+                &["Main topic classifications"],
+            ),
+            page("Category:Mechanics", CATEGORY, &["Physics"]),
+            page("Category:Classical mechanics", CATEGORY, &["Mechanics"]),
+            // A cycle back to the top of the subtree.
+            page(
+                "Category:Philosophy of physics",
+                CATEGORY,
+                &["Physics", "Mechanics"],
+            ),
+            page("Category:Physics", CATEGORY, &["Philosophy of physics"]),
+            page("Category:Stub articles", CATEGORY, &[]),
+            page("Newton's laws of motion", MAIN, &["Classical mechanics"]),
+            page("Force", MAIN, &["Mechanics", "Classical mechanics"]),
+            page("Kinetic energy", MAIN, &["Physics", "Stub articles"]),
+            page("Determinism", MAIN, &["Philosophy of physics"]),
+            page(
+                "Laplace's demon",
+                MAIN,
+                &["Classical mechanics", "Philosophy of physics"],
+            ),
+        ] {
+            graph.add(page);
+        }
+        graph.hide("Stub articles");
+        let names = |visits: Vec<Visit>| {
+            visits
+                .iter()
+                .map(|visit| format!("{}:{}", visit.name, visit.depth))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(graph.traverse().descendants("Physics")),
+            vec![
+                "Physics:0",
+                "Mechanics:1",
+                "Philosophy of physics:1",
+                "Classical mechanics:2",
+            ]
+        );
+        assert_eq!(
+            names(
+                graph
+                    .traverse()
+                    .with_order(Order::DepthFirst)
+                    .descendants("Physics")
+            ),
+            vec![
+                "Physics:0",
+                "Mechanics:1",
+                "Classical mechanics:2",
+                "Philosophy of physics:1",
+            ]
+        );
+        assert_eq!(
+            names(graph.traverse().with_max_depth(1).pages("Physics")),
+            vec!["Kinetic energy:1"]
+        );
+        assert_eq!(
+            names(graph.traverse().with_max_depth(2).pages("Physics")),
+            vec![
+                "Kinetic energy:1",
+                "Force:2",
+                "Determinism:2",
+                "Laplace's demon:2",
+            ]
+        );
+        assert_eq!(
+            names(
+                graph
+                    .traverse()
+                    .with_order(Order::DepthFirst)
+                    .with_max_depth(2)
+                    .pages("Physics")
+            ),
+            vec![
+                "Kinetic energy:1",
+                "Force:2",
+                "Determinism:2",
+                "Laplace's demon:2",
+            ]
+        );
+        // Found through Classical mechanics before the shallower Philosophy
+        // of physics.
+        let pages = graph
+            .traverse()
+            .with_order(Order::DepthFirst)
+            .pages("Physics");
+        assert_eq!(pages[3].via, Some("Philosophy of physics"));
+        assert_eq!(
+            names(pages),
+            vec![
+                "Kinetic energy:1",
+                "Force:2",
+                "Newton's laws of motion:3",
+                "Laplace's demon:2",
+                "Determinism:2",
+            ]
+        );
+        assert_eq!(
+            names(graph.traverse().pages("Physics")),
+            vec![
+                "Kinetic energy:1",
+                "Force:2",
+                "Determinism:2",
+                "Laplace's demon:2",
+                "Newton's laws of motion:3",
+            ]
+        );
+
+        let ancestors = graph
+            .traverse()
+            .without_hidden()
+            .ancestors(Node::Page("Kinetic energy"));
+        assert_eq!(
+            names(ancestors),
+            vec![
+                "Physics:1",
+                "Natural sciences:2",
+                "Philosophy of physics:2",
+                "Science:3",
+                "Mechanics:3",
+                "Main topic classifications:4",
+            ]
+        );
+
+        let path = graph
+            .traverse()
+            .path(Node::Page("Force"), &["Main topic classifications"])
+            .unwrap();
+        assert_eq!(
+            path.iter().map(|visit| visit.name).collect::<Vec<_>>(),
+            vec![
+                "Mechanics",
+                "Physics",
+                "Natural sciences",
+                "Science",
+                "Main topic classifications",
+            ]
+        );
+        let path = graph
+            .traverse()
+            .path(Node::Category("Mechanics"), &["Science"])
+            .unwrap();
+        assert_eq!(
+            path.iter().map(|visit| visit.name).collect::<Vec<_>>(),
+            vec!["Mechanics", "Physics", "Natural sciences", "Science"]
+        );
+        assert!(graph
+            .traverse()
+            .with_max_depth(2)
+            .path(Node::Page("Force"), &["Science"])
+            .is_none());
+
+        assert_eq!(
+            graph.cycles(),
+            vec![vec!["Mechanics", "Philosophy of physics", "Physics"]]
+        );
+    }
+}
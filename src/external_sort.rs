@@ -0,0 +1,365 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+use std::time::Instant;
+
+use fst::MapBuilder;
+use log::info;
+
+/// Approximate per-entry overhead of a `HashMap<Vec<u8>, u64>`, in bytes.
+static ENTRY_OVERHEAD: usize = 48;
+
+/// Maximum number of runs merged at once. More runs are merged in several
+/// passes, bounding the number of open files and read buffers.
+pub static MAX_FAN_IN: usize = 64;
+
+/// Size of the read buffer of each run being merged, in bytes.
+static RUN_BUFFER: usize = 256 * 1024;
+
+/// Accumulates key counts in memory, spilling them to sorted run files on disk
+/// whenever the memory budget is exceeded.
+pub struct SpillingCounter {
+    counts: HashMap<Vec<u8>, u64>,
+    bytes: usize,
+    budget: usize,
+    dir: PathBuf,
+    prefix: String,
+    runs: Vec<PathBuf>,
+}
+
+impl SpillingCounter {
+    /// Create a counter.
+    ///
+    /// # Arguments
+    /// * `dir` - Directory in which to write runs.
+    /// * `prefix` - Prefix for run file names, unique to this counter.
+    /// * `budget` - Approximate number of bytes to hold in memory before spilling.
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str, budget: usize) -> Self {
+        SpillingCounter {
+            counts: HashMap::new(),
+            bytes: 0,
+            budget,
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.to_owned(),
+            runs: vec![],
+        }
+    }
+
+    /// Add to the count for a key, spilling if over budget.
+    pub fn add(&mut self, key: &[u8], count: u64) -> io::Result<()> {
+        match self.counts.get_mut(key) {
+            Some(total) => *total += count,
+            None => {
+                self.bytes += key.len() + ENTRY_OVERHEAD;
+                self.counts.insert(key.to_vec(), count);
+            }
+        }
+        if self.bytes >= self.budget {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Write the counts held in memory to a new sorted run.
+    pub fn spill(&mut self) -> io::Result<()> {
+        if self.counts.is_empty() {
+            return Ok(());
+        }
+        let mut counts = self.counts.drain().collect::<Vec<_>>();
+        counts.sort_unstable_by(|(k1, _), (k2, _)| k1.cmp(k2));
+
+        let path = self
+            .dir
+            .join(format!("{}-{}.run", self.prefix, self.runs.len()));
+        let mut writer = BufWriter::with_capacity(1024 * 1024, File::create(&path)?);
+        for (key, count) in counts {
+            write_entry(&mut writer, &key, count)?;
+        }
+        writer.flush()?;
+
+        self.bytes = 0;
+        self.runs.push(path);
+        Ok(())
+    }
+
+    /// Spill any remaining counts and return the paths of all runs.
+    pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+        self.spill()?;
+        Ok(self.runs)
+    }
+}
+
+/// One `SpillingCounter` per thread, for counting in parallel.
+///
+/// Each thread adds to its own counter for as long as it runs, so that every
+/// counter fills its share of the memory budget before spilling, however
+/// rayon splits the work between jobs.
+pub struct ThreadCounters {
+    dir: PathBuf,
+    prefix: String,
+    budget: usize,
+    counters: Mutex<HashMap<ThreadId, Arc<Mutex<SpillingCounter>>>>,
+}
+
+impl ThreadCounters {
+    /// Create counters sharing a memory budget between rayon's threads.
+    ///
+    /// # Arguments
+    /// * `dir` - Directory in which to write runs.
+    /// * `prefix` - Prefix for run file names.
+    /// * `budget` - Approximate number of bytes to hold in memory in total.
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str, budget: usize) -> Self {
+        ThreadCounters {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.to_owned(),
+            budget: budget / rayon::current_num_threads().max(1),
+            counters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fetch the counter of the current thread, creating it if needed.
+    pub fn current(&self) -> Arc<Mutex<SpillingCounter>> {
+        let mut counters = self.counters.lock().unwrap();
+        let worker = counters.len();
+        counters
+            .entry(thread::current().id())
+            .or_insert_with(|| {
+                Arc::new(Mutex::new(SpillingCounter::new(
+                    &self.dir,
+                    &format!("{}-{}", self.prefix, worker),
+                    self.budget,
+                )))
+            })
+            .clone()
+    }
+
+    /// Spill the counts of every thread and return the paths of all runs.
+    pub fn finish(self) -> io::Result<Vec<PathBuf>> {
+        let mut runs = vec![];
+        for (_, counter) in self.counters.into_inner().unwrap() {
+            let counter = match Arc::try_unwrap(counter) {
+                Ok(counter) => counter.into_inner().unwrap(),
+                Err(_) => panic!("counter still in use"),
+            };
+            runs.extend(counter.finish()?);
+        }
+        runs.sort();
+        Ok(runs)
+    }
+}
+
+/// Write a length-prefixed key and its count to a run.
+fn write_entry<W: Write>(writer: &mut W, key: &[u8], count: u64) -> io::Result<()> {
+    writer.write_all(&(key.len() as u32).to_le_bytes())?;
+    writer.write_all(key)?;
+    writer.write_all(&count.to_le_bytes())
+}
+
+/// Sequential reader over the entries of a run.
+pub struct RunReader {
+    reader: BufReader<File>,
+}
+
+impl RunReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(RunReader {
+            reader: BufReader::with_capacity(RUN_BUFFER, File::open(path)?),
+        })
+    }
+
+    /// Read the next entry, or `None` at the end of the run.
+    pub fn next_entry(&mut self) -> io::Result<Option<(Vec<u8>, u64)>> {
+        let mut len = [0; 4];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => (),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let mut key = vec![0; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut key)?;
+        let mut count = [0; 8];
+        self.reader.read_exact(&mut count)?;
+        Ok(Some((key, u64::from_le_bytes(count))))
+    }
+}
+
+/// K-way merge over sorted runs, summing the counts of equal keys.
+///
+/// Every run is open at once, so at most `MAX_FAN_IN` runs should be merged;
+/// use `reduce_runs` to merge more.
+pub struct MergedRuns {
+    readers: Vec<RunReader>,
+    heap: BinaryHeap<Reverse<(Vec<u8>, usize, u64)>>,
+}
+
+impl MergedRuns {
+    pub fn new(runs: &[PathBuf]) -> io::Result<Self> {
+        let mut merged = MergedRuns {
+            readers: runs
+                .iter()
+                .map(RunReader::open)
+                .collect::<io::Result<_>>()?,
+            heap: BinaryHeap::with_capacity(runs.len()),
+        };
+        for run in 0..merged.readers.len() {
+            merged.refill(run)?;
+        }
+        Ok(merged)
+    }
+
+    /// Push the next entry of a run onto the heap.
+    fn refill(&mut self, run: usize) -> io::Result<()> {
+        if let Some((key, count)) = self.readers[run].next_entry()? {
+            self.heap.push(Reverse((key, run, count)));
+        }
+        Ok(())
+    }
+
+    /// Fetch the next key in sorted order along with its total count.
+    pub fn next_entry(&mut self) -> io::Result<Option<(Vec<u8>, u64)>> {
+        let (key, run, mut total) = match self.heap.pop() {
+            Some(Reverse(entry)) => entry,
+            None => return Ok(None),
+        };
+        self.refill(run)?;
+        while self
+            .heap
+            .peek()
+            .map(|Reverse((next, _, _))| *next == key)
+            .unwrap_or(false)
+        {
+            let Reverse((_, run, count)) = self.heap.pop().unwrap();
+            total += count;
+            self.refill(run)?;
+        }
+        Ok(Some((key, total)))
+    }
+}
+
+impl Iterator for MergedRuns {
+    type Item = io::Result<(Vec<u8>, u64)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Merge sorted runs into a single run at `path`.
+fn merge_to_run(runs: &[PathBuf], path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::with_capacity(1024 * 1024, File::create(path)?);
+    let mut merged = MergedRuns::new(runs)?;
+    while let Some((key, count)) = merged.next_entry()? {
+        write_entry(&mut writer, &key, count)?;
+    }
+    writer.flush()
+}
+
+/// Merge runs in groups of at most `fan_in` until no more than `fan_in`
+/// remain, returning the remaining runs.
+///
+/// Merged runs are removed, and intermediate runs are written alongside the
+/// first run.
+pub fn reduce_runs(mut runs: Vec<PathBuf>, fan_in: usize) -> io::Result<Vec<PathBuf>> {
+    let fan_in = fan_in.max(2);
+    let mut pass = 0;
+    while runs.len() > fan_in {
+        info!("Merging {} runs in groups of {}...", runs.len(), fan_in);
+        let dir = runs[0].parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut merged = Vec::with_capacity(runs.len() / fan_in + 1);
+        for (group, chunk) in runs.chunks(fan_in).enumerate() {
+            if chunk.len() == 1 {
+                merged.push(chunk[0].clone());
+                continue;
+            }
+            let path = dir.join(format!("merged-{}-{}.run", pass, group));
+            merge_to_run(chunk, &path)?;
+            remove_runs(chunk)?;
+            merged.push(path);
+        }
+        runs = merged;
+        pass += 1;
+    }
+    Ok(runs)
+}
+
+/// Merge sorted runs and stream the summed counts into a FST, removing the
+/// runs once merged.
+pub fn build_fst_from_runs(
+    runs: Vec<PathBuf>,
+    output_path: &Path,
+) -> Result<(), Box<std::error::Error>> {
+    info!("Merging {} runs...", runs.len());
+    let now = Instant::now();
+
+    let runs = reduce_runs(runs, MAX_FAN_IN)?;
+    let file = File::create(output_path)?;
+    let buf = BufWriter::with_capacity(64 * 1024 * 1024, file);
+    let mut bld = MapBuilder::new(buf)?;
+    let mut merged = MergedRuns::new(&runs)?;
+    while let Some((key, count)) = merged.next_entry()? {
+        bld.insert(key, count)?;
+    }
+    bld.finish()?;
+    remove_runs(&runs)?;
+
+    info!("Done in {} seconds", now.elapsed().as_secs());
+    Ok(())
+}
+
+/// Remove run files.
+pub fn remove_runs(runs: &[PathBuf]) -> io::Result<()> {
+    for run in runs {
+        fs::remove_file(run)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_spill_and_merge() {
+        let dir = std::env::temp_dir().join(format!("wikitools-runs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut runs = vec![];
+        for (worker, keys) in [&["b", "a", "c", "a"][..], &["c", "d", "a"][..]]
+            .iter()
+            .enumerate()
+        {
+            // A budget of one byte spills after every new key.
+            let mut counter = SpillingCounter::new(&dir, &format!("w{}", worker), 1);
+            for key in keys.iter() {
+                counter.add(key.as_bytes(), 1).unwrap();
+            }
+            runs.extend(counter.finish().unwrap());
+        }
+        assert_eq!(runs.len(), 7);
+
+        // Merging two runs at a time takes three passes.
+        let runs = reduce_runs(runs, 2).unwrap();
+        assert_eq!(runs.len(), 2);
+        let mut merged = MergedRuns::new(&runs).unwrap();
+        let mut entries = vec![];
+        while let Some((key, count)) = merged.next_entry().unwrap() {
+            entries.push((String::from_utf8(key).unwrap(), count));
+        }
+        remove_runs(&runs).unwrap();
+        fs::remove_dir(&dir).unwrap();
+
+        let expected = vec![("a", 3), ("b", 1), ("c", 2), ("d", 1)]
+            .into_iter()
+            .map(|(key, count)| (key.to_owned(), count))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, expected);
+    }
+}
@@ -1,41 +1,55 @@
+use std::collections::BTreeSet;
 use std::io;
 use std::io::Write;
-use std::path::Path;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
 use pbr;
 use qp_trie::{wrapper::BString, Trie};
 use rayon::prelude::*;
-use serde_json;
+use serde_json::{self, json, Value};
 
-use crate::indices::WikiDumpIndices;
-use crate::page::{
+use storage::page::{
     writer::{AnchorWriterJSONL, AnchorWriterTSV},
-    Anchor, Page, PageIterator, PageWriter, RawPageIterator,
+    Anchor, LinkParser, Page, PageFilter, PageIterator, PageWriter, PlainText, RawPageIterator,
+    TantivyPageIterator,
 };
-use crate::redirect::write_redirects;
+
+use crate::external_sort::ThreadCounters;
+use crate::indices::Indices;
+use crate::redirect::{write_redirects, RedirectMap};
 use crate::utils::{mutex_bufwriter, open_seek_bzip};
 
 /// Extract a vector of Pages from the zipped store at a given index in a
-/// Wikipedia dump.
-pub fn index_to_pages<P: AsRef<Path>>(data: P, index: &usize) -> Vec<Page> {
+/// Wikipedia dump, skipping pages rejected by the filter and parsing links
+/// with the given parser.
+pub fn index_to_pages<P: AsRef<Path>>(
+    data: P,
+    index: &usize,
+    filter: &Arc<PageFilter>,
+    links: &Arc<LinkParser>,
+) -> Vec<Page> {
     let store = open_seek_bzip(&data, *index).unwrap();
-    PageIterator::new(store).collect::<Vec<_>>()
+    PageIterator::new(store)
+        .with_filter(filter.clone())
+        .with_links(links.clone())
+        .collect::<Vec<_>>()
 }
 
 /// Extract anchors from a Wikipedia dump, writing them to JSON.
 pub fn extract_pages_json<W: Write + Send + Sync>(
-    indices: &WikiDumpIndices,
+    indices: &Indices,
     data: &Path,
     writer: &Mutex<W>,
+    filter: &Arc<PageFilter>,
+    links: &Arc<LinkParser>,
 ) {
-    let mut indices = indices.keys().collect::<Vec<_>>();
+    let indices = indices.offsets();
     let pbar = Mutex::new(pbr::ProgressBar::new(indices.len() as u64));
-    indices.sort();
 
     use serde_json;
-    indices.into_par_iter().for_each(|index| {
-        let pages = index_to_pages(data, index);
+    indices.par_iter().for_each(|index| {
+        let pages = index_to_pages(data, index, filter, links);
         {
             let mut w = writer.lock().unwrap();
             pages.into_iter().for_each(|page| {
@@ -52,19 +66,20 @@ pub fn extract_pages_json<W: Write + Send + Sync>(
 /// Extract page data and write using the specified PageWriter.
 pub fn extract_with_writer<P, W>(
     _page_writer: P,
-    indices: &WikiDumpIndices,
+    indices: &Indices,
     data: &Path,
     writer: &Mutex<W>,
+    filter: &Arc<PageFilter>,
+    links: &Arc<LinkParser>,
 ) where
     P: PageWriter,
     W: Write + Send + Sync,
 {
-    let mut indices = indices.keys().collect::<Vec<_>>();
+    let indices = indices.offsets();
     let pbar = Mutex::new(pbr::ProgressBar::new(indices.len() as u64));
-    indices.sort();
 
-    indices.into_par_iter().for_each(|index| {
-        let pages = index_to_pages(data, index);
+    indices.par_iter().for_each(|index| {
+        let pages = index_to_pages(data, index, filter, links);
         {
             let w = &mut *writer.lock().unwrap();
             pages.into_iter().for_each(|page| {
@@ -78,13 +93,215 @@ pub fn extract_with_writer<P, W>(
     });
 }
 
+/// Render the pages of a dump to JSON with `render`, which is given the ID,
+/// title and text of each page, writing one object per line for each page
+/// it renders.
+pub(crate) fn extract_rendered<W, F>(
+    indices: &Indices,
+    data: &Path,
+    writer: &Mutex<W>,
+    filter: &Arc<PageFilter>,
+    render: F,
+) where
+    W: Write + Send + Sync,
+    F: Fn(&str, &str, &str) -> Option<Value> + Sync,
+{
+    extract_folded(
+        indices,
+        data,
+        writer,
+        filter,
+        |_: &mut (), id, title, text| render(id, title, text),
+        |_, _| (),
+    )
+}
+
+/// Render the pages of a dump to JSON as by `extract_rendered`, also
+/// accumulating state over them.
+///
+/// `render` is given the state of the worker reading the page, and the states
+/// of workers are combined with `merge` once they finish, so that no lock is
+/// taken per page.
+pub(crate) fn extract_folded<W, S, F, M>(
+    indices: &Indices,
+    data: &Path,
+    writer: &Mutex<W>,
+    filter: &Arc<PageFilter>,
+    render: F,
+    merge: M,
+) -> S
+where
+    W: Write + Send + Sync,
+    S: Default + Send,
+    F: Fn(&mut S, &str, &str, &str) -> Option<Value> + Sync,
+    M: Fn(&mut S, S) + Sync + Send,
+{
+    let indices = indices.offsets();
+    let pbar = Mutex::new(pbr::ProgressBar::new(indices.len() as u64));
+
+    indices
+        .par_iter()
+        .fold(S::default, |mut state, index| {
+            let store = open_seek_bzip(data, *index).unwrap();
+            let pages = TantivyPageIterator(PageIterator::new(store).with_filter(filter.clone()))
+                .filter_map(|(id, title, text)| render(&mut state, &id, &title, &text))
+                .collect::<Vec<_>>();
+            {
+                let mut w = writer.lock().unwrap();
+                pages.into_iter().for_each(|page| {
+                    writeln!(w, "{}", page).unwrap();
+                });
+            }
+            {
+                let mut prog_bar = pbar.lock().unwrap();
+                prog_bar.inc();
+            }
+            state
+        })
+        .reduce(S::default, |mut state, other| {
+            merge(&mut state, other);
+            state
+        })
+}
+
+/// Extract the plain text of pages, writing one JSON object per line with the
+/// page's ID, title and text.
+pub fn extract_plaintext<W: Write + Send + Sync>(
+    indices: &Indices,
+    data: &Path,
+    writer: &Mutex<W>,
+    filter: &Arc<PageFilter>,
+    links: &Arc<LinkParser>,
+) {
+    let plaintext = PlainText::new(links);
+    extract_rendered(indices, data, writer, filter, |id, title, text| {
+        Some(json!({
+            "id": id.parse::<u64>().ok(),
+            "title": title,
+            "text": plaintext.render(text),
+        }))
+    });
+}
+
+/// Extract the plain text of pages with the spans of their links to articles,
+/// writing one JSON object per line with the page's ID, title, text and spans.
+///
+/// Spans are given as `{start, end, surface, target}`, with offsets counted in
+/// characters. If `redirects` is given, targets are resolved through it.
+pub fn extract_annotated<W: Write + Send + Sync>(
+    indices: &Indices,
+    data: &Path,
+    writer: &Mutex<W>,
+    filter: &Arc<PageFilter>,
+    links: &Arc<LinkParser>,
+    redirects: Option<&RedirectMap>,
+) {
+    let plaintext = PlainText::new(links);
+    extract_rendered(indices, data, writer, filter, |id, title, text| {
+        let mut annotated = plaintext.annotate(text);
+        if let Some(redirects) = redirects {
+            for span in &mut annotated.spans {
+                span.target = redirects.resolve(&span.target);
+            }
+        }
+        Some(json!({
+            "id": id.parse::<u64>().ok(),
+            "title": title,
+            "text": annotated.text,
+            "spans": annotated.spans,
+        }))
+    });
+}
 
 pub struct TrieBuilderFlat;
 pub struct TrieBuilderNested;
 
+/// Flat Trie builder which counts anchors against the page their target
+/// redirects to.
+pub struct TrieBuilderRedirected {
+    pub redirects: RedirectMap,
+}
+
 pub trait AnchorTrieBuilder<V> {
     fn fold(into: &mut Trie<BString, V>, from: Trie<BString, V>);
-    fn extract(path: &Path, index: usize) -> Trie<BString, V>;
+    fn extract(
+        &self,
+        path: &Path,
+        index: usize,
+        filter: &Arc<PageFilter>,
+        links: &LinkParser,
+    ) -> Trie<BString, V>;
+}
+
+/// Find the links to articles in a page, returning lowercased surface form and
+/// page name pairs.
+fn article_anchors(page: &str, links: &LinkParser) -> Vec<(String, String)> {
+    links
+        .parse(page)
+        .iter()
+        .filter(|link| link.is_article())
+        .map(|link| match link.anchor() {
+            Anchor::Direct(name) => (name.to_lowercase(), name),
+            Anchor::Label { surface, page } => (surface.to_lowercase(), page),
+        })
+        .collect()
+}
+
+/// Add the anchors of a page to a flat Trie of counts, mapping each link
+/// target through `resolve`.
+///
+/// The Trie maps tab-separated surface form and page name pairs to counts.
+fn count_flat<F>(
+    page: &str,
+    filter: &PageFilter,
+    links: &LinkParser,
+    resolve: &F,
+    counts: &mut Trie<BString, u32>,
+) where
+    F: Fn(&str) -> String,
+{
+    article_anchors(page, links)
+        .into_iter()
+        // Skip links to pages which would themselves be skipped.
+        .filter(|(_, page)| filter.accepts_link(page.trim()))
+        .for_each(|(surf, page)| {
+            *counts
+                .entry(format!("{}\t{}", surf.trim(), resolve(page.trim())).into())
+                .or_insert(0) += 1
+        });
+}
+
+/// Find the surface forms of the links to accepted articles in a page, each
+/// once however often it is linked.
+fn linked_surface_forms(page: &str, filter: &PageFilter, links: &LinkParser) -> BTreeSet<String> {
+    article_anchors(page, links)
+        .into_iter()
+        .filter(|(_, page)| filter.accepts_link(page.trim()))
+        .map(|(surf, _)| surf.trim().to_owned())
+        .collect()
+}
+
+/// Extract anchor counts for one file and return a flat Trie, mapping each
+/// link target through `resolve`.
+///
+/// Returned Trie maps tab-separated surface form and page name pairs to counts.
+fn extract_flat<F>(
+    path: &Path,
+    index: usize,
+    filter: &Arc<PageFilter>,
+    links: &LinkParser,
+    resolve: F,
+) -> Trie<BString, u32>
+where
+    F: Fn(&str) -> String,
+{
+    let store = open_seek_bzip(path, index).unwrap();
+    let mut chunk_counts: Trie<BString, u32> = Trie::new();
+
+    RawPageIterator(PageIterator::new(store).with_filter(filter.clone())).for_each(|page| {
+        count_flat(&page, &**filter, links, &resolve, &mut chunk_counts);
+    });
+    chunk_counts
 }
 
 impl AnchorTrieBuilder<u32> for TrieBuilderFlat {
@@ -96,36 +313,45 @@ impl AnchorTrieBuilder<u32> for TrieBuilderFlat {
 
     /// Extract anchor counts for one file and return a flat Trie.
     ///
-    /// Returned Trie maps surface forms to a Trie mapping page names to counts.
+    /// # Arguments
+    /// * `path` - Path to bzip2 file.
+    /// * `index` - Offset within bzip2 file at which to begin reading pages.
+    /// * `filter` - Filter for skipping pages.
+    /// * `links` - Parser for the links of each page.
+    fn extract(
+        &self,
+        path: &Path,
+        index: usize,
+        filter: &Arc<PageFilter>,
+        links: &LinkParser,
+    ) -> Trie<BString, u32> {
+        extract_flat(path, index, filter, links, |page| page.to_owned())
+    }
+}
+
+impl AnchorTrieBuilder<u32> for TrieBuilderRedirected {
+    fn fold(into: &mut Trie<BString, u32>, from: Trie<BString, u32>) {
+        TrieBuilderFlat::fold(into, from)
+    }
+
+    /// Extract anchor counts for one file and return a flat Trie, with link
+    /// targets resolved through redirects to their canonical page.
     ///
     /// # Arguments
     /// * `path` - Path to bzip2 file.
     /// * `index` - Offset within bzip2 file at which to begin reading pages.
-    fn extract(path: &Path, index: usize) -> Trie<BString, u32> {
-        let store = open_seek_bzip(path, index).unwrap();
-        let mut chunk_counts: Trie<BString, u32> = Trie::new();
-
-        RawPageIterator(PageIterator::new(store)).for_each(|page| {
-            page.match_indices("[[")
-                .filter_map(|(begin, _)| Anchor::pare_anchor_match(&page, begin))
-                .filter(|anchor| {
-                    !anchor.starts_with(":")
-                        && !anchor.starts_with("<")
-                        && !anchor.contains("User talk:")
-                        && !anchor.contains("File talk:")
-                })
-                .map(Anchor::parse)
-                .map(|anchor| match anchor {
-                    Anchor::Direct(name) => (name.to_lowercase(), name),
-                    Anchor::Label { surface, page } => (surface.to_lowercase(), page),
-                })
-                .for_each(|(surf, page)| {
-                    *chunk_counts
-                        .entry(format!("{}\t{}", surf.trim(), page.trim()).into())
-                        .or_insert(0) += 1
-                });
-        });
-        chunk_counts
+    /// * `filter` - Filter for skipping pages.
+    /// * `links` - Parser for the links of each page.
+    fn extract(
+        &self,
+        path: &Path,
+        index: usize,
+        filter: &Arc<PageFilter>,
+        links: &LinkParser,
+    ) -> Trie<BString, u32> {
+        extract_flat(path, index, filter, links, |page| {
+            self.redirects.resolve(page)
+        })
     }
 }
 
@@ -146,18 +372,21 @@ impl AnchorTrieBuilder<Trie<BString, u32>> for TrieBuilderNested {
     /// # Arguments
     /// * `path` - Path to bzip2 file.
     /// * `index` - Offset within bzip2 file at which to begin reading pages.
-    fn extract(path: &Path, index: usize) -> Trie<BString, Trie<BString, u32>> {
+    /// * `filter` - Filter for skipping pages.
+    /// * `links` - Parser for the links of each page.
+    fn extract(
+        &self,
+        path: &Path,
+        index: usize,
+        filter: &Arc<PageFilter>,
+        links: &LinkParser,
+    ) -> Trie<BString, Trie<BString, u32>> {
         let store = open_seek_bzip(path, index).unwrap();
         let mut chunk_counts: Trie<BString, Trie<BString, u32>> = Trie::new();
 
-        RawPageIterator(PageIterator::new(store)).for_each(|page| {
-            page.match_indices("[[")
-                .filter_map(|(begin, _)| Anchor::pare_anchor_match(&page, begin))
-                .map(Anchor::parse)
-                .map(|anchor| match anchor {
-                    Anchor::Direct(name) => (name.to_lowercase(), name),
-                    Anchor::Label { surface, page } => (surface.to_lowercase(), page),
-                })
+        RawPageIterator(PageIterator::new(store).with_filter(filter.clone())).for_each(|page| {
+            article_anchors(&page, links)
+                .into_iter()
                 .for_each(|(surf, page)| {
                     *chunk_counts
                         .entry(surf.trim().into())
@@ -175,28 +404,32 @@ impl AnchorTrieBuilder<Trie<BString, u32>> for TrieBuilderNested {
 /// This function allows the user to specify a method F taking the data file
 /// path and an offset and producing a trie, the value of which is unspecified.
 /// Thus, this function may be used to produce either a nested Trie for
-/// serialization to JSON, or a 'flat' Trie for use in TSV or FST serialization.
+/// serialization to JSON, or a 'flat' Trie for use in TSV or FST serialization,
+/// optionally with link targets resolved through redirects.
 ///
 /// # Arguments
-/// * `indices` - Map of bzip2 multistream indices to page indices.
+/// * `indices` - Stream offsets and the page ids within each stream.
 /// * `path` - Path to a wikipedia bzip2 multistream.
 /// * `method` - Method for transforming bzip2 chunks to Tries.
+/// * `filter` - Filter for skipping pages.
+/// * `links` - Parser for the links of each page.
 pub fn extract_anchor_counts_to_trie<Builder, V>(
-    _builder: Builder,
-    indices: &WikiDumpIndices,
-    data: &Path
+    builder: Builder,
+    indices: &Indices,
+    data: &Path,
+    filter: &Arc<PageFilter>,
+    links: &LinkParser,
 ) -> Trie<BString, V>
 where
     V: Send + Sync,
-    Builder: AnchorTrieBuilder<V>
+    Builder: AnchorTrieBuilder<V> + Sync,
 {
-    let mut indices = indices.keys().collect::<Vec<_>>();
+    let indices = indices.offsets();
     let pbar = Mutex::new(pbr::ProgressBar::new(indices.len() as u64));
     let anchor_counts = Mutex::new(Trie::new());
-    indices.sort();
 
-    indices.into_par_iter().for_each(|index| {
-        let chunk_counts = Builder::extract(data, *index);
+    indices.par_iter().for_each(|index| {
+        let chunk_counts = builder.extract(data, *index, filter, links);
         {
             let mut anchor_counts = anchor_counts.lock().unwrap();
             Builder::fold(&mut anchor_counts, chunk_counts);
@@ -208,4 +441,156 @@ where
     });
     anchor_counts.into_inner().unwrap()
 }
+
+/// Extract flat anchor counts for a set of indices in a dump, spilling sorted
+/// runs to disk rather than holding all counts in memory.
+///
+/// Each rayon thread holds its own counts, and spills whenever its share of
+/// the memory budget is exceeded. Returns the paths of all runs, to be merged
+/// with `build_fst_from_runs`.
+///
+/// # Arguments
+/// * `builder` - Flat anchor trie builder.
+/// * `indices` - Stream offsets and the page ids within each stream.
+/// * `data` - Path to a wikipedia bzip2 multistream.
+/// * `spill_dir` - Directory in which to write runs.
+/// * `budget` - Approximate memory budget in bytes, shared between threads.
+/// * `filter` - Filter for skipping pages.
+/// * `links` - Parser for the links of each page.
+pub fn extract_anchor_counts_to_runs<Builder>(
+    builder: Builder,
+    indices: &Indices,
+    data: &Path,
+    spill_dir: &Path,
+    budget: usize,
+    filter: &Arc<PageFilter>,
+    links: &LinkParser,
+) -> io::Result<Vec<PathBuf>>
+where
+    Builder: AnchorTrieBuilder<u32> + Sync,
+{
+    let indices = indices.offsets();
+    let pbar = Mutex::new(pbr::ProgressBar::new(indices.len() as u64));
+
+    let counters = ThreadCounters::new(spill_dir, "anchors", budget);
+    indices.par_iter().try_for_each(|index| -> io::Result<()> {
+        let counts = builder.extract(data, *index, filter, links);
+        {
+            let counter = counters.current();
+            let mut counter = counter.lock().unwrap();
+            for (key, count) in counts {
+                counter.add(key.as_str().as_bytes(), u64::from(count))?;
+            }
+        }
+        pbar.lock().unwrap().inc();
+        Ok(())
+    })?;
+    counters.finish()
+}
+
+/// Count the pages linking each surface form in a dump, spilling sorted runs
+/// to disk in the same way as `extract_anchor_counts_to_runs`.
+///
+/// Surface forms are normalised as they are for anchor counts, and counted
+/// once per page however often they are linked. Returns the paths of all runs.
+///
+/// # Arguments
+/// * `indices` - Stream offsets and the page ids within each stream.
+/// * `data` - Path to a wikipedia bzip2 multistream.
+/// * `spill_dir` - Directory in which to write runs.
+/// * `budget` - Approximate memory budget in bytes, shared between threads.
+/// * `filter` - Filter for skipping pages.
+/// * `links` - Parser for the links of each page.
+pub fn extract_linked_pages_to_runs(
+    indices: &Indices,
+    data: &Path,
+    spill_dir: &Path,
+    budget: usize,
+    filter: &Arc<PageFilter>,
+    links: &LinkParser,
+) -> io::Result<Vec<PathBuf>> {
+    let indices = indices.offsets();
+    let pbar = Mutex::new(pbr::ProgressBar::new(indices.len() as u64));
+
+    let counters = ThreadCounters::new(spill_dir, "linked", budget);
+    indices.par_iter().try_for_each(|index| -> io::Result<()> {
+        let store = open_seek_bzip(data, *index)?;
+        let pages = RawPageIterator(PageIterator::new(store).with_filter(filter.clone()))
+            .map(|page| linked_surface_forms(&page, &**filter, links))
+            .collect::<Vec<_>>();
+        {
+            let counter = counters.current();
+            let mut counter = counter.lock().unwrap();
+            for surface in pages.iter().flatten() {
+                counter.add(surface.as_bytes(), 1)?;
+            }
+        }
+        pbar.lock().unwrap().inc();
+        Ok(())
+    })?;
+    counters.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    use storage::page::{Namespaces, PageRules, RulesFilter, TitleNormaliser};
+
+    use crate::redirect::{map::build_redirect_map, Target};
+
+    #[test]
+    fn test_count_redirected() {
+        let mut targets = BTreeMap::new();
+        targets.insert("UK".to_owned(), Target::parse("United Kingdom"));
+        targets.insert(
+            "UK history".to_owned(),
+            Target::parse("United Kingdom#History"),
+        );
+        let (mut map, mut table) = (vec![], vec![]);
+        build_redirect_map(&targets, &mut map, &mut table).unwrap();
+        let builder = TrieBuilderRedirected {
+            redirects: RedirectMap::from_bytes(map, table, TitleNormaliser::default()).unwrap(),
+        };
+
+        let filter = RulesFilter::new(&PageRules::default(), Namespaces::default()).unwrap();
+        let mut counts = Trie::new();
+        count_flat(
+            "The [[UK]], [[United Kingdom|Britain]], [[uK|the UK]] and its \
+             [[UK history|history]]; [[File:Flag.svg]] [[France]]",
+            &filter,
+            &LinkParser::default(),
+            &|page: &str| builder.redirects.resolve(page),
+            &mut counts,
+        );
+        let counts = counts
+            .into_iter()
+            .map(|(key, count)| (key.as_str().to_owned(), count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            vec![
+                ("britain\tUnited Kingdom".to_owned(), 1),
+                ("france\tFrance".to_owned(), 1),
+                ("history\tUnited Kingdom".to_owned(), 1),
+                ("the uk\tUnited Kingdom".to_owned(), 1),
+                ("uk\tUnited Kingdom".to_owned(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_linked_surface_forms() {
+        let filter = RulesFilter::new(&PageRules::default(), Namespaces::default()).unwrap();
+        let surface_forms = linked_surface_forms(
+            "[[UK]], [[United Kingdom|UK]], [[uk]], [[United Kingdom]] and [[File:Flag.svg]]",
+            &filter,
+            &LinkParser::default(),
+        );
+        assert_eq!(
+            surface_forms.into_iter().collect::<Vec<_>>(),
+            vec!["uk".to_owned(), "united kingdom".to_owned()]
+        );
+    }
 }
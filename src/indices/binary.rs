@@ -0,0 +1,287 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+use memmap::Mmap;
+
+use super::WikiDumpIndices;
+
+/// Magic bytes at the start of a binary indices file.
+pub static MAGIC: &[u8; 4] = b"WKIX";
+/// Current binary format version.
+pub static VERSION: u32 = 1;
+
+/// Length of the header: magic, version and number of streams.
+const HEADER_LEN: usize = 16;
+/// Length of a directory entry: stream offset and position of its page ids.
+const ENTRY_LEN: usize = 16;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    let mut buf = [0; 10];
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+    writer.write_all(&buf[..len])
+}
+
+/// Decode a varint from the start of a slice, returning it and the number of
+/// bytes read.
+fn read_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0;
+    for (i, byte) in data.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+fn read_u64(data: &[u8], at: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[at..at + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Check whether a file starts with the binary indices magic bytes.
+pub fn is_binary<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let mut magic = [0; 4];
+    let mut file = File::open(path)?;
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == MAGIC),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Serialise indices in the binary format.
+///
+/// The file consists of a header, a directory of stream offsets sorted in
+/// ascending order with the position of each stream's page ids, and the page
+/// ids themselves, sorted and delta-encoded as varints following their count.
+pub fn write_binary<W: Write>(indices: &WikiDumpIndices, writer: W) -> io::Result<()> {
+    let mut offsets = indices.keys().cloned().collect::<Vec<_>>();
+    offsets.sort();
+
+    let mut ids = vec![];
+    let mut positions = Vec::with_capacity(offsets.len());
+    for offset in offsets.iter() {
+        positions.push(ids.len() as u64);
+        let mut inner = indices[offset].clone();
+        inner.sort();
+        write_varint(&mut ids, inner.len() as u64)?;
+        let mut last = 0;
+        for id in inner {
+            write_varint(&mut ids, (id - last) as u64)?;
+            last = id;
+        }
+    }
+
+    let mut writer = BufWriter::with_capacity(8192 * 4, writer);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(offsets.len() as u64).to_le_bytes())?;
+    let data_start = (HEADER_LEN + ENTRY_LEN * offsets.len()) as u64;
+    for (offset, position) in offsets.iter().zip(positions) {
+        writer.write_all(&(*offset as u64).to_le_bytes())?;
+        writer.write_all(&(data_start + position).to_le_bytes())?;
+    }
+    writer.write_all(&ids)?;
+    writer.flush()
+}
+
+/// Write indices to file in the binary format.
+pub fn write_binary_indices(indices: &WikiDumpIndices, path: &Path) -> io::Result<()> {
+    write_binary(indices, File::create(path)?)
+}
+
+/// Page ids in a stream, decoded lazily.
+#[derive(Clone)]
+pub struct PageIds<'a> {
+    data: &'a [u8],
+    remaining: u64,
+    last: usize,
+}
+
+impl<'a> Iterator for PageIds<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (delta, len) = read_varint(self.data)?;
+        self.data = &self.data[len..];
+        self.remaining -= 1;
+        self.last += delta as usize;
+        Some(self.last)
+    }
+}
+
+/// View over binary indices, which may be memory mapped or held in memory.
+pub struct BinaryIndices<D: AsRef<[u8]>> {
+    data: D,
+    len: usize,
+}
+
+/// Binary indices memory mapped from a file.
+pub type MappedIndices = BinaryIndices<Mmap>;
+
+impl MappedIndices {
+    /// Memory map a binary indices file.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        BinaryIndices::from_data(mmap)
+    }
+}
+
+impl<D: AsRef<[u8]>> BinaryIndices<D> {
+    /// Validate the header and directory of binary indices.
+    pub fn from_data(data: D) -> io::Result<Self> {
+        let bytes = data.as_ref();
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(invalid_data("not a binary indices file"));
+        }
+        let mut version = [0; 4];
+        version.copy_from_slice(&bytes[4..8]);
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported indices version {}",
+                version
+            )));
+        }
+        let len = read_u64(bytes, 8) as usize;
+        let directory_end = len
+            .checked_mul(ENTRY_LEN)
+            .and_then(|n| n.checked_add(HEADER_LEN))
+            .ok_or_else(|| invalid_data("indices directory length overflows"))?;
+        if bytes.len() < directory_end {
+            return Err(invalid_data("truncated indices directory"));
+        }
+        Ok(BinaryIndices { data, len })
+    }
+
+    /// Number of streams.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn offset_at(&self, i: usize) -> usize {
+        read_u64(self.data.as_ref(), HEADER_LEN + ENTRY_LEN * i) as usize
+    }
+
+    fn ids_at(&self, i: usize) -> PageIds {
+        let bytes = self.data.as_ref();
+        let position = read_u64(bytes, HEADER_LEN + ENTRY_LEN * i + 8) as usize;
+        let data = bytes.get(position..).unwrap_or(&[]);
+        match read_varint(data) {
+            Some((remaining, len)) => PageIds {
+                data: &data[len..],
+                remaining,
+                last: 0,
+            },
+            None => PageIds {
+                data: &[],
+                remaining: 0,
+                last: 0,
+            },
+        }
+    }
+
+    /// Stream offsets, in ascending order.
+    pub fn offsets<'a>(&'a self) -> impl Iterator<Item = usize> + 'a {
+        (0..self.len).map(move |i| self.offset_at(i))
+    }
+
+    /// Stream offsets and their page ids, in ascending order of offset.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (usize, PageIds<'a>)> + 'a {
+        (0..self.len).map(move |i| (self.offset_at(i), self.ids_at(i)))
+    }
+
+    /// Fetch the page ids for a stream offset.
+    pub fn get(&self, offset: usize) -> Option<PageIds> {
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let found = self.offset_at(mid);
+            if found == offset {
+                return Some(self.ids_at(mid));
+            }
+            if found < offset {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        None
+    }
+
+    /// Decode into a map of stream offsets to page ids.
+    pub fn to_indices(&self) -> WikiDumpIndices {
+        let mut indices = WikiDumpIndices::default();
+        indices.reserve(self.len);
+        for (offset, ids) in self.iter() {
+            indices.insert(offset, ids.collect());
+        }
+        indices
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut indices = WikiDumpIndices::default();
+        indices.insert(1_000_000, vec![300, 12, 299]);
+        indices.insert(597, vec![10, 12, 13]);
+        indices.insert(20_000_000_000, vec![1 << 40]);
+
+        let mut data = vec![];
+        write_binary(&indices, &mut data).unwrap();
+        let binary = BinaryIndices::from_data(data).unwrap();
+
+        assert_eq!(binary.len(), 3);
+        assert_eq!(
+            binary.offsets().collect::<Vec<_>>(),
+            vec![597, 1_000_000, 20_000_000_000]
+        );
+        assert_eq!(
+            binary.get(1_000_000).unwrap().collect::<Vec<_>>(),
+            vec![12, 299, 300]
+        );
+        assert_eq!(
+            binary.get(20_000_000_000).unwrap().collect::<Vec<_>>(),
+            vec![1 << 40]
+        );
+        assert!(binary.get(598).is_none());
+        assert_eq!(binary.to_indices().len(), 3);
+        assert!(BinaryIndices::from_data(b"597 10,12".to_vec()).is_err());
+
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&u64::max_value().to_le_bytes());
+        let err = BinaryIndices::from_data(header).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
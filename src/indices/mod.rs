@@ -0,0 +1,254 @@
+pub mod binary;
+
+pub use self::binary::{write_binary_indices, BinaryIndices, MappedIndices};
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use fnv::FnvHashMap;
+use pbr::ProgressBar;
+use spinners::{Spinner, Spinners};
+
+use storage::page::{namespace::TEMPLATE, Namespaces};
+
+use crate::utils::open_bzip;
+
+pub type WikiDumpIndices = FnvHashMap<usize, Vec<usize>>;
+
+/// Indices of a dump, either memory mapped from the binary format or held in
+/// memory.
+pub enum Indices {
+    Mapped(MappedIndices),
+    InMemory(WikiDumpIndices),
+}
+
+impl Indices {
+    /// Number of streams.
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::Mapped(indices) => indices.len(),
+            Indices::InMemory(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stream offsets, in ascending order.
+    pub fn offsets(&self) -> Vec<usize> {
+        match self {
+            Indices::Mapped(indices) => indices.offsets().collect(),
+            Indices::InMemory(indices) => {
+                let mut offsets = indices.keys().cloned().collect::<Vec<_>>();
+                offsets.sort();
+                offsets
+            }
+        }
+    }
+
+    /// Convert into a map of stream offsets to page ids, decoding mapped
+    /// indices.
+    pub fn into_map(self) -> WikiDumpIndices {
+        match self {
+            Indices::Mapped(indices) => indices.to_indices(),
+            Indices::InMemory(indices) => indices,
+        }
+    }
+}
+
+impl From<WikiDumpIndices> for Indices {
+    fn from(indices: WikiDumpIndices) -> Self {
+        Indices::InMemory(indices)
+    }
+}
+
+/// Find template indices in an index file.
+///
+/// Template pages are those whose title is prefixed with any name of the
+/// template namespace.
+pub fn find_template_indices(path: &Path, namespaces: &Namespaces) -> io::Result<WikiDumpIndices> {
+    let buf = open_bzip(path)?;
+
+    let mut hm: WikiDumpIndices = WikiDumpIndices::default();
+
+    let spinner = Spinner::new(Spinners::Dots, "Finding templates...".to_owned());
+    let lines = buf
+        .lines()
+        .map(|line| line.unwrap())
+        .filter(|line| match line.splitn(3, ':').nth(2) {
+            Some(title) => namespaces.key_for_title(title) == TEMPLATE,
+            None => false,
+        })
+        .collect::<Vec<_>>();
+    spinner.stop();
+    let mut pbar = ProgressBar::new(lines.len() as u64);
+    for line in lines {
+        let pair: Vec<&str> = line.split(':').take(2).collect();
+        let outer = pair[0].parse::<usize>().unwrap();
+        let inner = pair[1].parse::<usize>().unwrap();
+        hm.entry(outer).or_insert_with(Vec::new).push(inner);
+        pbar.inc();
+    }
+    Ok(hm)
+}
+
+/// Write (Page ID, Title) pairs from an index file.
+pub fn write_title_pageids<R: BufRead, W: Write>(indices: R, writer: &mut W) -> io::Result<()> {
+    let mut index = 0;
+
+    indices
+        .lines()
+        .map(|line| line.unwrap())
+        .filter_map(|line| {
+            index += 1;
+            if index % 100_000 == 0 {
+                println!("Processed {} indices", index);
+            }
+            if let Some(index) = line.find(':') {
+                let pair = &line[index + 1..];
+                if let Some(index) = pair.find(':') {
+                    let pair = (pair[0..index].to_owned(), pair[index + 1..].to_owned());
+                    return Some(pair);
+                }
+                return None;
+            }
+            None
+        })
+        .for_each(|(index, title)| writeln!(writer, "{}\t{}", index, title).unwrap());
+    Ok(())
+}
+
+/// Read the titles of all pages in an index file.
+pub fn read_titles(path: &Path) -> io::Result<HashSet<String>> {
+    let indices = open_bzip(path)?;
+    let mut titles = HashSet::new();
+    for line in indices.lines() {
+        let line = line?;
+        if let Some(title) = line.splitn(3, ':').nth(2) {
+            titles.insert(title.to_owned());
+        }
+    }
+    Ok(titles)
+}
+
+/// Build a lookup table of all indices.
+pub fn build_indices_map(path: &Path) -> io::Result<WikiDumpIndices> {
+    let indices = open_bzip(path)?;
+
+    let mut counter = 0;
+
+    let hm = indices
+        .lines()
+        .map(|line| line.unwrap())
+        .map(|line| {
+            let pair: Vec<&str> = line.split(':').take(2).collect();
+            let outer = pair[0].parse::<usize>().unwrap();
+            let inner = pair[1].parse::<usize>().unwrap();
+            counter += 1;
+            if counter % 100_000 == 0 {
+                println!("Read {} lines", counter);
+            }
+            (outer, inner)
+        })
+        .fold(WikiDumpIndices::default(), |mut hm, (o, i)| {
+            hm.entry(o).or_insert_with(Vec::new).push(i);
+            hm
+        });
+    Ok(hm)
+}
+
+/// Read an indices file, in either the binary or the text format.
+///
+/// Binary indices are memory mapped rather than decoded.
+pub fn read_indices(path: &Path) -> io::Result<Indices> {
+    if binary::is_binary(path)? {
+        return Ok(Indices::Mapped(MappedIndices::open(path)?));
+    }
+    Ok(read_text_indices(path)?.into())
+}
+
+fn parse_usize(num: &str) -> io::Result<usize> {
+    num.parse::<usize>().map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("bad index `{}`: {}", num, e),
+        )
+    })
+}
+
+/// Read an indices file in the text format, with one line of space-separated
+/// stream offset and comma-separated page ids per stream.
+pub fn read_text_indices(path: &Path) -> io::Result<WikiDumpIndices> {
+    let file = File::open(path)?;
+    let buf = BufReader::new(file);
+    let mut hm = WikiDumpIndices::default();
+    for line in buf.lines() {
+        let line = line?;
+        let mut pair = line.splitn(2, ' ');
+        let outer = parse_usize(pair.next().unwrap_or(""))?;
+        let inner = pair
+            .next()
+            .unwrap_or("")
+            .split(',')
+            .map(parse_usize)
+            .collect::<io::Result<Vec<_>>>()?;
+        hm.insert(outer, inner);
+    }
+    Ok(hm)
+}
+
+/// Write a HashMap of indices to file in the binary format.
+pub fn write_indices(hs: &WikiDumpIndices, path: &Path) -> io::Result<()> {
+    write_binary_indices(hs, path)
+}
+
+/// Write a HashMap of indices to file in the text format.
+pub fn write_text_indices(hs: &WikiDumpIndices, path: &Path) -> io::Result<()> {
+    let out = File::create(path)?;
+    let mut buf = BufWriter::with_capacity(8192 * 4, out);
+    for (outer, inners) in hs.iter() {
+        write!(&mut buf, "{} ", outer)?;
+        for inner in inners[0..inners.len() - 1].iter() {
+            write!(&mut buf, "{},", inner)?;
+        }
+        writeln!(&mut buf, "{}", inners.last().unwrap())?;
+    }
+    Ok(())
+}
+
+/// Convert an indices file between the text and binary formats.
+///
+/// # Arguments
+/// * `input` - Indices file in either format.
+/// * `output` - Path to write converted indices to.
+/// * `text` - Write the text format rather than the binary format.
+pub fn convert_indices(input: &Path, output: &Path, text: bool) -> io::Result<usize> {
+    let indices = read_indices(input)?.into_map();
+    if text {
+        write_text_indices(&indices, output)?;
+    } else {
+        write_binary_indices(&indices, output)?;
+    }
+    Ok(indices.len())
+}
+
+/// Fetch and write the indices of each Template.
+pub fn write_template_indices(
+    index: &Path,
+    output: &Path,
+    namespaces: &Namespaces,
+) -> io::Result<WikiDumpIndices> {
+    let hx = find_template_indices(index, namespaces)?;
+    write_indices(&hx, output)?;
+    Ok(hx)
+}
+
+/// Fetch and write all indices.
+pub fn write_all_indices(index: &Path, out_path: &Path) -> io::Result<WikiDumpIndices> {
+    let hx = build_indices_map(index)?;
+    write_indices(&hx, out_path)?;
+    Ok(hx)
+}
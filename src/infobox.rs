@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+use regex::{self, Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+use serde_json::{self, json};
+
+use storage::page::{CallKind, LinkParser, PageFilter, PlainText, TemplateCall, TemplateParser};
+
+use crate::extract::extract_folded;
+use crate::indices::Indices;
+
+lazy_static! {
+    /// Comments and references, which are dropped from infobox values.
+    static ref REFS: Regex =
+        Regex::new(r"(?is)<!--.*?-->|<ref\b[^>]*/>|<ref\b[^>]*>.*?</ref\s*>").unwrap();
+}
+
+/// Templates adding footnotes or references, which are dropped from infobox
+/// values along with the links in them. Names are compared in lowercase.
+static FOOTNOTES: &[&str] = &["efn", "efn-ua", "efn-lr", "refn", "sfn", "sfnp", "ref", "r"];
+
+/// Value of an infobox field.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Field {
+    /// Wikitext as written.
+    pub raw: String,
+    /// Value as plain text, without references or markup. Templates other
+    /// than footnotes are replaced by their positional arguments, so that the
+    /// items of `{{plainlist|...}}` or the spouse in `{{marriage|...}}` are
+    /// kept.
+    pub text: String,
+    /// Titles of the articles linked from the value.
+    pub links: Vec<String>,
+}
+
+/// An infobox in the text of a page.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Infobox {
+    /// Name of the infobox template, e.g. `Infobox person`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Fields with a value, by name.
+    pub fields: BTreeMap<String, Field>,
+}
+
+/// Finds infoboxes in page text.
+pub struct InfoboxExtractor {
+    /// Patterns matching the names of infobox templates.
+    names: RegexSet,
+    templates: TemplateParser,
+    links: Arc<LinkParser>,
+}
+
+impl InfoboxExtractor {
+    /// Create an extractor.
+    ///
+    /// # Arguments
+    /// * `patterns` - Patterns matching the names of infobox templates without
+    ///   their namespace, e.g. `^Infobox\b`.
+    /// * `templates` - Parser for template calls.
+    /// * `links` - Parser for the links in field values.
+    pub fn new(
+        patterns: &[String],
+        templates: TemplateParser,
+        links: Arc<LinkParser>,
+    ) -> Result<Self, regex::Error> {
+        Ok(InfoboxExtractor {
+            names: RegexSet::new(patterns)?,
+            templates,
+            links,
+        })
+    }
+
+    /// Check if a template call is an infobox.
+    pub fn is_infobox(&self, call: &TemplateCall) -> bool {
+        call.kind == CallKind::Template && self.names.is_match(call.template_name())
+    }
+
+    /// Extract the infoboxes in the text of a page, including those embedded
+    /// in other infoboxes.
+    pub fn extract(&self, text: &str) -> Vec<Infobox> {
+        self.templates
+            .parse(text)
+            .iter()
+            .filter(|call| self.is_infobox(call))
+            .map(|call| Infobox {
+                kind: call.template_name().to_owned(),
+                fields: call
+                    .args
+                    .iter()
+                    .filter(|arg| !arg.value.trim().is_empty())
+                    .map(|arg| (arg.name.clone(), self.field(&arg.value)))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Clean the value of a field.
+    fn field(&self, raw: &str) -> Field {
+        let stripped = self.strip_footnotes(&REFS.replace_all(raw, ""));
+        let mut links = vec![];
+        for link in self.links.parse(&stripped) {
+            if link.is_article() && !links.contains(&link.target) {
+                links.push(link.target);
+            }
+        }
+        let text = PlainText::new(&self.links).render(&self.unwrap_templates(&stripped));
+        Field {
+            raw: raw.trim().to_owned(),
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            links,
+        }
+    }
+
+    /// Remove the footnote templates in `FOOTNOTES` from wikitext.
+    fn strip_footnotes(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut end = 0;
+        for call in self.templates.parse(text) {
+            let footnote = call.kind == CallKind::Template
+                && FOOTNOTES.contains(&call.template_name().to_lowercase().as_str());
+            if footnote && call.span.start >= end {
+                out.push_str(&text[end..call.span.start]);
+                end = call.span.end;
+            }
+        }
+        out.push_str(&text[end..]);
+        out
+    }
+
+    /// Replace each template call in wikitext with its positional arguments,
+    /// themselves unwrapped, and drop parser functions and variables.
+    fn unwrap_templates(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut end = 0;
+        for call in self.templates.parse(text) {
+            if call.span.start < end {
+                continue;
+            }
+            out.push_str(&text[end..call.span.start]);
+            end = call.span.end;
+            if call.kind != CallKind::Template {
+                continue;
+            }
+            for arg in call.args.iter().filter(|arg| arg.positional) {
+                out.push(' ');
+                out.push_str(&self.unwrap_templates(&arg.value));
+            }
+            out.push(' ');
+        }
+        out.push_str(&text[end..]);
+        out
+    }
+}
+
+/// Counts of pages using an infobox type and of the fields they set.
+#[derive(Clone, Debug, Default)]
+pub struct InfoboxCounts {
+    pub pages: usize,
+    pub fields: HashMap<String, usize>,
+}
+
+/// Frequencies of fields per infobox type.
+#[derive(Clone, Debug, Default)]
+pub struct InfoboxReport {
+    pub types: HashMap<String, InfoboxCounts>,
+}
+
+impl InfoboxReport {
+    /// Count the infoboxes of a page. Each field is counted once per page.
+    pub fn add(&mut self, infoboxes: &[Infobox]) {
+        let mut seen: HashMap<&str, Vec<&str>> = HashMap::new();
+        for infobox in infoboxes {
+            let fields = seen.entry(&infobox.kind).or_insert_with(Vec::new);
+            fields.extend(infobox.fields.keys().map(|field| field.as_str()));
+        }
+        for (kind, mut fields) in seen {
+            fields.sort();
+            fields.dedup();
+            let counts = self.types.entry(kind.to_owned()).or_default();
+            counts.pages += 1;
+            for field in fields {
+                *counts.fields.entry(field.to_owned()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Merge counts from another report.
+    pub fn merge(&mut self, other: InfoboxReport) {
+        for (kind, other) in other.types {
+            let counts = self.types.entry(kind).or_default();
+            counts.pages += other.pages;
+            for (field, count) in other.fields {
+                *counts.fields.entry(field).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// Write the report as tab-separated `type, pages, field, count, fraction`
+    /// rows, with types ordered by the number of pages using them and fields
+    /// by frequency.
+    pub fn write_tsv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut types = self.types.iter().collect::<Vec<_>>();
+        types.sort_by(|a, b| b.1.pages.cmp(&a.1.pages).then_with(|| a.0.cmp(b.0)));
+        for (kind, counts) in types {
+            let mut fields = counts.fields.iter().collect::<Vec<_>>();
+            fields.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            for (field, count) in fields {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{:.4}",
+                    kind,
+                    counts.pages,
+                    field,
+                    count,
+                    *count as f64 / counts.pages as f64
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Extract the infoboxes of pages, writing one JSON object per line with the
+/// page's ID, title and infoboxes for each page with at least one, and return
+/// the frequencies of their fields.
+pub fn extract_infoboxes<W: Write + Send + Sync>(
+    indices: &Indices,
+    data: &Path,
+    writer: &Mutex<W>,
+    filter: &Arc<PageFilter>,
+    extractor: &InfoboxExtractor,
+) -> InfoboxReport {
+    extract_folded(
+        indices,
+        data,
+        writer,
+        filter,
+        |report: &mut InfoboxReport, id, title, text| {
+            let infoboxes = extractor.extract(text);
+            if infoboxes.is_empty() {
+                return None;
+            }
+            report.add(&infoboxes);
+            Some(json!({
+                "id": id.parse::<u64>().ok(),
+                "title": title,
+                "infoboxes": infoboxes,
+            }))
+        },
+        InfoboxReport::merge,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_extract_infoboxes() {
+        let extractor = InfoboxExtractor::new(
+            &[r"^Infobox\b".to_owned()],
+            TemplateParser::default(),
+            Arc::new(LinkParser::default()),
+        )
+        .unwrap();
+        let text = "{{Short description|Legendary musician}}\
+                    {{infobox deity | name = Orpheus<ref>{{cite book|title=[[Metamorphoses]]}}</ref>\
+                    | image = \
+                    | consort = [[Eurydice]]<!-- [[Calliope]] -->\
+                    | parents = [[Oeagrus]] and [[Calliope]] {{efn|Or [[Apollo]]}}\
+                    | abode = [[Thrace]]\n}} '''Orpheus''' was a musician.";
+        let infoboxes = extractor.extract(text);
+        assert_eq!(infoboxes.len(), 1);
+        let infobox = &infoboxes[0];
+        assert_eq!(infobox.kind, "Infobox deity");
+        assert_eq!(
+            infobox.fields.keys().collect::<Vec<_>>(),
+            vec!["abode", "consort", "name", "parents"]
+        );
+        let name = &infobox.fields["name"];
+        assert_eq!(name.text, "Orpheus");
+        assert!(name.links.is_empty());
+        assert!(name.raw.starts_with("Orpheus<ref>"));
+        let parents = &infobox.fields["parents"];
+        assert_eq!(parents.text, "Oeagrus and Calliope");
+        assert_eq!(parents.links, vec!["Oeagrus", "Calliope"]);
+        assert_eq!(infobox.fields["consort"].links, vec!["Eurydice"]);
+
+        let text = "{{Infobox person | children = {{plainlist|\n* [[Musaeus]]\n* [[Linus]]\n}}\
+                    | spouse = {{marriage|[[Eurydice]]|{{circa|1300 BC}}}}{{sfn|Ovid|p=10}}}}";
+        let infobox = &extractor.extract(text)[0];
+        let children = &infobox.fields["children"];
+        assert_eq!(children.text, "Musaeus Linus");
+        assert_eq!(children.links, vec!["Musaeus", "Linus"]);
+        let spouse = &infobox.fields["spouse"];
+        assert_eq!(spouse.text, "Eurydice 1300 BC");
+        assert_eq!(spouse.links, vec!["Eurydice"]);
+        let field = extractor.field("{{plainlist|* [[A]] * [[B]]}}");
+        assert_eq!(field.links, vec!["A", "B"]);
+
+        let mut report = InfoboxReport::default();
+        report.add(&infoboxes);
+        report.add(&extractor.extract("{{Infobox deity|name=Eurydice}}"));
+        let mut tsv = vec![];
+        report.write_tsv(&mut tsv).unwrap();
+        let tsv = String::from_utf8(tsv).unwrap();
+        assert_eq!(
+            tsv.lines().take(2).collect::<Vec<_>>(),
+            vec![
+                "Infobox deity\t2\tname\t2\t1.0000",
+                "Infobox deity\t2\tabode\t1\t0.5000",
+            ]
+        );
+    }
+}
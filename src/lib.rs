@@ -1,8 +1,13 @@
 #![allow(dead_code, unused_imports)]
+pub mod category;
+pub mod external_sort;
 pub mod extract;
 pub mod find_indices;
 pub mod indices;
+pub mod infobox;
 pub mod loaders;
+pub mod lookup;
+pub mod pipeline;
 pub mod redirect;
 pub mod settings;
 pub mod surface_forms;
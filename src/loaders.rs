@@ -1,28 +1,205 @@
-use std::io;
+use crate::category::CategoryGraph;
+use crate::indices::{read_indices, write_all_indices, write_template_indices, Indices};
+use crate::infobox::InfoboxExtractor;
+use crate::pipeline::{Pipeline, Stage};
+use crate::redirect::AliasFilter;
 use crate::settings::Settings;
-use log::{debug, info};
-use crate::indices::{read_indices, write_all_indices, write_template_indices, WikiDumpIndices};
+use crate::template::Templates;
+use crate::utils::open_bzip;
+use log::{debug, info, warn};
+use std::io;
+use std::sync::Arc;
+use storage::fst::WikiAnchors;
+use storage::page::{
+    AnyFilter, LinkParser, NamespaceFilter, Namespaces, PageFilter, PageRules, RulesFilter,
+    SiteInfo, TemplateParser, TitleNormaliser,
+};
+
+/// Load the `<siteinfo>` of the dump, falling back to the English Wikipedia
+/// namespaces and case rule if the dump cannot be read.
+///
+/// Load it once and pass it to the other loaders and to
+/// `Pipeline::with_site_info`, rather than reading the dump for each.
+pub fn load_site_info(settings: &Settings) -> SiteInfo {
+    // The siteinfo occupies the first stream of a multistream dump.
+    match open_bzip(&settings.data.dump)
+        .ok()
+        .and_then(SiteInfo::parse)
+    {
+        Some(info) => {
+            debug!(
+                "Loaded {} namespaces from {:?}",
+                info.namespaces.iter().count(),
+                settings.data.dump
+            );
+            info
+        }
+        None => {
+            warn!(
+                "No siteinfo found in {:?}; assuming English namespaces",
+                settings.data.dump
+            );
+            SiteInfo {
+                namespaces: Namespaces::english(),
+                ..Default::default()
+            }
+        }
+    }
+}
+
+/// Load the filter for pages from which to extract pages, anchors and the
+/// search index.
+pub fn load_page_filter(
+    settings: &Settings,
+    site: &SiteInfo,
+) -> Result<Arc<PageFilter>, regex::Error> {
+    Ok(Arc::new(RulesFilter::new(
+        &settings.pages,
+        site.namespaces.clone(),
+    )?))
+}
+
+/// Load the filter for pages from which to extract categories: the pages
+/// selected by `pages` along with all category pages, which give the parents
+/// of each category.
+pub fn load_category_filter(
+    settings: &Settings,
+    site: &SiteInfo,
+) -> Result<Arc<PageFilter>, regex::Error> {
+    let categories = RulesFilter::new(&PageRules::categories(), site.namespaces.clone())?;
+    let pages = RulesFilter::new(&settings.pages, site.namespaces.clone())?;
+    Ok(Arc::new(AnyFilter(vec![
+        Arc::new(pages),
+        Arc::new(categories),
+    ])))
+}
+
+/// Load the parser for links in page text, normalising link targets with the
+/// namespaces and case rule of the dump and using the configured link trail.
+pub fn load_link_parser(
+    settings: &Settings,
+    site: &SiteInfo,
+) -> Result<Arc<LinkParser>, regex::Error> {
+    let titles = TitleNormaliser::from_site_info(site);
+    Ok(Arc::new(LinkParser::new(titles, &settings.links.trail)?))
+}
+
+/// Load the extractor for infoboxes, parsing templates and links with the
+/// namespaces and case rule of the dump.
+pub fn load_infobox_extractor(
+    settings: &Settings,
+    site: &SiteInfo,
+) -> Result<InfoboxExtractor, regex::Error> {
+    let titles = TitleNormaliser::from_site_info(site);
+    let links = Arc::new(LinkParser::new(titles.clone(), &settings.links.trail)?);
+    InfoboxExtractor::new(
+        &settings.infoboxes.patterns,
+        TemplateParser::new(titles),
+        links,
+    )
+}
 
+/// Load the templates compiled to `templates`, building them first if needed.
+pub fn load_templates(
+    settings: &Settings,
+    site: &SiteInfo,
+) -> Result<Templates, Box<std::error::Error>> {
+    Pipeline::new(settings)?
+        .with_site_info(site)
+        .build(&[Stage::Templates], &[])?;
+    let titles = TitleNormaliser::from_site_info(site);
+    info!("Loading templates from {:?}", settings.templates);
+    let templates = Templates::open(&settings.templates, titles)?;
+    debug!("Loaded {} templates", templates.len());
+    Ok(templates)
+}
+
+/// Load the category graph written by `wikitools categories`.
+pub fn load_category_graph(settings: &Settings) -> io::Result<CategoryGraph> {
+    info!("Loading categories from {:?}", settings.categories.links);
+    let graph = CategoryGraph::open(
+        &settings.categories.links,
+        Some(&settings.categories.hidden),
+    )?;
+    debug!("Loaded {} hidden categories", graph.hidden().count());
+    Ok(graph)
+}
+
+/// Load the filter selecting template pages.
+pub fn load_template_filter(site: &SiteInfo) -> Arc<PageFilter> {
+    let namespaces = site.namespaces.clone();
+    Arc::new(RulesFilter::new(&PageRules::templates(), namespaces).expect("no title patterns"))
+}
+
+/// Load the filter for pages which may be used as redirects and title surface
+/// forms.
+pub fn load_alias_filter(settings: &Settings, site: &SiteInfo) -> Arc<AliasFilter> {
+    let namespaces = site.namespaces.clone();
+    Arc::new(AliasFilter {
+        namespaces: NamespaceFilter::new(namespaces, &settings.namespaces.exclude_aliases),
+        excluded_prefixes: settings.namespaces.exclude_alias_prefixes.clone(),
+    })
+}
 
-pub fn build_or_load_page_indices(settings: &Settings) -> io::Result<WikiDumpIndices> {
-    if !settings.indices.pages.exists() {
-        debug!("Building page indices");
-        write_all_indices(&settings.data.index, &settings.indices.pages)
-    } else {
-        debug!("Loading page indices from {:?}", settings.indices.pages);
-        read_indices(&settings.indices.pages)
+pub fn build_or_load_page_indices(settings: &Settings) -> io::Result<Indices> {
+    let mut pipeline = Pipeline::new(settings)?;
+    match pipeline.status(Stage::PageIndices)? {
+        Some(reason) => {
+            debug!("Building page indices: {}", reason);
+            pipeline.invalidate(Stage::PageIndices)?;
+            let indices = write_all_indices(&settings.data.index, &settings.indices.pages)?;
+            pipeline.record(Stage::PageIndices)?;
+            Ok(indices.into())
+        }
+        None => {
+            debug!("Loading page indices from {:?}", settings.indices.pages);
+            read_indices(&settings.indices.pages)
+        }
     }
 }
 
-pub fn build_or_load_template_indices(settings: &Settings) -> io::Result<WikiDumpIndices> {
-    if !settings.indices.templates.exists() {
-        debug!("Building template indices");
-        write_template_indices(&settings.data.index, &settings.indices.templates)
-    } else {
-        debug!(
-            "Loading template indices from {:?}",
-            settings.indices.templates
+pub fn build_or_load_template_indices(settings: &Settings, site: &SiteInfo) -> io::Result<Indices> {
+    let mut pipeline = Pipeline::new(settings)?;
+    match pipeline.status(Stage::TemplateIndices)? {
+        Some(reason) => {
+            debug!("Building template indices: {}", reason);
+            pipeline.invalidate(Stage::TemplateIndices)?;
+            let indices = write_template_indices(
+                &settings.data.index,
+                &settings.indices.templates,
+                &site.namespaces,
+            )?;
+            pipeline.record(Stage::TemplateIndices)?;
+            Ok(indices.into())
+        }
+        None => {
+            debug!(
+                "Loading template indices from {:?}",
+                settings.indices.templates
+            );
+            read_indices(&settings.indices.templates)
+        }
+    }
+}
+
+/// Load the anchor FST, along with title surface forms and link counts if they
+/// have been built.
+pub fn load_anchors(settings: &Settings) -> fst::Result<WikiAnchors> {
+    info!("Loading anchors from {:?}", settings.anchors.fst);
+    let mut anchors = WikiAnchors::new(&settings.anchors.fst)?;
+    if settings.anchors.titles.exists() {
+        info!(
+            "Loading title surface forms from {:?}",
+            settings.anchors.titles
         );
-        read_indices(&settings.indices.templates)
+        anchors = anchors.with_titles(&settings.anchors.titles)?;
     }
-}
\ No newline at end of file
+    if settings.anchors.link_probability.exists() {
+        info!(
+            "Loading link counts from {:?}",
+            settings.anchors.link_probability
+        );
+        anchors = anchors.with_link_probabilities(&settings.anchors.link_probability)?;
+    }
+    Ok(anchors)
+}
@@ -0,0 +1,229 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+
+use fst::{Map, MapBuilder};
+use log::info;
+use memmap::Mmap;
+use quick_xml::{self as qx, events::Event};
+
+use storage::page::{Page, TitleNormaliser};
+
+use crate::redirect::redirect_key;
+use crate::utils::{open_bzip, open_seek_bzip};
+
+/// Position of a page within a multistream dump.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageLocation {
+    /// Offset of the bzip2 stream containing the page.
+    pub offset: usize,
+    /// Page ID.
+    pub id: u64,
+    /// Page title, as given in the index.
+    pub title: String,
+}
+
+/// Parse a line of a multistream index, of the form `offset:id:title`.
+fn parse_index_line(line: &str) -> Option<(usize, u64, &str)> {
+    let mut parts = line.splitn(3, ':');
+    let offset = parts.next()?.parse::<usize>().ok()?;
+    let id = parts.next()?.parse::<u64>().ok()?;
+    let title = parts.next()?;
+    Some((offset, id, title))
+}
+
+/// Pack the position of a page record in the pages table into a FST value.
+fn pack(offset: usize, len: usize) -> u64 {
+    ((offset as u64) << 16) | len as u64
+}
+
+fn unpack(value: u64) -> (usize, usize) {
+    ((value >> 16) as usize, (value & 0xFFFF) as usize)
+}
+
+/// Build title and page ID lookup FSTs from a multistream index.
+///
+/// Writes `titles.fst`, mapping titles to page IDs, and `ids.fst`, mapping
+/// big-endian page IDs to records in `pages.table`, to the output directory.
+/// Each record holds the little-endian stream offset of a page followed by its
+/// title, and FST values hold the position and title length of each record.
+pub fn build_lookup(index: &Path, output_dir: &Path) -> Result<(), Box<std::error::Error>> {
+    let mut pages: Vec<(u64, usize, String)> = vec![];
+    for line in open_bzip(index)?.lines() {
+        let line = line?;
+        if let Some((offset, id, title)) = parse_index_line(&line) {
+            pages.push((id, offset, title.to_owned()));
+        }
+    }
+    info!("Read {} pages from {:?}", pages.len(), index);
+
+    pages.sort_by(|(i1, _, _), (i2, _, _)| i1.cmp(i2));
+    pages.dedup_by(|(i1, _, _), (i2, _, _)| i1 == i2);
+    let mut titles = pages
+        .iter()
+        .map(|(id, _, title)| (title.as_str(), *id))
+        .collect::<Vec<_>>();
+    titles.sort_by(|(t1, _), (t2, _)| t1.cmp(t2));
+    titles.dedup_by(|(t1, _), (t2, _)| t1 == t2);
+
+    fs::create_dir_all(output_dir)?;
+    let mut bld = MapBuilder::new(BufWriter::new(File::create(output_dir.join("titles.fst"))?))?;
+    bld.extend_iter(titles.into_iter())?;
+    bld.finish()?;
+
+    let mut bld = MapBuilder::new(BufWriter::new(File::create(output_dir.join("ids.fst"))?))?;
+    let mut table = BufWriter::new(File::create(output_dir.join("pages.table"))?);
+    let mut position = 0;
+    for (id, offset, title) in &pages {
+        if title.len() > 0xFFFF {
+            return Err(From::from(format!("page title too long: {}", title)));
+        }
+        table.write_all(&(*offset as u64).to_le_bytes())?;
+        table.write_all(title.as_bytes())?;
+        bld.insert(id.to_be_bytes(), pack(position, title.len()))?;
+        position += 8 + title.len();
+    }
+    bld.finish()?;
+    table.flush()?;
+    Ok(())
+}
+
+/// Lookup of page locations by title or page ID.
+pub struct PageLookup {
+    titles: Map,
+    ids: Map,
+    pages: Mmap,
+    normaliser: TitleNormaliser,
+}
+
+impl PageLookup {
+    /// Open lookup FSTs and the pages table written by `build_lookup`,
+    /// normalising titles with the dump's `normaliser`.
+    pub fn new<P: AsRef<Path>>(
+        dir: P,
+        normaliser: TitleNormaliser,
+    ) -> Result<Self, Box<std::error::Error>> {
+        let dir = dir.as_ref();
+        let titles = unsafe { Map::from_path(dir.join("titles.fst")) }?;
+        let ids = unsafe { Map::from_path(dir.join("ids.fst")) }?;
+        let pages = unsafe { Mmap::map(&File::open(dir.join("pages.table"))?)? };
+        Ok(PageLookup {
+            titles,
+            ids,
+            pages,
+            normaliser,
+        })
+    }
+
+    /// Find a page by title.
+    ///
+    /// Titles are matched exactly, then as normalised by `redirect_key`.
+    pub fn by_title(&self, title: &str) -> Option<PageLocation> {
+        let id = self
+            .titles
+            .get(title)
+            .or_else(|| self.titles.get(redirect_key(&self.normaliser, title)))?;
+        self.by_id(id)
+    }
+
+    /// Find a page by ID.
+    pub fn by_id(&self, id: u64) -> Option<PageLocation> {
+        let (position, len) = unpack(self.ids.get(id.to_be_bytes())?);
+        let record = self.pages.get(position..position + 8 + len)?;
+        let mut offset = [0; 8];
+        offset.copy_from_slice(&record[..8]);
+        Some(PageLocation {
+            offset: u64::from_le_bytes(offset) as usize,
+            id,
+            title: String::from_utf8_lossy(&record[8..]).into_owned(),
+        })
+    }
+}
+
+/// Title, ID and wikitext of a single page.
+#[derive(Clone, Debug, Default)]
+pub struct RawPage {
+    pub title: String,
+    pub id: u64,
+    pub text: String,
+}
+
+impl RawPage {
+    /// Parse links and categories from the page text.
+    pub fn to_page(&self) -> Page {
+        Page::new(self.title.clone(), self.id.to_string(), &self.text)
+    }
+}
+
+/// Scan a stream of pages for the page with the given ID.
+///
+/// The page ID is the first `<id>` within a `<page>`; later IDs belong to the
+/// revision and contributor. Unlike `PageIterator`, no pages are skipped.
+pub fn find_page<R: BufRead>(reader: R, id: u64) -> Option<RawPage> {
+    let mut reader = qx::Reader::from_reader(reader);
+    let mut buf = vec![];
+    let mut text_buf = vec![];
+    let mut page = RawPage::default();
+    let mut has_id = false;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref tag)) => match tag.name() {
+                b"page" => {
+                    page = RawPage::default();
+                    has_id = false;
+                }
+                b"title" => page.title = reader.read_text(b"title", &mut text_buf).ok()?,
+                b"id" if !has_id => {
+                    page.id = reader.read_text(b"id", &mut text_buf).ok()?.parse().ok()?;
+                    has_id = true;
+                }
+                b"text" => page.text = reader.read_text(b"text", &mut text_buf).ok()?,
+                _ => (),
+            },
+            Ok(Event::End(ref tag)) => {
+                if tag.name() == b"page" && page.id == id {
+                    return Some(page);
+                }
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            Ok(_) => (),
+        }
+        buf.clear();
+    }
+}
+
+/// Fetch a single page from a multistream dump, decompressing only the stream
+/// that contains it.
+pub fn fetch_page(data: &Path, location: &PageLocation) -> io::Result<Option<RawPage>> {
+    let reader = open_seek_bzip(data, location.offset)?;
+    Ok(find_page(reader, location.id))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_find_page() {
+        let xml = r#"
+<page>
+    <title>Alpha</title>
+    <id>1</id>
+    <revision><id>100</id><text>alpha text</text></revision>
+</page>
+<page>
+    <title>Beta</title>
+    <id>2</id>
+    <revision><id>1</id><text>[[Alpha|a]] beta text</text></revision>
+</page>"#;
+        let page = find_page(BufReader::new(xml.as_bytes()), 2).unwrap();
+        assert_eq!(page.title, "Beta");
+        assert_eq!(page.text, "[[Alpha|a]] beta text");
+        assert!(find_page(BufReader::new(xml.as_bytes()), 100).is_none());
+        assert_eq!(
+            parse_index_line("597:10:Foo: Bar"),
+            Some((597, 10, "Foo: Bar"))
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Version of the tool recorded against each built artefact.
+pub static TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Seconds since the epoch for a system time.
+fn epoch_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Size and modification time of a file or directory.
+///
+/// Directories are fingerprinted by the total size and latest modification
+/// time of the files they directly contain.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Fingerprint {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: u64,
+}
+
+impl Fingerprint {
+    /// Fingerprint the file or directory at a path.
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        let (size, modified) = if meta.is_dir() {
+            fs::read_dir(path)?.try_fold((0, 0), |(size, modified), entry| {
+                let meta = entry?.metadata()?;
+                Ok::<_, io::Error>((
+                    size + meta.len(),
+                    modified.max(epoch_secs(meta.modified()?)),
+                ))
+            })?
+        } else {
+            (meta.len(), epoch_secs(meta.modified()?))
+        };
+        Ok(Fingerprint {
+            path: path.to_path_buf(),
+            size,
+            modified,
+        })
+    }
+
+    /// Check if the file at the recorded path still matches this fingerprint.
+    pub fn is_current(&self) -> bool {
+        match Fingerprint::of(&self.path) {
+            Ok(current) => current == *self,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Record of a successfully built stage.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StageRecord {
+    /// Tool version that built the stage.
+    pub version: String,
+    /// Inputs at the time the stage was built.
+    pub inputs: Vec<Fingerprint>,
+    /// Hash of the settings the stage depends on, if any.
+    #[serde(default)]
+    pub settings: Option<u64>,
+    /// Output as written by the stage.
+    pub output: Fingerprint,
+    /// Time at which the stage finished, in seconds since the epoch.
+    pub completed: u64,
+}
+
+/// Record of all built stages, keyed by stage name.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Manifest {
+    pub stages: BTreeMap<String, StageRecord>,
+}
+
+impl Manifest {
+    /// Read a manifest, returning an empty manifest if the file does not
+    /// exist.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        let reader = BufReader::new(File::open(path)?);
+        serde_json::from_reader(reader).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write the manifest, replacing any existing file only once the new
+    /// manifest has been written in full.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        {
+            let writer = BufWriter::new(File::create(&tmp_path)?);
+            serde_json::to_writer_pretty(writer, self)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        }
+        fs::rename(tmp_path, path)
+    }
+
+    /// Record a completed stage.
+    pub fn insert(
+        &mut self,
+        name: &str,
+        inputs: Vec<Fingerprint>,
+        settings: Option<u64>,
+        output: Fingerprint,
+    ) {
+        let record = StageRecord {
+            version: TOOL_VERSION.to_owned(),
+            inputs,
+            settings,
+            output,
+            completed: epoch_secs(SystemTime::now()),
+        };
+        self.stages.insert(name.to_owned(), record);
+    }
+}
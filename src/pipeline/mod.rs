@@ -0,0 +1,299 @@
+pub mod manifest;
+pub mod stage;
+
+pub use self::{
+    manifest::{Fingerprint, Manifest},
+    stage::Stage,
+};
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use log::info;
+
+use storage::page::SiteInfo;
+
+use crate::loaders::load_site_info;
+use crate::settings::Settings;
+
+/// Reason a stage must be rebuilt.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reason {
+    /// Rebuild was requested.
+    Forced,
+    /// The output does not exist.
+    MissingOutput,
+    /// The output exists but was not recorded as complete.
+    NotRecorded,
+    /// The output was built by a different version of the tool.
+    VersionChanged(String),
+    /// Settings the stage depends on differ from when the output was built.
+    SettingsChanged,
+    /// An input differs from when the output was built.
+    InputChanged(PathBuf),
+    /// The output was modified after it was built.
+    OutputChanged,
+    /// A dependency will be rebuilt first.
+    UpstreamRebuilt(Stage),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::Forced => write!(f, "forced"),
+            Reason::MissingOutput => write!(f, "output missing"),
+            Reason::NotRecorded => write!(f, "output incomplete or not built by wikitools"),
+            Reason::VersionChanged(version) => write!(f, "built by wikitools {}", version),
+            Reason::SettingsChanged => write!(f, "settings changed"),
+            Reason::InputChanged(path) => write!(f, "input changed: {:?}", path),
+            Reason::OutputChanged => write!(f, "output modified since build"),
+            Reason::UpstreamRebuilt(stage) => write!(f, "{} will be rebuilt", stage),
+        }
+    }
+}
+
+/// Graph of stages with staleness detection against a manifest of previous
+/// builds.
+pub struct Pipeline<'a> {
+    settings: &'a Settings,
+    manifest: Manifest,
+    site: Option<&'a SiteInfo>,
+}
+
+impl<'a> Pipeline<'a> {
+    /// Create a pipeline, loading the manifest named in the settings.
+    pub fn new(settings: &'a Settings) -> io::Result<Self> {
+        let manifest = Manifest::load(&settings.manifest)?;
+        Ok(Pipeline {
+            settings,
+            manifest,
+            site: None,
+        })
+    }
+
+    /// Build stages with the `<siteinfo>` already loaded by the caller, rather
+    /// than reading it from the dump again.
+    pub fn with_site_info(mut self, site: &'a SiteInfo) -> Self {
+        self.site = Some(site);
+        self
+    }
+
+    /// Check whether a stage is up to date, returning the reason it must be
+    /// rebuilt if not.
+    pub fn status(&self, stage: Stage) -> io::Result<Option<Reason>> {
+        let output = stage.output(self.settings);
+        if !output.exists() {
+            return Ok(Some(Reason::MissingOutput));
+        }
+        let record = match self.manifest.stages.get(stage.name()) {
+            Some(record) => record,
+            None => return Ok(Some(Reason::NotRecorded)),
+        };
+        if record.version != manifest::TOOL_VERSION {
+            return Ok(Some(Reason::VersionChanged(record.version.clone())));
+        }
+        if record.settings != stage.settings_hash(self.settings) {
+            return Ok(Some(Reason::SettingsChanged));
+        }
+        let inputs = stage.inputs(self.settings);
+        for input in &inputs {
+            let current = Fingerprint::of(input)?;
+            if !record.inputs.iter().any(|recorded| *recorded == current) {
+                return Ok(Some(Reason::InputChanged(input.clone())));
+            }
+        }
+        // Inputs no longer read also invalidate the output, e.g. the redirect
+        // map once anchors are no longer resolved through it.
+        if let Some(dropped) = record
+            .inputs
+            .iter()
+            .find(|recorded| !inputs.contains(&recorded.path))
+        {
+            return Ok(Some(Reason::InputChanged(dropped.path.clone())));
+        }
+        if record.output.path != output || !record.output.is_current() {
+            return Ok(Some(Reason::OutputChanged));
+        }
+        Ok(None)
+    }
+
+    /// Determine which stages must be rebuilt to bring the targets up to date,
+    /// in build order.
+    ///
+    /// # Arguments
+    /// * `targets` - Stages to bring up to date, along with their dependencies.
+    /// * `forced` - Stages to rebuild regardless of their status.
+    pub fn plan(&self, targets: &[Stage], forced: &[Stage]) -> io::Result<Vec<(Stage, Reason)>> {
+        let mut required = targets.to_vec();
+        let mut i = 0;
+        while i < required.len() {
+            for dep in required[i].dependencies(self.settings) {
+                if !required.contains(&dep) {
+                    required.push(dep);
+                }
+            }
+            i += 1;
+        }
+
+        let mut plan: Vec<(Stage, Reason)> = vec![];
+        for stage in Stage::all().iter().filter(|stage| required.contains(stage)) {
+            let upstream = stage
+                .dependencies(self.settings)
+                .into_iter()
+                .find(|dep| plan.iter().any(|(planned, _)| planned == dep));
+            let reason = if forced.contains(stage) {
+                Some(Reason::Forced)
+            } else if let Some(dep) = upstream {
+                Some(Reason::UpstreamRebuilt(dep))
+            } else {
+                self.status(*stage)?
+            };
+            if let Some(reason) = reason {
+                plan.push((*stage, reason));
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Rebuild all out of date stages required by the targets.
+    pub fn build(
+        &mut self,
+        targets: &[Stage],
+        forced: &[Stage],
+    ) -> Result<(), Box<std::error::Error>> {
+        let plan = self.plan(targets, forced)?;
+        if plan.is_empty() {
+            info!("All stages up to date");
+            return Ok(());
+        }
+        let loaded;
+        let site = match self.site {
+            Some(site) => site,
+            None => {
+                loaded = load_site_info(self.settings);
+                &loaded
+            }
+        };
+        for (stage, reason) in plan {
+            info!("Rebuilding {} ({})", stage, reason);
+            self.invalidate(stage)?;
+            stage.build(self.settings, site)?;
+            self.record(stage)?;
+        }
+        Ok(())
+    }
+
+    /// Remove a stage from the manifest, so that an interrupted build is not
+    /// mistaken for a complete one.
+    pub fn invalidate(&mut self, stage: Stage) -> io::Result<()> {
+        if self.manifest.stages.remove(stage.name()).is_some() {
+            self.manifest.save(&self.settings.manifest)?;
+        }
+        Ok(())
+    }
+
+    /// Record a stage as successfully built from its current inputs.
+    pub fn record(&mut self, stage: Stage) -> io::Result<()> {
+        let inputs = stage
+            .inputs(self.settings)
+            .iter()
+            .map(|path| Fingerprint::of(path))
+            .collect::<io::Result<Vec<_>>>()?;
+        let output = Fingerprint::of(&stage.output(self.settings))?;
+        let settings = stage.settings_hash(self.settings);
+        self.manifest.insert(stage.name(), inputs, settings, output);
+        self.manifest.save(&self.settings.manifest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_staleness() {
+        let dir = std::env::temp_dir().join(format!("wikitools-pipeline-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut settings = Settings::default();
+        settings.data.index = dir.join("index.txt.bz2");
+        settings.data.dump = dir.join("dump.xml.bz2");
+        settings.indices.pages = dir.join("pages.idx");
+        settings.redirects = dir.join("redirects.tsv");
+        settings.redirect_map = dir.join("redirects.fst");
+        settings.manifest = dir.join("manifest.json");
+        fs::write(&settings.data.index, "index").unwrap();
+        fs::write(&settings.data.dump, "dump").unwrap();
+
+        let mut pipeline = Pipeline::new(&settings).unwrap();
+        assert_eq!(
+            pipeline.status(Stage::PageIndices).unwrap(),
+            Some(Reason::MissingOutput)
+        );
+        fs::write(&settings.indices.pages, "indices").unwrap();
+        assert_eq!(
+            pipeline.status(Stage::PageIndices).unwrap(),
+            Some(Reason::NotRecorded)
+        );
+        fs::write(&settings.redirects, "redirects").unwrap();
+        fs::write(&settings.redirect_map, "map").unwrap();
+        for stage in &[Stage::PageIndices, Stage::Redirects, Stage::RedirectMap] {
+            pipeline.record(*stage).unwrap();
+        }
+        assert_eq!(pipeline.status(Stage::PageIndices).unwrap(), None);
+        assert!(pipeline
+            .plan(&[Stage::RedirectMap], &[])
+            .unwrap()
+            .is_empty());
+
+        // The manifest is saved with each record.
+        let mut changed = settings.clone();
+        changed.namespaces.exclude_aliases.push(6);
+        let other = Pipeline::new(&changed).unwrap();
+        assert_eq!(other.status(Stage::PageIndices).unwrap(), None);
+        assert_eq!(
+            other.status(Stage::Redirects).unwrap(),
+            Some(Reason::SettingsChanged)
+        );
+
+        // Invalidating a stage rebuilds those depending on it.
+        pipeline.invalidate(Stage::Redirects).unwrap();
+        assert_eq!(
+            pipeline.plan(&[Stage::RedirectMap], &[]).unwrap(),
+            vec![
+                (Stage::Redirects, Reason::NotRecorded),
+                (
+                    Stage::RedirectMap,
+                    Reason::UpstreamRebuilt(Stage::Redirects)
+                ),
+            ]
+        );
+        pipeline.record(Stage::Redirects).unwrap();
+
+        fs::write(&settings.data.index, "changed index").unwrap();
+        assert_eq!(
+            pipeline.status(Stage::PageIndices).unwrap(),
+            Some(Reason::InputChanged(settings.data.index.clone()))
+        );
+        assert_eq!(
+            pipeline.plan(&[Stage::RedirectMap], &[]).unwrap(),
+            vec![
+                (
+                    Stage::PageIndices,
+                    Reason::InputChanged(settings.data.index.clone())
+                ),
+                (
+                    Stage::Redirects,
+                    Reason::UpstreamRebuilt(Stage::PageIndices)
+                ),
+                (
+                    Stage::RedirectMap,
+                    Reason::UpstreamRebuilt(Stage::Redirects)
+                ),
+            ]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,405 @@
+use std::fmt;
+use std::fs;
+use std::hash::Hasher;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use fnv::FnvHasher;
+use log::info;
+use serde_json::json;
+
+use storage::fst::{build_fst_from_anchors, build_fst_from_titles};
+use storage::link_probability::build_fst_from_link_counts;
+use storage::page::{SiteInfo, TitleNormaliser};
+use storage::tantivy::{build_index, TantivyWikiIndex};
+
+use crate::external_sort::{build_fst_from_runs, reduce_runs, remove_runs, MergedRuns, MAX_FAN_IN};
+use crate::extract::{
+    extract_anchor_counts_to_runs, extract_anchor_counts_to_trie, extract_linked_pages_to_runs,
+    AnchorTrieBuilder, TrieBuilderFlat, TrieBuilderRedirected,
+};
+use crate::indices::{
+    read_indices, read_titles, write_all_indices, write_template_indices, Indices,
+};
+use crate::loaders::{load_alias_filter, load_link_parser, load_page_filter, load_template_filter};
+use crate::lookup::build_lookup;
+use crate::redirect::{
+    map::targets_path, read_redirects, resolve_redirects, write_redirect_map, write_redirects,
+    RedirectMap,
+};
+use crate::settings::Settings;
+use crate::surface_forms::title_surface_forms;
+use crate::template::compile_templates;
+use crate::utils::mutex_bufwriter;
+
+/// An artefact built from a Wikipedia dump.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Stage {
+    /// Offsets of every stream in the multistream, and the pages within them.
+    PageIndices,
+    /// Offsets of streams containing template pages.
+    TemplateIndices,
+    /// Title and page ID lookup FSTs.
+    Lookup,
+    /// Pseudo-XML file of template pages.
+    Templates,
+    /// Tab-separated redirect pairs.
+    Redirects,
+    /// FST of redirects resolved to the page they end at.
+    RedirectMap,
+    /// FST of surface form/entity anchor counts.
+    Anchors,
+    /// FST of title, title-nv and redirect surface forms.
+    Titles,
+    /// Tantivy index of page content and links.
+    SearchIndex,
+    /// FST of link and document counts for each surface form.
+    LinkProbability,
+}
+
+impl Stage {
+    /// All stages, ordered such that each stage follows its dependencies.
+    pub fn all() -> &'static [Stage] {
+        &[
+            Stage::PageIndices,
+            Stage::TemplateIndices,
+            Stage::Lookup,
+            Stage::Templates,
+            Stage::Redirects,
+            Stage::RedirectMap,
+            Stage::Anchors,
+            Stage::Titles,
+            Stage::SearchIndex,
+            Stage::LinkProbability,
+        ]
+    }
+
+    /// Name of the stage, as used on the command line and in the manifest.
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::PageIndices => "page-indices",
+            Stage::TemplateIndices => "template-indices",
+            Stage::Lookup => "lookup",
+            Stage::Templates => "templates",
+            Stage::Anchors => "anchors",
+            Stage::Titles => "titles",
+            Stage::Redirects => "redirects",
+            Stage::RedirectMap => "redirect-map",
+            Stage::SearchIndex => "search-index",
+            Stage::LinkProbability => "link-probability",
+        }
+    }
+
+    /// Stages whose outputs are inputs to this stage.
+    ///
+    /// Anchors depend on the redirect map only if `anchors.resolve_redirects`
+    /// is set.
+    pub fn dependencies(self, settings: &Settings) -> Vec<Stage> {
+        match self {
+            Stage::PageIndices | Stage::TemplateIndices | Stage::Lookup => vec![],
+            Stage::Templates => vec![Stage::TemplateIndices],
+            Stage::RedirectMap => vec![Stage::Redirects],
+            Stage::Titles => vec![Stage::RedirectMap],
+            Stage::LinkProbability => vec![Stage::PageIndices, Stage::SearchIndex],
+            Stage::Anchors if settings.anchors.resolve_redirects => {
+                vec![Stage::PageIndices, Stage::RedirectMap]
+            }
+            Stage::Anchors | Stage::Redirects | Stage::SearchIndex => vec![Stage::PageIndices],
+        }
+    }
+
+    /// Paths read by this stage, including the outputs of its dependencies.
+    pub fn inputs(self, settings: &Settings) -> Vec<PathBuf> {
+        let mut inputs = match self {
+            Stage::PageIndices | Stage::Lookup | Stage::RedirectMap => {
+                vec![settings.data.index.clone()]
+            }
+            // Namespaces are read from the dump's siteinfo.
+            Stage::TemplateIndices | Stage::Titles => {
+                vec![settings.data.index.clone(), settings.data.dump.clone()]
+            }
+            _ => vec![settings.data.dump.clone()],
+        };
+        for dep in self.dependencies(settings) {
+            inputs.push(dep.output(settings));
+            // The redirect map's targets table is read along with its FST.
+            if dep == Stage::RedirectMap {
+                inputs.push(targets_path(&settings.redirect_map));
+            }
+        }
+        inputs
+    }
+
+    /// Hash of the settings that change this stage's output, if any.
+    ///
+    /// Paths and resource limits are left out, as they change where or how
+    /// the output is built but not what it contains. `--filter` replaces
+    /// `pages`, so is covered by it.
+    pub fn settings_hash(self, settings: &Settings) -> Option<u64> {
+        let relevant = match self {
+            Stage::Redirects | Stage::Titles => json!({ "namespaces": settings.namespaces }),
+            Stage::Anchors => json!({
+                "pages": settings.pages,
+                "trail": settings.links.trail,
+                "resolve_redirects": settings.anchors.resolve_redirects,
+            }),
+            Stage::SearchIndex | Stage::LinkProbability => json!({
+                "pages": settings.pages,
+                "trail": settings.links.trail,
+            }),
+            _ => return None,
+        };
+        let mut hasher = FnvHasher::default();
+        hasher.write(relevant.to_string().as_bytes());
+        Some(hasher.finish())
+    }
+
+    /// Path written by this stage.
+    pub fn output(self, settings: &Settings) -> PathBuf {
+        match self {
+            Stage::PageIndices => settings.indices.pages.clone(),
+            Stage::TemplateIndices => settings.indices.templates.clone(),
+            Stage::Lookup => settings.lookup.clone(),
+            Stage::Templates => settings.templates.clone(),
+            Stage::Anchors => settings.anchors.fst.clone(),
+            Stage::Redirects => settings.redirects.clone(),
+            Stage::RedirectMap => settings.redirect_map.clone(),
+            Stage::Titles => settings.anchors.titles.clone(),
+            Stage::SearchIndex => settings.search_index.index_dir.clone(),
+            Stage::LinkProbability => settings.anchors.link_probability.clone(),
+        }
+    }
+
+    /// Build the stage's output from its inputs, with the `<siteinfo>` of the
+    /// dump as loaded by `load_site_info`.
+    ///
+    /// Dependencies are assumed to be up to date.
+    pub fn build(self, settings: &Settings, site: &SiteInfo) -> Result<(), Box<std::error::Error>> {
+        info!("Building {}", self);
+        match self {
+            Stage::PageIndices => {
+                write_all_indices(&settings.data.index, &settings.indices.pages)?;
+            }
+            Stage::TemplateIndices => {
+                write_template_indices(
+                    &settings.data.index,
+                    &settings.indices.templates,
+                    &site.namespaces,
+                )?;
+            }
+            Stage::Lookup => {
+                if settings.lookup.exists() {
+                    fs::remove_dir_all(&settings.lookup)?;
+                }
+                build_lookup(&settings.data.index, &settings.lookup)?;
+            }
+            Stage::Templates => {
+                let indices = read_indices(&settings.indices.templates)?;
+                let filter = load_template_filter(site);
+                compile_templates(&indices, &settings.data.dump, &settings.templates, &filter);
+            }
+            Stage::Anchors => {
+                let indices = read_indices(&settings.indices.pages)?;
+                if settings.anchors.resolve_redirects {
+                    let titles = TitleNormaliser::from_site_info(site);
+                    let redirects = RedirectMap::new(&settings.redirect_map, titles)?;
+                    info!("Resolving anchors through {} redirects", redirects.len());
+                    build_anchors(
+                        TrieBuilderRedirected { redirects },
+                        &indices,
+                        settings,
+                        site,
+                    )?;
+                } else {
+                    build_anchors(TrieBuilderFlat, &indices, settings, site)?;
+                }
+            }
+            Stage::Redirects => {
+                let indices = read_indices(&settings.indices.pages)?;
+                let writer = mutex_bufwriter(&settings.redirects, 8192 * 16)?;
+                let filter = load_alias_filter(settings, site);
+                write_redirects(&indices, &settings.data.dump, &writer, &filter);
+                writer.into_inner().unwrap().flush()?;
+            }
+            Stage::RedirectMap => {
+                let normaliser = TitleNormaliser::from_site_info(site);
+                let redirects = read_redirects(&settings.redirects, normaliser)?;
+                let titles = read_titles(&settings.data.index)?;
+                let resolved = resolve_redirects(&redirects, Some(&titles));
+                info!("Resolved redirects:\n{}", resolved.stats);
+                write_redirect_map(&resolved.targets, &settings.redirect_map)?;
+            }
+            Stage::Titles => {
+                let titles = read_titles(&settings.data.index)?;
+                let normaliser = TitleNormaliser::from_site_info(site);
+                let redirects = RedirectMap::new(&settings.redirect_map, normaliser)?;
+                let filter = load_alias_filter(settings, site);
+                let surface_forms = title_surface_forms(&titles, &redirects, &filter);
+                build_fst_from_titles(surface_forms, &settings.anchors.titles)?;
+            }
+            Stage::SearchIndex => {
+                // Indexing appends documents, so start from an empty index.
+                let index_dir = &settings.search_index.index_dir;
+                if index_dir.exists() {
+                    fs::remove_dir_all(index_dir)?;
+                }
+                let indices = read_indices(&settings.indices.pages)?;
+                let filter = load_page_filter(settings, site)?;
+                let links = load_link_parser(settings, site)?;
+                let offsets = indices.offsets();
+                build_index(
+                    offsets.iter().collect(),
+                    &settings.data.dump,
+                    index_dir,
+                    settings.search_index.writer_heap,
+                    settings.search_index.chunk_size,
+                    &filter,
+                    &links,
+                )?;
+            }
+            Stage::LinkProbability => {
+                let indices = read_indices(&settings.indices.pages)?;
+                let filter = load_page_filter(settings, site)?;
+                let links = load_link_parser(settings, site)?;
+                let budget = settings.anchors.memory_budget.unwrap_or(LINK_COUNT_BUDGET);
+                let spill_dir = spill_dir(settings, &settings.anchors.link_probability);
+                fs::create_dir_all(&spill_dir)?;
+                info!(
+                    "Counting linked pages within {} bytes, spilling to {:?}",
+                    budget, spill_dir
+                );
+                let runs = extract_linked_pages_to_runs(
+                    &indices,
+                    &settings.data.dump,
+                    &spill_dir,
+                    budget,
+                    &filter,
+                    &links,
+                )?;
+                let runs = reduce_runs(runs, MAX_FAN_IN)?;
+                let index =
+                    TantivyWikiIndex::new(&settings.search_index.index_dir, links.titles().clone());
+                build_fst_from_link_counts(
+                    MergedRuns::new(&runs)?,
+                    &index,
+                    &settings.anchors.link_probability,
+                )?;
+                remove_runs(&runs)?;
+                if settings.anchors.spill_dir.is_none() {
+                    fs::remove_dir(&spill_dir)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Memory budget in bytes for counting linked pages if `anchors.memory_budget`
+/// is unset.
+static LINK_COUNT_BUDGET: usize = 1024 * 1024 * 1024;
+
+/// Directory for the sorted runs of an output: `anchors.spill_dir` if set, or
+/// the output path with a `.runs` extension.
+fn spill_dir(settings: &Settings, output: &Path) -> PathBuf {
+    match settings.anchors.spill_dir {
+        Some(ref dir) => dir.clone(),
+        None => output.with_extension("runs"),
+    }
+}
+
+/// Count anchors and build the anchor FST.
+///
+/// If `anchors.memory_budget` is set, counts are spilled to sorted runs on
+/// disk and merged into the FST; otherwise they are held in memory.
+fn build_anchors<Builder>(
+    builder: Builder,
+    indices: &Indices,
+    settings: &Settings,
+    site: &SiteInfo,
+) -> Result<(), Box<std::error::Error>>
+where
+    Builder: AnchorTrieBuilder<u32> + Sync,
+{
+    let filter = load_page_filter(settings, site)?;
+    let links = load_link_parser(settings, site)?;
+    let budget = match settings.anchors.memory_budget {
+        Some(budget) => budget,
+        None => {
+            let anchor_counts = extract_anchor_counts_to_trie(
+                builder,
+                indices,
+                &settings.data.dump,
+                &filter,
+                &links,
+            );
+            return build_fst_from_anchors(anchor_counts, &settings.anchors.fst);
+        }
+    };
+    let spill_dir = spill_dir(settings, &settings.anchors.fst);
+    fs::create_dir_all(&spill_dir)?;
+    info!(
+        "Counting anchors within {} bytes, spilling to {:?}",
+        budget, spill_dir
+    );
+    let runs = extract_anchor_counts_to_runs(
+        builder,
+        indices,
+        &settings.data.dump,
+        &spill_dir,
+        budget,
+        &filter,
+        &links,
+    )?;
+    build_fst_from_runs(runs, &settings.anchors.fst)?;
+    if settings.anchors.spill_dir.is_none() {
+        fs::remove_dir(&spill_dir)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Stage::all()
+            .iter()
+            .find(|stage| stage.name() == name)
+            .cloned()
+            .ok_or_else(|| format!("unknown stage: {}", name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_settings_hash() {
+        let settings = Settings::default();
+        let mut filtered = settings.clone();
+        filtered.pages.min_text_len = 100;
+        let mut trail = settings.clone();
+        trail.links.trail = "a-z".to_owned();
+
+        assert_ne!(
+            Stage::Anchors.settings_hash(&settings),
+            Stage::Anchors.settings_hash(&filtered)
+        );
+        assert_ne!(
+            Stage::SearchIndex.settings_hash(&settings),
+            Stage::SearchIndex.settings_hash(&trail)
+        );
+        assert_eq!(
+            Stage::Titles.settings_hash(&settings),
+            Stage::Titles.settings_hash(&filtered)
+        );
+        assert_eq!(Stage::Lookup.settings_hash(&filtered), None);
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use fst::{IntoStreamer, Map, MapBuilder, Streamer};
+
+use storage::page::TitleNormaliser;
+
+use super::redirect_key;
+use super::resolve::Target;
+
+/// Path of the targets table stored alongside a redirect map FST.
+pub fn targets_path(path: &Path) -> PathBuf {
+    let mut targets = path.as_os_str().to_owned();
+    targets.push(".targets");
+    targets.into()
+}
+
+/// Pack the position of a target in the targets table into a FST value.
+fn pack(offset: usize, len: usize) -> u64 {
+    ((offset as u64) << 16) | len as u64
+}
+
+fn unpack(value: u64) -> (usize, usize) {
+    ((value >> 16) as usize, (value & 0xFFFF) as usize)
+}
+
+/// Serialise resolved redirects as a FST of normalised titles and a table of
+/// their targets.
+///
+/// FST values hold the offset and length of each target in the table; shared
+/// targets are stored once.
+pub fn build_redirect_map<W: Write, T: Write>(
+    targets: &BTreeMap<String, Target>,
+    map_writer: W,
+    targets_writer: &mut T,
+) -> Result<(), Box<std::error::Error>> {
+    let mut builder = MapBuilder::new(map_writer)?;
+    let mut written: HashMap<String, u64> = HashMap::new();
+    let mut offset = 0;
+    for (key, target) in targets {
+        let target = target.to_string();
+        if target.len() > 0xFFFF {
+            return Err(From::from(format!("redirect target too long: {}", target)));
+        }
+        let value = match written.get(&target) {
+            Some(value) => *value,
+            None => {
+                targets_writer.write_all(target.as_bytes())?;
+                let value = pack(offset, target.len());
+                offset += target.len();
+                written.insert(target, value);
+                value
+            }
+        };
+        builder.insert(key, value)?;
+    }
+    builder.finish()?;
+    Ok(())
+}
+
+/// Write resolved redirects to a FST at `path`, and its targets table
+/// alongside.
+pub fn write_redirect_map(
+    targets: &BTreeMap<String, Target>,
+    path: &Path,
+) -> Result<(), Box<std::error::Error>> {
+    let map_writer = BufWriter::new(File::create(path)?);
+    let mut targets_writer = BufWriter::new(File::create(targets_path(path))?);
+    build_redirect_map(targets, map_writer, &mut targets_writer)?;
+    targets_writer.flush()?;
+    Ok(())
+}
+
+/// Queryable map of redirect titles to the page they resolve to.
+pub struct RedirectMap {
+    map: Map,
+    targets: Vec<u8>,
+    titles: TitleNormaliser,
+}
+
+impl RedirectMap {
+    /// Open a redirect map written by `write_redirect_map`, normalising
+    /// queried titles with the dump's `titles`.
+    ///
+    /// The FST is memory mapped; the targets table is read into memory.
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        titles: TitleNormaliser,
+    ) -> Result<Self, Box<std::error::Error>> {
+        let map = unsafe { Map::from_path(path.as_ref()) }?;
+        let targets = fs::read(targets_path(path.as_ref()))?;
+        Ok(RedirectMap {
+            map,
+            targets,
+            titles,
+        })
+    }
+
+    /// Create a redirect map from the output of `build_redirect_map`.
+    pub fn from_bytes(
+        map: Vec<u8>,
+        targets: Vec<u8>,
+        titles: TitleNormaliser,
+    ) -> fst::Result<Self> {
+        Ok(RedirectMap {
+            map: Map::from_bytes(map)?,
+            targets,
+            titles,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Fetch the target of a redirect, or `None` if the title is not a
+    /// resolvable redirect.
+    pub fn get(&self, title: &str) -> Option<Target> {
+        self.map
+            .get(redirect_key(&self.titles, title))
+            .map(|value| self.target(value))
+    }
+
+    /// Fetch the title of the page a title redirects to, or the title itself
+    /// if it is not a redirect.
+    pub fn resolve(&self, title: &str) -> String {
+        match self.get(title) {
+            Some(target) => target.title,
+            None => title.to_owned(),
+        }
+    }
+
+    fn target(&self, value: u64) -> Target {
+        let (offset, len) = unpack(value);
+        Target::parse(&String::from_utf8_lossy(
+            &self.targets[offset..offset + len],
+        ))
+    }
+
+    /// Fetch every normalised redirect title and its target, in sorted order.
+    pub fn entries(&self) -> Vec<(String, Target)> {
+        let mut entries = Vec::with_capacity(self.len());
+        let mut stream = self.map.stream();
+        while let Some((key, value)) = stream.next() {
+            entries.push((
+                String::from_utf8_lossy(key).into_owned(),
+                self.target(value),
+            ));
+        }
+        entries
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_redirect_map() {
+        let mut targets = BTreeMap::new();
+        targets.insert("UK".to_owned(), Target::parse("United Kingdom"));
+        targets.insert("Britain".to_owned(), Target::parse("United Kingdom"));
+        targets.insert(
+            "UK history".to_owned(),
+            Target::parse("United Kingdom#History"),
+        );
+
+        let mut map = vec![];
+        let mut table = vec![];
+        build_redirect_map(&targets, &mut map, &mut table).unwrap();
+        assert_eq!(&table[..], &b"United KingdomUnited Kingdom#History"[..]);
+
+        let redirects = RedirectMap::from_bytes(map, table, TitleNormaliser::default()).unwrap();
+        assert_eq!(redirects.len(), 3);
+        assert_eq!(redirects.resolve("uK"), "United Kingdom");
+        assert_eq!(
+            redirects.get("UK_history").unwrap().fragment,
+            Some("History".to_owned())
+        );
+        assert_eq!(redirects.get("France"), None);
+    }
+}
@@ -1,36 +1,71 @@
+pub mod map;
+pub mod resolve;
+
+pub use self::{
+    map::{write_redirect_map, RedirectMap},
+    resolve::{resolve_redirects, RedirectStats, ResolvedRedirects, Target},
+};
+
 use std::borrow::Cow;
-use std::io::{BufRead, Write};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::Path;
 use std::str;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use pbr;
 use quick_xml::{self as qx, events::Event};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::indices::WikiDumpIndices;
+use storage::page::{
+    namespace::{PORTAL, PROJECT, TEMPLATE},
+    NamespaceFilter, Namespaces, TitleNormaliser,
+};
+
+use crate::indices::Indices;
 use crate::utils::open_seek_bzip;
 
-/// Check if a Wikipedia page title constitutes a valid redirect.
-/// Wikipedia internal pages, templates, portals and lists are all currently
-/// ignored for the purposes of extracting redirects.
-fn is_valid_alias(title: &str) -> bool {
-    if title.starts_with("Wikipedia:")
-        || title.starts_with("Template:")
-        || title.starts_with("Portal:")
-        || title.starts_with("List of ")
-    {
-        return false;
+/// Filter deciding which page titles may be used as aliases, i.e. redirects
+/// and title surface forms.
+#[derive(Clone, Debug)]
+pub struct AliasFilter {
+    pub namespaces: NamespaceFilter,
+    pub excluded_prefixes: Vec<String>,
+}
+
+impl Default for AliasFilter {
+    /// Wikipedia internal pages, templates, portals and lists are ignored.
+    fn default() -> Self {
+        AliasFilter {
+            namespaces: NamespaceFilter::new(Namespaces::english(), &[PROJECT, TEMPLATE, PORTAL]),
+            excluded_prefixes: vec!["List of ".to_owned()],
+        }
+    }
+}
+
+impl AliasFilter {
+    /// Check if a Wikipedia page title constitutes a valid alias.
+    ///
+    /// # Arguments
+    /// * `title` - Page title.
+    /// * `ns` - Namespace ID of the page, if known; otherwise the namespace is
+    ///     determined from the title prefix.
+    pub fn is_valid(&self, title: &str, ns: Option<i32>) -> bool {
+        !self.namespaces.is_excluded(title, ns)
+            && !self
+                .excluded_prefixes
+                .iter()
+                .any(|prefix| title.starts_with(prefix.as_str()))
     }
-    true
 }
 
 /// An individual Wikipedia redirect.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Redirect {
-    from: String,
-    to: String,
+    pub from: String,
+    pub to: String,
 }
 
 /// Iterator over redirects in an XML file of Wikipedia data.
@@ -39,6 +74,8 @@ pub struct RedirectIterator<R: BufRead> {
     buf: Vec<u8>,
     text_buf: Vec<u8>,
     title: String,
+    ns: Option<i32>,
+    filter: Arc<AliasFilter>,
 }
 
 /// Extract the destination page for a <redirect> tag.
@@ -65,8 +102,16 @@ impl<R: BufRead> RedirectIterator<R> {
             buf: Default::default(),
             text_buf: Default::default(),
             title: Default::default(),
+            ns: None,
+            filter: Arc::new(AliasFilter::default()),
         }
     }
+
+    /// Skip redirects using the given filter rather than the default.
+    pub fn with_filter(mut self, filter: Arc<AliasFilter>) -> Self {
+        self.filter = filter;
+        self
+    }
 }
 
 impl<R: BufRead> Iterator for RedirectIterator<R> {
@@ -74,14 +119,20 @@ impl<R: BufRead> Iterator for RedirectIterator<R> {
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             match self.reader.read_event(&mut self.buf) {
-                Ok(Event::Start(ref tag)) => {
-                    if let b"title" = tag.name() {
+                Ok(Event::Start(ref tag)) => match tag.name() {
+                    b"page" => self.ns = None,
+                    b"title" => {
                         self.title = self.reader.read_text(b"title", &mut self.text_buf).unwrap();
                     }
-                }
+                    b"ns" => {
+                        let ns = self.reader.read_text(b"ns", &mut self.text_buf).unwrap();
+                        self.ns = ns.trim().parse::<i32>().ok();
+                    }
+                    _ => (),
+                },
                 Ok(Event::Empty(ref tag)) => {
                     if let b"redirect" = tag.name() {
-                        if is_valid_alias(&self.title) {
+                        if self.filter.is_valid(&self.title, self.ns) {
                             if let Some(to_title) = extract_to(tag) {
                                 let to_title = str::from_utf8(&to_title).unwrap();
                                 return Some(Redirect {
@@ -101,29 +152,93 @@ impl<R: BufRead> Iterator for RedirectIterator<R> {
     }
 }
 
-/// Dump all redirects to file as tab-separated pairs.
+/// Dump all redirects to file as tab-separated pairs, sorted by title.
 pub fn write_redirects<W: Write + Send + Sync>(
-    indices: &WikiDumpIndices,
+    indices: &Indices,
     data: &Path,
     writer: &Mutex<W>,
+    filter: &Arc<AliasFilter>,
 ) {
-    let indices = indices.keys().collect::<Vec<_>>();
+    let indices = indices.offsets();
 
     let pbar = Mutex::new(pbr::ProgressBar::new(indices.len() as u64));
 
-    indices.into_par_iter().for_each(|index| {
-        let reader = open_seek_bzip(&data, *index).unwrap();
-        let iter = RedirectIterator::new(reader);
-        let reds = iter.into_iter().collect::<Vec<Redirect>>();
-        {
-            let mut w = writer.lock().unwrap();
-            reds.into_iter().for_each(|red| {
-                writeln!(w, "{}\t{}", red.from, red.to).unwrap();
-            });
+    let mut redirects = indices
+        .par_iter()
+        .flat_map(|index| {
+            let reader = open_seek_bzip(&data, *index).unwrap();
+            let reds = RedirectIterator::new(reader)
+                .with_filter(filter.clone())
+                .collect::<Vec<Redirect>>();
+            {
+                let mut prog = pbar.lock().unwrap();
+                prog.inc();
+            }
+            reds
+        })
+        .collect::<Vec<_>>();
+    redirects.par_sort_unstable_by(|a, b| a.from.cmp(&b.from).then_with(|| a.to.cmp(&b.to)));
+
+    let mut w = writer.lock().unwrap();
+    redirects.into_iter().for_each(|red| {
+        writeln!(w, "{}\t{}", red.from, red.to).unwrap();
+    });
+}
+
+/// Normalise a title for redirect lookup.
+///
+/// Link targets differ from page titles in the use of underscores, spacing,
+/// the case of the first letter and so on, so titles are canonicalised with
+/// the namespaces and case rule of the dump; invalid titles are only trimmed.
+pub fn redirect_key(titles: &TitleNormaliser, title: &str) -> String {
+    match titles.parse(title) {
+        Some(title) => title.into_string(),
+        None => title.trim().to_owned(),
+    }
+}
+
+/// Map of redirect titles to their immediate, unresolved targets.
+#[derive(Clone, Debug, Default)]
+pub struct Redirects {
+    targets: HashMap<String, String>,
+    titles: TitleNormaliser,
+}
+
+impl Redirects {
+    /// Create an empty map, normalising titles with `titles`.
+    pub fn new(titles: TitleNormaliser) -> Self {
+        Redirects {
+            targets: HashMap::new(),
+            titles,
         }
-        {
-            let mut prog = pbar.lock().unwrap();
-            prog.inc();
+    }
+
+    /// Add a redirect.
+    pub fn insert(&mut self, from: &str, to: &str) {
+        self.targets
+            .insert(redirect_key(&self.titles, from), to.trim().to_owned());
+    }
+
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+}
+
+/// Read redirects written by `write_redirects`, normalising titles with the
+/// dump's `titles`.
+pub fn read_redirects<P: AsRef<Path>>(path: P, titles: TitleNormaliser) -> io::Result<Redirects> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut redirects = Redirects::new(titles);
+    for line in reader.lines() {
+        let line = line?;
+        let mut pair = line.splitn(2, '\t');
+        if let (Some(from), Some(to)) = (pair.next(), pair.next()) {
+            redirects.insert(from, to);
         }
-    });
+    }
+    Ok(redirects)
 }
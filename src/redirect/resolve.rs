@@ -0,0 +1,253 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use super::{redirect_key, Redirects};
+
+/// Maximum number of redirects followed when resolving a title.
+pub static MAX_HOPS: usize = 16;
+
+/// Page a redirect resolves to, along with the section it points at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Target {
+    pub title: String,
+    pub fragment: Option<String>,
+}
+
+impl Target {
+    /// Split a `Title#Section` redirect target.
+    pub fn parse(target: &str) -> Self {
+        match target.find('#') {
+            Some(index) => {
+                let fragment = target[index + 1..].trim();
+                Target {
+                    title: target[..index].trim().to_owned(),
+                    fragment: if fragment.is_empty() {
+                        None
+                    } else {
+                        Some(fragment.to_owned())
+                    },
+                }
+            }
+            None => Target {
+                title: target.trim().to_owned(),
+                fragment: None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.fragment {
+            Some(ref fragment) => write!(f, "{}#{}", self.title, fragment),
+            None => write!(f, "{}", self.title),
+        }
+    }
+}
+
+/// Outcome of following a chain of redirects from a title.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Resolution {
+    /// The title is not a redirect.
+    NotRedirect,
+    /// The chain ends at a page after `hops` redirects.
+    Page { target: Target, hops: usize },
+    /// The chain ends at a title with no page.
+    Broken { target: Target, hops: usize },
+    /// The chain returns to a title it has already visited.
+    Cycle(Vec<String>),
+    /// The chain is longer than `MAX_HOPS`.
+    TooLong,
+}
+
+impl Redirects {
+    /// Follow redirects from a title to the page they end at.
+    ///
+    /// The fragment of the last redirect in the chain to name a section is
+    /// kept. If `titles` is given, chains ending at a title not in the set are
+    /// reported as broken.
+    pub fn follow(&self, title: &str, titles: Option<&HashSet<String>>) -> Resolution {
+        let mut key = redirect_key(&self.titles, title);
+        let mut raw = match self.targets.get(&key) {
+            Some(raw) => raw,
+            None => return Resolution::NotRedirect,
+        };
+        let mut visited = vec![];
+        let mut fragment = None;
+        loop {
+            visited.push(key);
+            let next = Target::parse(raw);
+            if next.fragment.is_some() {
+                fragment = next.fragment;
+            }
+            key = redirect_key(&self.titles, &next.title);
+            if visited.contains(&key) {
+                return Resolution::Cycle(visited);
+            }
+            match self.targets.get(&key) {
+                Some(next_raw) if visited.len() < MAX_HOPS => raw = next_raw,
+                Some(_) => return Resolution::TooLong,
+                None => {
+                    let hops = visited.len();
+                    let target = Target {
+                        title: next.title,
+                        fragment,
+                    };
+                    if titles.map(|titles| !titles.contains(&key)).unwrap_or(false) {
+                        return Resolution::Broken { target, hops };
+                    }
+                    return Resolution::Page { target, hops };
+                }
+            }
+        }
+    }
+
+    /// Fetch the title of the page a title redirects to, or the title itself
+    /// if it is not a redirect or cannot be resolved.
+    pub fn resolve(&self, title: &str) -> String {
+        match self.follow(title, None) {
+            Resolution::Page { target, .. } => target.title,
+            _ => title.to_owned(),
+        }
+    }
+}
+
+/// Counts of redirect problems found while resolving a dump's redirects.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RedirectStats {
+    /// Redirects read.
+    pub redirects: usize,
+    /// Redirects resolved to an existing page.
+    pub resolved: usize,
+    /// Resolved redirects which pass through at least one other redirect.
+    pub double_redirects: usize,
+    /// Redirects ending at a title with no page.
+    pub broken: usize,
+    /// Redirects in or leading into a cycle.
+    pub cycles: usize,
+    /// Redirects whose chain is longer than `MAX_HOPS`.
+    pub too_long: usize,
+    /// Resolved redirects which point at a section.
+    pub fragments: usize,
+}
+
+impl fmt::Display for RedirectStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "redirects\t{}", self.redirects)?;
+        writeln!(f, "resolved\t{}", self.resolved)?;
+        writeln!(f, "double redirects\t{}", self.double_redirects)?;
+        writeln!(f, "broken\t{}", self.broken)?;
+        writeln!(f, "cycles\t{}", self.cycles)?;
+        writeln!(f, "too long\t{}", self.too_long)?;
+        write!(f, "section targets\t{}", self.fragments)
+    }
+}
+
+/// Redirects mapped directly to the page they end at.
+#[derive(Clone, Debug, Default)]
+pub struct ResolvedRedirects {
+    /// Normalised redirect titles and their resolved targets, in sorted order.
+    pub targets: BTreeMap<String, Target>,
+    pub stats: RedirectStats,
+}
+
+/// Resolve every redirect, collecting diagnostics along the way.
+///
+/// Broken, cyclic and overlong redirects are counted but left out of the
+/// resolved map.
+///
+/// # Arguments
+/// * `redirects` - Redirects to resolve.
+/// * `titles` - Titles of all pages in the dump, if known.
+pub fn resolve_redirects(
+    redirects: &Redirects,
+    titles: Option<&HashSet<String>>,
+) -> ResolvedRedirects {
+    let mut resolved = ResolvedRedirects::default();
+    for key in redirects.targets.keys() {
+        let stats = &mut resolved.stats;
+        stats.redirects += 1;
+        match redirects.follow(key, titles) {
+            Resolution::Page { target, hops } => {
+                stats.resolved += 1;
+                if hops > 1 {
+                    stats.double_redirects += 1;
+                }
+                if target.fragment.is_some() {
+                    stats.fragments += 1;
+                }
+                resolved.targets.insert(key.clone(), target);
+            }
+            Resolution::Broken { .. } => stats.broken += 1,
+            Resolution::Cycle(_) => stats.cycles += 1,
+            Resolution::TooLong => stats.too_long += 1,
+            Resolution::NotRedirect => unreachable!(),
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use storage::page::TitleNormaliser;
+
+    fn redirects(pairs: &[(&str, &str)]) -> Redirects {
+        let mut redirects = Redirects::new(TitleNormaliser::default());
+        for (from, to) in pairs {
+            redirects.insert(from, to);
+        }
+        redirects
+    }
+
+    #[test]
+    fn test_follow_chain() {
+        let redirects = redirects(&[("UK", "Britain"), ("Britain", "United Kingdom#History")]);
+        assert_eq!(
+            redirects.follow("UK", None),
+            Resolution::Page {
+                target: Target {
+                    title: "United Kingdom".to_owned(),
+                    fragment: Some("History".to_owned()),
+                },
+                hops: 2,
+            }
+        );
+        assert_eq!(
+            redirects.follow("United Kingdom", None),
+            Resolution::NotRedirect
+        );
+        assert_eq!(redirects.resolve("uK"), "United Kingdom");
+    }
+
+    #[test]
+    fn test_resolve_redirects() {
+        let redirects = redirects(&[
+            ("A", "B"),
+            ("B", "C"),
+            ("C", "A"),
+            ("D", "A"),
+            ("E", "Missing"),
+            ("F", "Page#Section"),
+            ("G", "F"),
+        ]);
+        let titles = ["Page"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<HashSet<_>>();
+        let resolved = resolve_redirects(&redirects, Some(&titles));
+        assert_eq!(
+            resolved.stats,
+            RedirectStats {
+                redirects: 7,
+                resolved: 2,
+                double_redirects: 1,
+                broken: 1,
+                cycles: 4,
+                too_long: 0,
+                fragments: 2,
+            }
+        );
+        assert_eq!(resolved.targets["G"].to_string(), "Page#Section");
+    }
+}
@@ -1,10 +1,13 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 use config::{Config, ConfigError, File};
 use serde::{Deserialize, Serialize};
 
+use storage::page::{links::ENGLISH_TRAIL, PageRules};
+
 /// Configuration for Wikipedia data sources.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Data {
     /// Path to the wikipedia dump.
     pub dump: PathBuf,
@@ -39,9 +42,41 @@ impl Default for Indices {
     }
 }
 
+/// Configuration for the tantivy search index.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SearchIndex {
+    #[serde(default = "SearchIndex::default_index_dir")]
     pub index_dir: PathBuf,
+    /// Index writer heap size in bytes.
+    #[serde(default = "SearchIndex::default_writer_heap")]
+    pub writer_heap: usize,
+    /// Number of dump streams to index between commits.
+    #[serde(default = "SearchIndex::default_chunk_size")]
+    pub chunk_size: usize,
+}
+
+impl SearchIndex {
+    pub fn default_index_dir() -> PathBuf {
+        "wiki-index".into()
+    }
+
+    pub fn default_writer_heap() -> usize {
+        1024 * 1024 * 1024
+    }
+
+    pub fn default_chunk_size() -> usize {
+        10_000
+    }
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        SearchIndex {
+            index_dir: SearchIndex::default_index_dir(),
+            writer_heap: SearchIndex::default_writer_heap(),
+            chunk_size: SearchIndex::default_chunk_size(),
+        }
+    }
 }
 
 /// Configuration for anchor summary files.
@@ -51,6 +86,27 @@ pub struct Anchors {
     pub anchors: PathBuf,
     #[serde(default = "Anchors::default_anchor_counts_path")]
     pub anchor_counts: PathBuf,
+    #[serde(default = "Anchors::default_fst_path")]
+    pub fst: PathBuf,
+    /// FST of title, title-nv and redirect surface forms.
+    #[serde(default = "Anchors::default_titles_path")]
+    pub titles: PathBuf,
+    /// FST of link and document counts for each surface form.
+    #[serde(default = "Anchors::default_link_probability_path")]
+    pub link_probability: PathBuf,
+    /// Count anchors against the page their target redirects to.
+    #[serde(default)]
+    pub resolve_redirects: bool,
+    /// Approximate memory budget in bytes for counting anchors. If set, counts
+    /// are spilled to sorted runs on disk and merged into the FST. Pages
+    /// linking each surface form are always counted this way, within 1 GiB if
+    /// unset.
+    #[serde(default)]
+    pub memory_budget: Option<usize>,
+    /// Directory for sorted runs; defaults to the FST path with a `.runs`
+    /// extension.
+    #[serde(default)]
+    pub spill_dir: Option<PathBuf>,
 }
 
 impl Anchors {
@@ -61,17 +117,241 @@ impl Anchors {
     pub fn default_anchor_counts_path() -> PathBuf {
         "anchor_counts.tsv".into()
     }
+
+    pub fn default_fst_path() -> PathBuf {
+        "anchors.fst".into()
+    }
+
+    pub fn default_titles_path() -> PathBuf {
+        "titles.fst".into()
+    }
+
+    pub fn default_link_probability_path() -> PathBuf {
+        "anchors.lp.fst".into()
+    }
+}
+
+impl Default for Anchors {
+    fn default() -> Self {
+        Anchors {
+            anchors: Anchors::default_anchors_path(),
+            anchor_counts: Anchors::default_anchor_counts_path(),
+            fst: Anchors::default_fst_path(),
+            titles: Anchors::default_titles_path(),
+            link_probability: Anchors::default_link_probability_path(),
+            resolve_redirects: false,
+            memory_budget: None,
+            spill_dir: None,
+        }
+    }
+}
+
+/// Configuration for namespace filtering.
+///
+/// Namespaces are identified by ID, with names read from the `<siteinfo>` of
+/// the dump so that localised prefixes are recognised.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Namespaces {
+    /// Namespaces whose pages are not used as redirects or title surface forms.
+    #[serde(default = "Namespaces::default_exclude_aliases")]
+    pub exclude_aliases: Vec<i32>,
+    /// Title prefixes of pages not used as redirects or title surface forms.
+    #[serde(default = "Namespaces::default_exclude_alias_prefixes")]
+    pub exclude_alias_prefixes: Vec<String>,
+}
+
+impl Namespaces {
+    /// Project, template and portal namespaces.
+    pub fn default_exclude_aliases() -> Vec<i32> {
+        vec![4, 10, 100]
+    }
+
+    pub fn default_exclude_alias_prefixes() -> Vec<String> {
+        vec!["List of ".to_owned()]
+    }
+}
+
+impl Default for Namespaces {
+    fn default() -> Self {
+        Namespaces {
+            exclude_aliases: Namespaces::default_exclude_aliases(),
+            exclude_alias_prefixes: Namespaces::default_exclude_alias_prefixes(),
+        }
+    }
+}
+
+/// Configuration for parsing links in page text.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Links {
+    /// Pattern matching letters after a link which are displayed as part of
+    /// it, e.g. `[[bus]]es`. Depends on the language of the wiki; empty for
+    /// languages without link trails.
+    #[serde(default = "Links::default_trail")]
+    pub trail: String,
+}
+
+impl Links {
+    pub fn default_trail() -> String {
+        ENGLISH_TRAIL.to_owned()
+    }
+}
+
+impl Default for Links {
+    fn default() -> Self {
+        Links {
+            trail: Links::default_trail(),
+        }
+    }
+}
+
+/// Configuration for infobox extraction.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Infoboxes {
+    /// Patterns matching the names of infobox templates, without their
+    /// namespace. Depends on the language of the wiki.
+    #[serde(default = "Infoboxes::default_patterns")]
+    pub patterns: Vec<String>,
+    /// Frequencies of fields per infobox type.
+    #[serde(default = "Infoboxes::default_report_path")]
+    pub report: PathBuf,
+}
+
+impl Infoboxes {
+    pub fn default_patterns() -> Vec<String> {
+        vec![r"^Infobox\b".to_owned()]
+    }
+
+    pub fn default_report_path() -> PathBuf {
+        "infobox_report.tsv".into()
+    }
+}
+
+impl Default for Infoboxes {
+    fn default() -> Self {
+        Infoboxes {
+            patterns: Infoboxes::default_patterns(),
+            report: Infoboxes::default_report_path(),
+        }
+    }
+}
+
+/// Configuration for category extraction.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Categories {
+    /// Categories of pages, and parent categories of category pages.
+    #[serde(default = "Categories::default_links_path")]
+    pub links: PathBuf,
+    /// Names of the categories hidden with `__HIDDENCAT__`.
+    #[serde(default = "Categories::default_hidden_path")]
+    pub hidden: PathBuf,
+    /// Expand templates to find the categories they add. Most hidden
+    /// categories are hidden by templates, so are only found if this is set.
+    #[serde(default = "Categories::default_expand_templates")]
+    pub expand_templates: bool,
+}
+
+impl Categories {
+    pub fn default_links_path() -> PathBuf {
+        "categories.tsv".into()
+    }
+
+    pub fn default_hidden_path() -> PathBuf {
+        "hidden_categories.txt".into()
+    }
+
+    pub fn default_expand_templates() -> bool {
+        true
+    }
+}
+
+impl Default for Categories {
+    fn default() -> Self {
+        Categories {
+            links: Categories::default_links_path(),
+            hidden: Categories::default_hidden_path(),
+            expand_templates: Categories::default_expand_templates(),
+        }
+    }
+}
+
+/// Configuration for template usage counts and the transclusion graph.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TemplateUsage {
+    /// Number of pages and calls per template and module.
+    #[serde(default = "TemplateUsage::default_usage_path")]
+    pub usage: PathBuf,
+    /// Edges from templates to the templates and modules they transclude.
+    #[serde(default = "TemplateUsage::default_graph_path")]
+    pub graph: PathBuf,
+    /// Templates and modules transcluded by each page, as JSONL.
+    #[serde(default = "TemplateUsage::default_pages_path")]
+    pub pages: PathBuf,
+}
+
+impl TemplateUsage {
+    pub fn default_usage_path() -> PathBuf {
+        "template_usage.tsv".into()
+    }
+
+    pub fn default_graph_path() -> PathBuf {
+        "template_graph.tsv".into()
+    }
+
+    pub fn default_pages_path() -> PathBuf {
+        "template_pages.jsonl".into()
+    }
+}
+
+impl Default for TemplateUsage {
+    fn default() -> Self {
+        TemplateUsage {
+            usage: TemplateUsage::default_usage_path(),
+            graph: TemplateUsage::default_graph_path(),
+            pages: TemplateUsage::default_pages_path(),
+        }
+    }
 }
 
 /// Settings aggregate.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Settings {
+    #[serde(default)]
     pub data: Data,
     #[serde(default)]
     pub indices: Indices,
     #[serde(default = "Settings::default_templates_path")]
     pub templates: PathBuf,
+    #[serde(default = "Settings::default_redirects_path")]
+    pub redirects: PathBuf,
+    /// FST of redirects resolved to the page they end at.
+    #[serde(default = "Settings::default_redirect_map_path")]
+    pub redirect_map: PathBuf,
+    /// Directory of title and page ID lookup FSTs.
+    #[serde(default = "Settings::default_lookup_path")]
+    pub lookup: PathBuf,
+    /// Record of built pipeline artefacts.
+    #[serde(default = "Settings::default_manifest_path")]
+    pub manifest: PathBuf,
+    #[serde(default)]
+    pub namespaces: Namespaces,
+    /// Rules selecting the pages from which pages, anchors and the search
+    /// index are extracted.
+    #[serde(default)]
+    pub pages: PageRules,
+    /// Named alternatives to `pages`, selected with `--filter`.
+    #[serde(default)]
+    pub filters: HashMap<String, PageRules>,
+    #[serde(default)]
+    pub links: Links,
+    #[serde(default)]
+    pub infoboxes: Infoboxes,
+    #[serde(default)]
+    pub template_usage: TemplateUsage,
+    #[serde(default)]
+    pub categories: Categories,
+    #[serde(default)]
     pub anchors: Anchors,
+    #[serde(default)]
     pub search_index: SearchIndex,
 }
 
@@ -85,4 +365,43 @@ impl Settings {
     pub fn default_templates_path() -> PathBuf {
         "templates.xml".into()
     }
+
+    pub fn default_redirects_path() -> PathBuf {
+        "redirects.tsv".into()
+    }
+
+    pub fn default_redirect_map_path() -> PathBuf {
+        "redirects.fst".into()
+    }
+
+    pub fn default_lookup_path() -> PathBuf {
+        "lookup".into()
+    }
+
+    pub fn default_manifest_path() -> PathBuf {
+        "manifest.json".into()
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            data: Data::default(),
+            indices: Indices::default(),
+            templates: Settings::default_templates_path(),
+            redirects: Settings::default_redirects_path(),
+            redirect_map: Settings::default_redirect_map_path(),
+            lookup: Settings::default_lookup_path(),
+            manifest: Settings::default_manifest_path(),
+            namespaces: Namespaces::default(),
+            pages: PageRules::default(),
+            filters: HashMap::new(),
+            links: Links::default(),
+            infoboxes: Infoboxes::default(),
+            template_usage: TemplateUsage::default(),
+            categories: Categories::default(),
+            anchors: Anchors::default(),
+            search_index: SearchIndex::default(),
+        }
+    }
 }
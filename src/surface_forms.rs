@@ -1,14 +1,18 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use qp_trie::{wrapper::BString, Trie};
 use rayon::prelude::*;
 
+use storage::page::{namespace::MAIN, strip_title, Anchor, LinkParser, PageFilter};
+use storage::surface_form::Source;
+
 use crate::extract::index_to_pages;
-use crate::indices::WikiDumpIndices;
-use crate::page::anchor::Anchor;
+use crate::indices::Indices;
+use crate::redirect::{AliasFilter, RedirectMap};
 use crate::utils::chunk_file;
 
 pub type AnchorCounts = Trie<BString, u32>;
@@ -24,18 +28,22 @@ fn format_anchor(anchor: Anchor) -> (String, String) {
 }
 
 /// Count anchors from a wikidump multistream, returning a map of surface forms.
-pub fn extract_anchor_counts(indices: &WikiDumpIndices, data: &Path) -> AnchorCounts {
-    let mut indices = indices.keys().collect::<Vec<_>>();
+pub fn extract_anchor_counts(
+    indices: &Indices,
+    data: &Path,
+    filter: &Arc<PageFilter>,
+    links: &Arc<LinkParser>,
+) -> AnchorCounts {
+    let indices = indices.offsets();
     let pbar = Mutex::new(pbr::ProgressBar::new(indices.len() as u64));
-    indices.sort();
 
     let anchor_counts: AnchorCounts = Trie::new();
     let anchor_counts = Mutex::new(anchor_counts);
 
     indices
-        .into_par_iter()
+        .par_iter()
         .map(|index| {
-            let pages = index_to_pages(data, index);
+            let pages = index_to_pages(data, index, filter, links);
             let anchors: AnchorCounts =
                 pages
                     .into_iter()
@@ -75,11 +83,12 @@ pub fn extract_anchor_counts_from_anchors<P: AsRef<Path>>(
     let anchor_counts: Trie<BString, u32> = Trie::new();
     let anchor_counts = Mutex::new(anchor_counts);
     let chunk_len = chunk_len.unwrap_or(256 * 1024 * 1024);
-    let chunks = chunk_file(anchor_file, chunk_len)?;
+    let chunks = chunk_file(&anchor_file, chunk_len)?;
     let pbar = Mutex::new(pbr::ProgressBar::new(chunks.len() as u64));
 
+    let anchor_file = anchor_file.as_ref();
     chunks.into_par_iter().for_each(|(start, end)| {
-        let mut file = File::open("anchors-2019-01-10").unwrap();
+        let mut file = File::open(anchor_file).unwrap();
         file.seek(SeekFrom::Start(start)).unwrap();
         let file = file.take(end - start);
         let file = BufReader::with_capacity(128 * 1024 * 1024, file);
@@ -115,9 +124,107 @@ pub fn write_anchor_counts<W: Write>(
     Ok(())
 }
 
-pub fn merge_surface_forms<P: AsRef<Path>>(anchor_file_path: P) -> io::Result<()> {
-    let _anchor_counts = extract_anchor_counts_from_anchors(anchor_file_path, None)?;
-    // extract_titles(titles_path)
-    // extract_redirects(redirects_path)
-    Ok(())
+/// Collect title, title-nv and redirect surface forms for every article.
+///
+/// Only titles in the article namespace are used, and redirects from and to
+/// articles. Returns a map of tab-separated surface form and entity pairs to
+/// `Source` flags. Redirect pages contribute their alias as a redirect surface
+/// form of their target, rather than a title of their own.
+///
+/// # Arguments
+/// * `titles` - Titles of all pages in the dump.
+/// * `redirects` - Resolved redirects.
+/// * `filter` - Filter for titles which may be used as surface forms.
+pub fn title_surface_forms(
+    titles: &HashSet<String>,
+    redirects: &RedirectMap,
+    filter: &AliasFilter,
+) -> HashMap<String, u64> {
+    let mut surface_forms: HashMap<String, u64> = HashMap::new();
+    let mut add = |surface: &str, entity: &str, source: Source| {
+        let pair = format!("{}\t{}", surface.trim().to_lowercase(), entity);
+        *surface_forms.entry(pair).or_insert(0) |= source.flag();
+    };
+
+    // Only articles are entities, so titles in other namespaces, such as
+    // `File:` or `Help:`, are skipped whether or not the filter excludes them.
+    let is_article = |title: &str| filter.namespaces.namespace_of(title, None) == MAIN;
+
+    for title in titles
+        .iter()
+        .filter(|title| is_article(title) && filter.is_valid(title, None))
+    {
+        if redirects.get(title).is_some() {
+            continue;
+        }
+        add(title, title, Source::Title);
+        let stripped = strip_title(title);
+        if stripped != title {
+            add(stripped, title, Source::TitleNv);
+        }
+    }
+    for (alias, target) in redirects.entries() {
+        if is_article(&alias) && is_article(&target.title) {
+            add(&alias, &target.title, Source::Redirect);
+        }
+    }
+    surface_forms
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    use storage::page::TitleNormaliser;
+
+    use crate::redirect::{map::build_redirect_map, Target};
+
+    #[test]
+    fn test_title_surface_forms() {
+        let mut targets = BTreeMap::new();
+        targets.insert("UK".to_owned(), Target::parse("United Kingdom"));
+        targets.insert(
+            "WP:NPOV".to_owned(),
+            Target::parse("Wikipedia:Neutral point of view"),
+        );
+        targets.insert("Flag".to_owned(), Target::parse("File:Flag.svg"));
+        let (mut map, mut table) = (vec![], vec![]);
+        build_redirect_map(&targets, &mut map, &mut table).unwrap();
+        let redirects = RedirectMap::from_bytes(map, table, TitleNormaliser::default()).unwrap();
+
+        let titles = [
+            "United Kingdom",
+            "UK",
+            "Mercury (planet)",
+            "File:Flag.svg",
+            "Help:Contents",
+            "Category:Planets",
+        ]
+        .iter()
+        .map(|title| title.to_string())
+        .collect::<HashSet<_>>();
+        let mut surface_forms = title_surface_forms(&titles, &redirects, &AliasFilter::default())
+            .into_iter()
+            .collect::<Vec<_>>();
+        surface_forms.sort();
+        assert_eq!(
+            surface_forms,
+            vec![
+                (
+                    "mercury\tMercury (planet)".to_owned(),
+                    Source::TitleNv.flag()
+                ),
+                (
+                    "mercury (planet)\tMercury (planet)".to_owned(),
+                    Source::Title.flag()
+                ),
+                ("uk\tUnited Kingdom".to_owned(), Source::Redirect.flag()),
+                (
+                    "united kingdom\tUnited Kingdom".to_owned(),
+                    Source::Title.flag()
+                ),
+            ]
+        );
+    }
 }
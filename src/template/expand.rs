@@ -0,0 +1,758 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use storage::page::{
+    Arg, CallKind, PageFilter, PageIterator, PageRules, RulesFilter, TantivyPageIterator,
+    TemplateCall, TemplateParser, TitleNormaliser,
+};
+
+lazy_static! {
+    static ref COMMENT: Regex = Regex::new(r"(?s)<!--.*?(?:-->|$)").unwrap();
+    static ref ONLYINCLUDE: Regex =
+        Regex::new(r"(?is)<onlyinclude\s*>(.*?)(?:</onlyinclude\s*>|$)").unwrap();
+    static ref NOINCLUDE: Regex =
+        Regex::new(r"(?is)<noinclude\s*>.*?(?:</noinclude\s*>|$)").unwrap();
+    static ref INCLUDEONLY: Regex =
+        Regex::new(r"(?is)<includeonly\s*>.*?(?:</includeonly\s*>|$)").unwrap();
+    static ref INCLUSION_TAGS: Regex =
+        Regex::new(r"(?i)</?(?:noinclude|includeonly|onlyinclude)\s*/?>").unwrap();
+    static ref REDIRECT: Regex = Regex::new(r"(?i)^\s*#redirect\s*:?\s*\[\[([^\]|#]+)").unwrap();
+}
+
+/// Maximum number of redirects followed to find a template.
+const MAX_REDIRECTS: usize = 5;
+
+/// Text of a template page as transcluded: only its `<onlyinclude>` sections
+/// if it has any, and otherwise all but its `<noinclude>` sections. Comments
+/// are removed.
+pub fn transcluded(source: &str) -> String {
+    let source = COMMENT.replace_all(source, "");
+    let source = if ONLYINCLUDE.is_match(&source) {
+        ONLYINCLUDE
+            .captures_iter(&source)
+            .map(|section| section[1].to_owned())
+            .collect::<String>()
+    } else {
+        source.into_owned()
+    };
+    let source = NOINCLUDE.replace_all(&source, "");
+    INCLUSION_TAGS.replace_all(&source, "").into_owned()
+}
+
+/// Text of a page as rendered on its own: all but its `<includeonly>`
+/// sections. Comments are removed.
+pub fn not_transcluded(source: &str) -> String {
+    let source = COMMENT.replace_all(source, "");
+    let source = INCLUDEONLY.replace_all(&source, "");
+    INCLUSION_TAGS.replace_all(&source, "").into_owned()
+}
+
+/// Template pages available for expansion, by title.
+#[derive(Clone, Debug)]
+pub struct Templates {
+    titles: TitleNormaliser,
+    /// Transcluded text of each template, by canonical title.
+    pages: HashMap<String, String>,
+    /// Targets of template redirects, by canonical title.
+    redirects: HashMap<String, String>,
+}
+
+impl Templates {
+    /// Create an empty set of templates, normalising titles with the given
+    /// normaliser.
+    pub fn new(titles: TitleNormaliser) -> Self {
+        Templates {
+            titles,
+            pages: HashMap::new(),
+            redirects: HashMap::new(),
+        }
+    }
+
+    /// Read templates from a file written by `compile_templates`.
+    pub fn read<R: Read>(reader: R, titles: TitleNormaliser) -> Self {
+        let filter: Arc<PageFilter> = Arc::new(
+            RulesFilter::new(&PageRules::templates(), titles.namespaces().clone())
+                .expect("no title patterns"),
+        );
+        let pages = PageIterator::new(BufReader::new(reader)).with_filter(filter);
+        let mut templates = Templates::new(titles);
+        for (_, title, source) in TantivyPageIterator(pages) {
+            templates.insert(&title, &source);
+        }
+        templates
+    }
+
+    /// Open a templates file written by `compile_templates`.
+    pub fn open<P: AsRef<Path>>(path: P, titles: TitleNormaliser) -> io::Result<Self> {
+        Ok(Templates::read(File::open(path)?, titles))
+    }
+
+    /// Add a template page, given its title and wikitext.
+    pub fn insert(&mut self, title: &str, source: &str) {
+        let title = self.titles.normalise(title);
+        match REDIRECT.captures(source) {
+            Some(target) => {
+                let target = self.titles.normalise(&target[1]);
+                self.redirects.insert(title, target);
+            }
+            None => {
+                self.pages.insert(title, transcluded(source));
+            }
+        }
+    }
+
+    /// Get the transcluded text of a template by its canonical title,
+    /// following redirects.
+    pub fn get(&self, title: &str) -> Option<&str> {
+        self.pages
+            .get(self.resolve(title))
+            .map(|text| text.as_str())
+    }
+
+    /// Follow redirects from a canonical title, returning the title of the
+    /// template they end at.
+    pub fn resolve<'t>(&'t self, title: &'t str) -> &'t str {
+        let mut title = title;
+        for _ in 0..MAX_REDIRECTS {
+            match self.redirects.get(title) {
+                Some(target) => title = target,
+                None => break,
+            }
+        }
+        title
+    }
+
+    /// Iterate over the canonical titles and transcluded text of templates,
+    /// excluding redirects.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pages
+            .iter()
+            .map(|(title, text)| (title.as_str(), text.as_str()))
+    }
+
+    /// Normaliser for the titles of templates.
+    pub fn titles(&self) -> &TitleNormaliser {
+        &self.titles
+    }
+
+    /// Number of templates, excluding redirects.
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+}
+
+/// Limits on the expansion of a page, beyond which calls expand to nothing.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Maximum depth of nested template calls.
+    pub max_depth: usize,
+    /// Maximum total size in bytes of the expanded text of templates.
+    pub max_size: usize,
+}
+
+impl Default for Limits {
+    /// The defaults of MediaWiki.
+    fn default() -> Self {
+        Limits {
+            max_depth: 40,
+            max_size: 2 * 1024 * 1024,
+        }
+    }
+}
+
+/// Arguments of the template being expanded, after expansion.
+#[derive(Debug, Default)]
+struct Frame {
+    args: HashMap<String, String>,
+}
+
+/// State of the expansion of a page.
+#[derive(Debug)]
+struct State<'a> {
+    /// Title of the page being expanded.
+    page: &'a str,
+    /// Titles of the templates being expanded.
+    stack: Vec<String>,
+    /// Size of the expanded text of templates so far.
+    size: usize,
+}
+
+/// Expands the template calls in a page.
+///
+/// Parameters, transclusion, the inclusion tags, page name variables and the
+/// `#if`, `#ifeq`, `#iferror`, `#ifexpr`, `#switch` and `#expr` parser
+/// functions are supported, along with the case functions; other parser
+/// functions, including `#invoke`, and missing templates expand to nothing.
+#[derive(Clone, Debug)]
+pub struct Expander<'a> {
+    templates: &'a Templates,
+    parser: TemplateParser,
+    limits: Limits,
+}
+
+impl<'a> Expander<'a> {
+    pub fn new(templates: &'a Templates) -> Self {
+        Expander {
+            templates,
+            parser: TemplateParser::new(templates.titles.clone()),
+            limits: Limits::default(),
+        }
+    }
+
+    /// Use the given limits rather than MediaWiki's.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Expand the templates in the text of a page.
+    ///
+    /// # Arguments
+    /// * `title` - Title of the page, for variables such as `{{PAGENAME}}`.
+    /// * `text` - Wikitext of the page.
+    pub fn expand(&self, title: &str, text: &str) -> String {
+        let mut state = State {
+            page: title,
+            stack: vec![],
+            size: 0,
+        };
+        self.expand_text(&not_transcluded(text), &Frame::default(), &mut state)
+    }
+
+    /// Expand the calls in `text`, which is part of a page or template.
+    fn expand_text(&self, text: &str, frame: &Frame, state: &mut State) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for call in self
+            .parser
+            .parse(text)
+            .iter()
+            .filter(|call| call.depth == 0)
+        {
+            out.push_str(&text[last..call.span.start]);
+            let expanded = match call.kind {
+                CallKind::Parameter => self.parameter(text, call, frame, state),
+                CallKind::Variable => page_variable(&call.name, state.page, &self.templates.titles)
+                    .unwrap_or_default(),
+                CallKind::ParserFunction => self.function(text, call, frame, state),
+                CallKind::Template => self.template(text, call, frame, state),
+            };
+            out.push_str(&expanded);
+            last = call.span.end;
+        }
+        out.push_str(&text[last..]);
+        out
+    }
+
+    /// Expand an argument of a call, trimming the result.
+    fn arg(&self, text: &str, arg: Option<&Arg>, frame: &Frame, state: &mut State) -> String {
+        match arg {
+            Some(arg) => self
+                .expand_text(&text[arg.span.clone()], frame, state)
+                .trim()
+                .to_owned(),
+            None => String::new(),
+        }
+    }
+
+    /// Expand `{{{name|default}}}` to the argument of the template, its
+    /// default, or else as written.
+    fn parameter(
+        &self,
+        text: &str,
+        call: &TemplateCall,
+        frame: &Frame,
+        state: &mut State,
+    ) -> String {
+        let name = self.expand_text(&text[call.head.clone()], frame, state);
+        if let Some(value) = frame.args.get(name.trim()) {
+            return value.clone();
+        }
+        match call.args.first() {
+            Some(default) => self.expand_text(&text[default.span.clone()], frame, state),
+            None => text[call.span.clone()].to_owned(),
+        }
+    }
+
+    /// Expand a template call, with its arguments expanded in the calling
+    /// frame.
+    fn template(
+        &self,
+        text: &str,
+        call: &TemplateCall,
+        frame: &Frame,
+        state: &mut State,
+    ) -> String {
+        if state.stack.len() >= self.limits.max_depth || state.size >= self.limits.max_size {
+            return String::new();
+        }
+        let title = if call.name.contains('{') {
+            let head = self.expand_text(&text[call.head.clone()], frame, state);
+            self.parser.template_title(head.trim()).1
+        } else {
+            call.name.clone()
+        };
+        // Templates including themselves would loop.
+        if state.stack.contains(&title) {
+            return String::new();
+        }
+        let source = match self.templates.get(&title) {
+            Some(source) => source,
+            None => return String::new(),
+        };
+
+        let mut args = HashMap::with_capacity(call.args.len());
+        for arg in &call.args {
+            let name = if arg.name.contains('{') {
+                self.expand_text(&arg.name, frame, state).trim().to_owned()
+            } else {
+                arg.name.clone()
+            };
+            let value = self.expand_text(&text[arg.span.clone()], frame, state);
+            let value = if arg.positional {
+                value
+            } else {
+                value.trim().to_owned()
+            };
+            args.insert(name, value);
+        }
+
+        state.stack.push(title);
+        let mut expanded = self.expand_text(source, &Frame { args }, state);
+        state.stack.pop();
+        state.size += expanded.len();
+        // Output starting with block syntax starts a new line, as in MediaWiki.
+        if expanded.starts_with(&['*', '#', ':', ';'][..]) || expanded.starts_with("{|") {
+            expanded.insert(0, '\n');
+        }
+        expanded
+    }
+
+    /// Expand a parser function.
+    fn function(
+        &self,
+        text: &str,
+        call: &TemplateCall,
+        frame: &Frame,
+        state: &mut State,
+    ) -> String {
+        let args = &call.args;
+        match call.name.as_str() {
+            "#if" => {
+                let test = self.arg(text, args.get(0), frame, state);
+                let branch = if test.is_empty() { 2 } else { 1 };
+                self.arg(text, args.get(branch), frame, state)
+            }
+            "#ifeq" => {
+                let left = self.arg(text, args.get(0), frame, state);
+                let right = self.arg(text, args.get(1), frame, state);
+                let branch = if equals(&left, &right) { 2 } else { 3 };
+                self.arg(text, args.get(branch), frame, state)
+            }
+            "#iferror" => {
+                let test = self.arg(text, args.get(0), frame, state);
+                if test.contains("class=\"error\"") {
+                    self.arg(text, args.get(1), frame, state)
+                } else if args.len() > 2 {
+                    self.arg(text, args.get(2), frame, state)
+                } else {
+                    test
+                }
+            }
+            "#ifexpr" => {
+                let test = self.arg(text, args.get(0), frame, state);
+                match evaluate(&test) {
+                    Ok(Some(value)) if value != 0.0 => self.arg(text, args.get(1), frame, state),
+                    Ok(_) => self.arg(text, args.get(2), frame, state),
+                    Err(error) => expression_error(&error),
+                }
+            }
+            "#expr" => {
+                let expr = self.arg(text, args.get(0), frame, state);
+                match evaluate(&expr) {
+                    Ok(Some(value)) => format_number(value),
+                    Ok(None) => String::new(),
+                    Err(error) => expression_error(&error),
+                }
+            }
+            "#switch" => self.switch(text, call, frame, state),
+            "lc" => self.arg(text, args.get(0), frame, state).to_lowercase(),
+            "uc" => self.arg(text, args.get(0), frame, state).to_uppercase(),
+            "lcfirst" | "ucfirst" => {
+                let value = self.arg(text, args.get(0), frame, state);
+                let mut chars = value.chars();
+                match chars.next() {
+                    Some(first) if call.name == "lcfirst" => {
+                        first.to_lowercase().chain(chars).collect()
+                    }
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => value,
+                }
+            }
+            name => {
+                // Variables given a page, e.g. `{{PAGENAME:Orpheus}}`.
+                let page = self.arg(text, args.get(0), frame, state);
+                page_variable(name, &page, &self.templates.titles).unwrap_or_default()
+            }
+        }
+    }
+
+    /// Expand `{{#switch:value|case=result|...|#default=result}}`. Cases
+    /// without a result fall through to the next result, and a final value
+    /// without a case is the default.
+    fn switch(&self, text: &str, call: &TemplateCall, frame: &Frame, state: &mut State) -> String {
+        let value = self.arg(text, call.args.get(0), frame, state);
+        let cases = call.args.get(1..).unwrap_or(&[]);
+        let mut matched = false;
+        let mut default = None;
+        for (index, arg) in cases.iter().enumerate() {
+            if arg.positional {
+                let case = self.arg(text, Some(arg), frame, state);
+                if index + 1 == cases.len() {
+                    return case;
+                }
+                matched = matched || equals(&case, &value);
+                continue;
+            }
+            let case = if arg.name.contains('{') {
+                self.expand_text(&arg.name, frame, state).trim().to_owned()
+            } else {
+                arg.name.clone()
+            };
+            if matched || equals(&case, &value) {
+                return self.arg(text, Some(arg), frame, state);
+            }
+            if case == "#default" {
+                default = Some(arg);
+            }
+        }
+        self.arg(text, default, frame, state)
+    }
+}
+
+/// Compare values as numbers if both are numeric, and otherwise as text.
+fn equals(left: &str, right: &str) -> bool {
+    match (left.parse::<f64>(), right.parse::<f64>()) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => left == right,
+    }
+}
+
+/// Expand a variable naming a page, e.g. `PAGENAME`, for the given title.
+fn page_variable(name: &str, page: &str, titles: &TitleNormaliser) -> Option<String> {
+    match name {
+        "!" => return Some("|".to_owned()),
+        "=" => return Some("=".to_owned()),
+        _ => (),
+    }
+    let title = titles.parse(page)?;
+    let value = match name {
+        "FULLPAGENAME" => title.as_str(),
+        "PAGENAME" => title.name(),
+        "BASEPAGENAME" => match title.name().rfind('/') {
+            Some(index) => &title.name()[..index],
+            None => title.name(),
+        },
+        "ROOTPAGENAME" => title.name().split('/').next().unwrap_or(""),
+        "SUBPAGENAME" => title.name().rsplit('/').next().unwrap_or(""),
+        "NAMESPACE" => {
+            let name_start = title.as_str().len() - title.name().len();
+            title.as_str()[..name_start].trim_end_matches(':')
+        }
+        "NAMESPACENUMBER" => return Some(title.ns.to_string()),
+        _ => return None,
+    };
+    Some(value.to_owned())
+}
+
+/// Error output of `#expr`, which `#iferror` detects.
+fn expression_error(error: &str) -> String {
+    format!(
+        "<strong class=\"error\">Expression error: {}</strong>",
+        error
+    )
+}
+
+/// Format the result of an expression, without a fractional part if it is a
+/// whole number.
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+/// A token of an `#expr` expression.
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Op(&'static str),
+}
+
+/// Operators of `#expr`, longest first.
+static OPERATORS: &[&str] = &[
+    "<=", ">=", "<>", "!=", "=", "<", ">", "+", "-", "*", "/", "(", ")",
+];
+
+/// Word operators of `#expr`.
+static WORD_OPERATORS: &[&str] = &["div", "mod", "and", "or", "not"];
+
+/// Evaluate an `#expr` expression, returning `None` if it is empty.
+///
+/// Numbers, parentheses, the arithmetic operators `+ - * / div mod`,
+/// comparisons `= <> != < > <= >=` and the logical operators `and or not`
+/// are supported.
+fn evaluate(expr: &str) -> Result<Option<f64>, String> {
+    let mut tokens = vec![];
+    let mut rest = expr.trim_start();
+    while !rest.is_empty() {
+        let number = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or_else(|| rest.len());
+        if number > 0 {
+            let value = rest[..number]
+                .parse::<f64>()
+                .map_err(|_| format!("Unrecognised number \"{}\".", &rest[..number]))?;
+            tokens.push(Token::Number(value));
+            rest = &rest[number..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(*op));
+            rest = &rest[op.len()..];
+        } else {
+            let word = rest
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or_else(|| rest.len());
+            let op = WORD_OPERATORS
+                .iter()
+                .find(|op| op.eq_ignore_ascii_case(&rest[..word]))
+                .ok_or_else(|| {
+                    let c = rest.chars().next().unwrap();
+                    format!("Unrecognised punctuation character \"{}\".", c)
+                })?;
+            tokens.push(Token::Op(*op));
+            rest = &rest[word..];
+        }
+        rest = rest.trim_start();
+    }
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let mut parser = ExprParser { tokens, pos: 0 };
+    let value = parser.or()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(Some(value)),
+        Some(Token::Op(")")) => Err("Unexpected closing bracket.".to_owned()),
+        Some(_) => Err("Unexpected number.".to_owned()),
+    }
+}
+
+/// Recursive descent parser for `#expr`, evaluating as it parses.
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+fn truth(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+impl ExprParser {
+    /// Consume the next token if it is one of `ops`.
+    fn eat(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn or(&mut self) -> Result<f64, String> {
+        let mut value = self.and()?;
+        while self.eat(&["or"]).is_some() {
+            let right = self.and()?;
+            value = truth(value != 0.0 || right != 0.0);
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<f64, String> {
+        let mut value = self.comparison()?;
+        while self.eat(&["and"]).is_some() {
+            let right = self.comparison()?;
+            value = truth(value != 0.0 && right != 0.0);
+        }
+        Ok(value)
+    }
+
+    fn comparison(&mut self) -> Result<f64, String> {
+        let mut value = self.sum()?;
+        while let Some(op) = self.eat(&["=", "<>", "!=", "<", ">", "<=", ">="]) {
+            let right = self.sum()?;
+            value = truth(match op {
+                "=" => value == right,
+                "<>" | "!=" => value != right,
+                "<" => value < right,
+                ">" => value > right,
+                "<=" => value <= right,
+                _ => value >= right,
+            });
+        }
+        Ok(value)
+    }
+
+    fn sum(&mut self) -> Result<f64, String> {
+        let mut value = self.product()?;
+        while let Some(op) = self.eat(&["+", "-"]) {
+            let right = self.product()?;
+            value = if op == "+" {
+                value + right
+            } else {
+                value - right
+            };
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.eat(&["*", "/", "div", "mod"]) {
+            let right = self.unary()?;
+            value = match op {
+                "*" => value * right,
+                "mod" => {
+                    let right = right.trunc() as i64;
+                    if right == 0 {
+                        return Err("Division by zero.".to_owned());
+                    }
+                    // The remainder of the least integer by -1 overflows.
+                    (value.trunc() as i64)
+                        .checked_rem(right)
+                        .ok_or_else(|| "Integer overflow.".to_owned())? as f64
+                }
+                _ if right == 0.0 => return Err("Division by zero.".to_owned()),
+                _ => value / right,
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        match self.eat(&["-", "+", "not"]) {
+            Some("-") => Ok(-self.unary()?),
+            Some("+") => self.unary(),
+            Some(_) => Ok(truth(self.unary()? == 0.0)),
+            None => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<f64, String> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Token::Number(value)) => {
+                self.pos += 1;
+                Ok(value)
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let value = self.or()?;
+                match self.eat(&[")"]) {
+                    Some(_) => Ok(value),
+                    None => Err("Unclosed bracket.".to_owned()),
+                }
+            }
+            Some(Token::Op(")")) => Err("Unexpected closing bracket.".to_owned()),
+            _ => Err("Missing operand.".to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::template::Template;
+
+    #[test]
+    fn test_expand() {
+        let mut templates = Templates::new(TitleNormaliser::default());
+        templates.insert(
+            "Template:Main",
+            "<noinclude>Hatnote.</noinclude>Main article: [[{{{1}}}]]{{#if:{{{2|}}}|, [[{{{2}}}]]}}",
+        );
+        templates.insert("Template:Further", "#REDIRECT [[Template:Main]]");
+        templates.insert(
+            "Template:Lang",
+            "{{#switch:{{{1}}}|grc|el=Greek|la=Latin|#default=Unknown}} {{lc:{{{2|}}}}}",
+        );
+        templates.insert("Template:Outer", "outer {{inner|{{{1}}}}}");
+        templates.insert("Template:Inner", "inner {{#ifexpr:{{{1}}} > 1|big|small}}");
+        templates.insert("Template:Loop", "again {{loop}}");
+        templates.insert(
+            "Template:Navbox",
+            "Docs<onlyinclude>{| class=\"navbox\"\n| {{{list}}}\n|}</onlyinclude>",
+        );
+
+        let expander = Expander::new(&templates);
+        let expand = |text: &str| expander.expand("Orpheus (opera)", text);
+        assert_eq!(
+            expand("{{main|Orpheus}} {{Further|Lyre|Kithara}}"),
+            "Main article: [[Orpheus]] Main article: [[Lyre]], [[Kithara]]"
+        );
+        assert_eq!(expand("{{lang|el|ΛΎΡΑ}} {{lang|la}}"), "Greek λύρα Latin ");
+        assert_eq!(expand("{{lang|fr}}"), "Unknown ");
+        assert_eq!(
+            expand("{{outer|3}} {{outer|1 - 1}}"),
+            "outer inner big outer inner small"
+        );
+        assert_eq!(expand("{{loop}}"), "again ");
+        assert_eq!(
+            expand("{{Navbox|list=[[Eurydice]]}}"),
+            "\n{| class=\"navbox\"\n| [[Eurydice]]\n|}"
+        );
+        assert_eq!(
+            expand("{{PAGENAME}} {{{1|x}}} {{{2}}}"),
+            "Orpheus (opera) x {{{2}}}"
+        );
+        assert_eq!(expand("{{missing}}<!-- comment -->"), "");
+        assert_eq!(
+            expand("{{#expr: (2 + 3) * 4 mod 7}} {{#expr: 1/0}}"),
+            "6 <strong class=\"error\">Expression error: Division by zero.</strong>"
+        );
+        assert_eq!(
+            expand("{{#expr: -9223372036854775808 mod -1}}"),
+            "<strong class=\"error\">Expression error: Integer overflow.</strong>"
+        );
+        assert_eq!(expand("{{#ifeq: 01 | 1 | same | different}}"), "same");
+
+        let expander = Expander::new(&templates).with_limits(Limits {
+            max_depth: 1,
+            ..Default::default()
+        });
+        assert_eq!(expander.expand("", "{{outer|3}}"), "outer ");
+
+        // Templates round trip through the compiled templates file.
+        let xml = Template::from_unclean(
+            "Template:Main".to_owned(),
+            "<noinclude>Docs & more</noinclude>See [[{{{1}}}]]".to_owned(),
+        )
+        .to_string();
+        let templates = Templates::read(xml.as_bytes(), TitleNormaliser::default());
+        assert_eq!(templates.get("Template:Main"), Some("See [[{{{1}}}]]"));
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+
+use storage::page::{namespace::MODULE, CallKind, PageFilter, TemplateParser, TitleNormaliser};
+
+use crate::extract::extract_folded;
+use crate::indices::Indices;
+use crate::template::Templates;
+
+/// Templates and Lua modules transcluded by a page or template, once per call.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transclusions {
+    /// Canonical titles of the templates called, including calls within the
+    /// arguments of other calls.
+    pub templates: Vec<String>,
+    /// Canonical titles of the modules invoked with `{{#invoke:...}}`.
+    pub modules: Vec<String>,
+}
+
+impl Transclusions {
+    /// Find the transclusions in wikitext. Calls whose template name is built
+    /// from parameters are skipped, and templates are resolved through the
+    /// redirects in `templates`.
+    pub fn find(text: &str, parser: &TemplateParser, templates: &Templates) -> Self {
+        let mut transclusions = Transclusions::default();
+        for call in parser.parse(text) {
+            match call.kind {
+                CallKind::Template if !call.name.contains('{') => {
+                    let title = templates.resolve(&call.name).to_owned();
+                    transclusions.templates.push(title);
+                }
+                CallKind::ParserFunction if call.name == "#invoke" => {
+                    if let Some(module) = call.get("1") {
+                        if !module.contains('{') {
+                            let module = module_title(module, templates.titles());
+                            transclusions.modules.push(module);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        transclusions
+    }
+}
+
+/// Canonical title of a module, given its name as invoked.
+fn module_title(name: &str, titles: &TitleNormaliser) -> String {
+    let prefix = match titles.namespaces().get(MODULE) {
+        Some(namespace) => namespace.name.clone(),
+        None => "Module".to_owned(),
+    };
+    titles.normalise(&format!("{}:{}", prefix, name.trim()))
+}
+
+/// Transclusion dependencies between templates.
+#[derive(Clone, Debug, Default)]
+pub struct TemplateGraph {
+    /// Templates and modules transcluded directly by each template.
+    dependencies: BTreeMap<String, Dependencies>,
+}
+
+/// Templates and modules transcluded directly by a template.
+#[derive(Clone, Debug, Default)]
+pub struct Dependencies {
+    pub templates: BTreeSet<String>,
+    pub modules: BTreeSet<String>,
+}
+
+impl TemplateGraph {
+    /// Build the graph of the templates in `templates`, from their text as
+    /// transcluded.
+    pub fn build(templates: &Templates, parser: &TemplateParser) -> Self {
+        let dependencies = templates
+            .iter()
+            .map(|(title, text)| {
+                let transclusions = Transclusions::find(text, parser, templates);
+                let dependencies = Dependencies {
+                    templates: transclusions
+                        .templates
+                        .into_iter()
+                        .filter(|template| template != title)
+                        .collect(),
+                    modules: transclusions.modules.into_iter().collect(),
+                };
+                (title.to_owned(), dependencies)
+            })
+            .collect();
+        TemplateGraph { dependencies }
+    }
+
+    /// Get the templates and modules a template transcludes directly.
+    pub fn get(&self, title: &str) -> Option<&Dependencies> {
+        self.dependencies.get(title)
+    }
+
+    /// Iterate over the modules invoked by templates.
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        self.dependencies
+            .values()
+            .flat_map(|dependencies| dependencies.modules.iter())
+            .map(|module| module.as_str())
+    }
+
+    /// Find the templates implemented with Lua, i.e. those invoking a module
+    /// directly or through the templates they transclude.
+    pub fn lua_templates(&self) -> HashSet<&str> {
+        let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (title, dependencies) in &self.dependencies {
+            for template in &dependencies.templates {
+                dependents
+                    .entry(template.as_str())
+                    .or_default()
+                    .push(title.as_str());
+            }
+        }
+        let mut lua = HashSet::new();
+        let mut queue = self
+            .dependencies
+            .iter()
+            .filter(|(_, dependencies)| !dependencies.modules.is_empty())
+            .map(|(title, _)| title.as_str())
+            .collect::<Vec<_>>();
+        while let Some(title) = queue.pop() {
+            if lua.insert(title) {
+                if let Some(dependents) = dependents.get(title) {
+                    queue.extend(dependents);
+                }
+            }
+        }
+        lua
+    }
+
+    /// Write the graph as tab-separated `template, dependency` edges, where
+    /// dependencies are templates or modules.
+    pub fn write_edges<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for (title, dependencies) in &self.dependencies {
+            for dependency in dependencies.templates.iter().chain(&dependencies.modules) {
+                writeln!(writer, "{}\t{}", title, dependency)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Number of pages transcluding a template or module, and of calls to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UsageCount {
+    pub pages: usize,
+    pub calls: usize,
+}
+
+/// Usage of templates and modules by pages.
+#[derive(Clone, Debug, Default)]
+pub struct TemplateUsage {
+    pub counts: HashMap<String, UsageCount>,
+    /// Titles of the modules among `counts`.
+    pub modules: HashSet<String>,
+}
+
+impl TemplateUsage {
+    /// Count the transclusions of a page.
+    pub fn add(&mut self, transclusions: &Transclusions) {
+        self.modules.extend(transclusions.modules.iter().cloned());
+        let mut seen = HashSet::new();
+        for title in transclusions.templates.iter().chain(&transclusions.modules) {
+            let count = self.counts.entry(title.clone()).or_default();
+            count.calls += 1;
+            if seen.insert(title) {
+                count.pages += 1;
+            }
+        }
+    }
+
+    /// Merge counts from another set of pages.
+    pub fn merge(&mut self, other: TemplateUsage) {
+        for (title, other) in other.counts {
+            let count = self.counts.entry(title).or_default();
+            count.pages += other.pages;
+            count.calls += other.calls;
+        }
+        self.modules.extend(other.modules);
+    }
+
+    /// Write tab-separated `template, pages, calls, lua` rows for every
+    /// template and module in the graph or used by a page, ordered by the
+    /// number of pages using them. `lua` is `true` for modules and templates
+    /// implemented with them.
+    pub fn write_tsv<W: Write>(&self, mut writer: W, graph: &TemplateGraph) -> io::Result<()> {
+        let mut lua = graph.lua_templates();
+        lua.extend(self.modules.iter().map(|module| module.as_str()));
+        lua.extend(graph.modules());
+        let mut titles = self
+            .counts
+            .keys()
+            .map(|title| title.as_str())
+            .chain(graph.dependencies.keys().map(|title| title.as_str()))
+            .chain(graph.modules())
+            .collect::<Vec<_>>();
+        titles.sort();
+        titles.dedup();
+        let mut rows = titles
+            .into_iter()
+            .map(|title| (title, self.counts.get(title).cloned().unwrap_or_default()))
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| b.1.pages.cmp(&a.1.pages).then_with(|| a.0.cmp(b.0)));
+        for (title, count) in rows {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}",
+                title,
+                count.pages,
+                count.calls,
+                lua.contains(title)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Titles in the order of their first call, without repeats.
+fn distinct(titles: &[String]) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    titles
+        .iter()
+        .map(|title| title.as_str())
+        .filter(|title| seen.insert(*title))
+        .collect()
+}
+
+/// Count the templates and modules transcluded by pages in a dump, writing
+/// one JSON object per line with the page's ID, title and the templates and
+/// modules it transcludes for each page transcluding any.
+pub fn count_template_usage<W: Write + Send + Sync>(
+    indices: &Indices,
+    data: &Path,
+    writer: &Mutex<W>,
+    filter: &Arc<PageFilter>,
+    templates: &Templates,
+    parser: &TemplateParser,
+) -> TemplateUsage {
+    extract_folded(
+        indices,
+        data,
+        writer,
+        filter,
+        |usage: &mut TemplateUsage, id, title, text| {
+            let transclusions = Transclusions::find(text, parser, templates);
+            if transclusions.templates.is_empty() && transclusions.modules.is_empty() {
+                return None;
+            }
+            usage.add(&transclusions);
+            Some(json!({
+                "id": id.parse::<u64>().ok(),
+                "title": title,
+                "templates": distinct(&transclusions.templates),
+                "modules": distinct(&transclusions.modules),
+            }))
+        },
+        TemplateUsage::merge,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_template_graph() {
+        let mut templates = Templates::new(TitleNormaliser::default());
+        templates.insert("Template:Cite web", "{{#invoke:citation/CS1|citation}}");
+        templates.insert("Template:Cite news", "{{cite web|{{{1}}}}}");
+        templates.insert("Template:Cn", "#REDIRECT [[Template:Citation needed]]");
+        templates.insert(
+            "Template:Citation needed",
+            "<sup>{{fix|text=citation needed}}</sup><noinclude>{{documentation}}</noinclude>",
+        );
+        templates.insert("Template:Fix", "[[{{{link|WP:V}}}|{{{text}}}]]");
+        let parser = TemplateParser::new(templates.titles().clone());
+        let graph = TemplateGraph::build(&templates, &parser);
+
+        let mut edges = vec![];
+        graph.write_edges(&mut edges).unwrap();
+        assert_eq!(
+            String::from_utf8(edges).unwrap(),
+            "Template:Citation needed\tTemplate:Fix\n\
+             Template:Cite news\tTemplate:Cite web\n\
+             Template:Cite web\tModule:Citation/CS1\n"
+        );
+        let mut lua = graph.lua_templates().into_iter().collect::<Vec<_>>();
+        lua.sort();
+        assert_eq!(lua, vec!["Template:Cite news", "Template:Cite web"]);
+
+        let mut usage = TemplateUsage::default();
+        for text in &[
+            "Lyre{{cn}} and kithara{{Citation needed}}.<ref>{{cite news|x}}</ref>",
+            "{{citation needed}} {{ {{{type}}} }}",
+        ] {
+            usage.add(&Transclusions::find(text, &parser, &templates));
+        }
+        assert_eq!(
+            usage.counts["Template:Citation needed"],
+            UsageCount { pages: 2, calls: 3 }
+        );
+        let page = Transclusions::find(
+            "Lyre{{cn}} and kithara{{Citation needed}}.<ref>{{cite news|x}}</ref>",
+            &parser,
+            &templates,
+        );
+        assert_eq!(
+            distinct(&page.templates),
+            vec!["Template:Citation needed", "Template:Cite news"]
+        );
+        let mut tsv = vec![];
+        usage.write_tsv(&mut tsv, &graph).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "Template:Citation needed\t2\t3\tfalse\n\
+             Template:Cite news\t1\t1\ttrue\n\
+             Module:Citation/CS1\t0\t0\ttrue\n\
+             Template:Cite web\t0\t0\ttrue\n\
+             Template:Fix\t0\t0\tfalse\n"
+        );
+    }
+}
@@ -1,21 +1,27 @@
+pub mod expand;
 pub mod extract;
+pub mod graph;
 pub mod template;
 pub mod writer;
 
 pub use self::{
+    expand::{Expander, Limits, Templates},
     extract::extract_templates,
+    graph::{count_template_usage, TemplateGraph, TemplateUsage, Transclusions},
     template::Template,
     writer::{FileTemplateWriter, TemplateWriter},
 };
 
 use std::fs::File;
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use pbr::ProgressBar;
 use rayon::prelude::*;
 
-use crate::indices::WikiDumpIndices;
+use storage::page::PageFilter;
+
+use crate::indices::Indices;
 use crate::utils::open_seek_bzip;
 
 /// Fetch templates from a Wikipedia dump, writing them to file.
@@ -27,21 +33,26 @@ use crate::utils::open_seek_bzip;
 ///
 /// # Arguments
 ///
-/// * `indices` - WikiDumpIndices indicating the offsets within the data file
+/// * `indices` - Indices indicating the offsets within the data file
 ///     for bundles containing template pages.
 /// * `data` - Path to the Wikipedia dump multistream bz2.
 /// * `output_path` - Output path to write the templates file to.
+/// * `filter` - Filter selecting template pages.
 ///
-pub fn compile_templates(indices: &WikiDumpIndices, data: &Path, output_path: &Path) {
-    let mut idx = indices.keys().cloned().collect::<Vec<_>>();
-    idx.sort();
+pub fn compile_templates(
+    indices: &Indices,
+    data: &Path,
+    output_path: &Path,
+    filter: &Arc<PageFilter>,
+) {
+    let idx = indices.offsets();
     let pbar = Mutex::new(ProgressBar::new(idx.len() as u64));
     let out_file = File::create(output_path).unwrap();
     let ftw = FileTemplateWriter::new(out_file);
 
     idx.into_par_iter().for_each(|index| {
         let dx = open_seek_bzip(&data, index).unwrap();
-        extract_templates(dx, &ftw);
+        extract_templates(dx, &ftw, filter);
         {
             let mut prog = pbar.lock().unwrap();
             prog.inc();
@@ -53,7 +64,8 @@ pub fn compile_templates(indices: &WikiDumpIndices, data: &Path, output_path: &P
 mod test {
     use super::*;
     use std::cell::RefCell;
-    use std::io::Cursor;
+    use std::io::{self, Cursor};
+    use storage::page::{Namespaces, PageRules, RulesFilter};
 
     #[derive(Clone, Debug, Default)]
     struct TestTemplateWriter {
@@ -61,8 +73,9 @@ mod test {
     }
 
     impl TemplateWriter for TestTemplateWriter {
-        fn write_template_impl(&self, template: Template) {
+        fn write_template_impl(&self, template: Template) -> io::Result<()> {
             self.templates.borrow_mut().push(template);
+            Ok(())
         }
     }
 
@@ -88,7 +101,9 @@ mod test {
         "#;
         let reader = Cursor::new(test_xml);
         let tw = TestTemplateWriter::default();
-        extract_templates(reader, &tw);
+        let filter: Arc<PageFilter> =
+            Arc::new(RulesFilter::new(&PageRules::templates(), Namespaces::english()).unwrap());
+        extract_templates(reader, &tw, &filter);
         let templates = tw.templates.into_inner();
         assert_eq!(templates.len(), 2);
         let template = &templates[0];
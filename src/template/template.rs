@@ -3,8 +3,8 @@ use std::fmt;
 /// Wikipedia Template data.
 #[derive(Clone, Debug, Default)]
 pub struct Template {
-    title: String,
-    page: String,
+    pub title: String,
+    pub page: String,
 }
 
 impl Template {
@@ -18,12 +18,22 @@ impl Template {
     }
 }
 
+/// Escape text for inclusion in an XML element.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
 impl fmt::Display for Template {
+    /// Write the template as a page element, escaping its title and text so
+    /// that tags such as `<noinclude>` are read back as text.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "<page>\n   <title>{}</title>\n   <ns>10</ns>\n   <text>{}\n   </text>\n</page>",
-            self.title, self.page
+            "<page>\n   <title>{}</title>\n   <ns>10</ns>\n   <text>{}</text>\n</page>",
+            escape(&self.title),
+            escape(&self.page)
         )
     }
 }
@@ -7,7 +7,7 @@ use std::time::Instant;
 use bzip2::{read::BzDecoder, Decompress, Status};
 use log::{info, trace};
 
-use crate::indices::WikiDumpIndices;
+use crate::indices::Indices;
 
 type BZipReader = BufReader<BzDecoder<BufReader<File>>>;
 
@@ -165,15 +165,13 @@ impl BZipMultiStream<BufReader<File>> {
 
 /// Extract one file to disk.
 pub fn extract_one(
-    indices: &WikiDumpIndices,
+    indices: &Indices,
     index: usize,
     data: &Path,
     out: &str,
 ) -> Result<(), io::Error> {
-    let mut indices = indices.keys().collect::<Vec<_>>();
-    indices.sort();
-    let index = indices[index];
-    let reader = open_seek_bzip(data, *index).unwrap();
+    let index = indices.offsets()[index];
+    let reader = open_seek_bzip(data, index).unwrap();
     let out_file = File::create(out).unwrap();
     let mut out_buf = BufWriter::with_capacity(8192 * 2, out_file);
     reader.lines().map(|l| l.unwrap()).for_each(|line| {
@@ -1,4 +1,5 @@
-use fst::{self, IntoStreamer, Map, MapBuilder};
+use fst::{self, Automaton, IntoStreamer, Map, MapBuilder, Streamer};
+use fst_levenshtein::Levenshtein;
 use fst_regex::Regex;
 use log::info;
 use qp_trie::{wrapper::BString, Trie};
@@ -7,10 +8,11 @@ use std::io::BufWriter;
 use std::path::Path;
 use std::time::Instant;
 
+use crate::link_probability::{LinkCounts, LinkProbabilities};
 use crate::surface_form::SurfaceForm;
 
 /// Build and serialise a FST from a Trie of flat anchors.
-fn build_fst_from_anchors(
+pub fn build_fst_from_anchors(
     anchor_counts: Trie<BString, u32>,
     output_path: &Path,
 ) -> Result<(), Box<std::error::Error>> {
@@ -45,21 +47,255 @@ fn build_fst_from_anchors(
     Ok(())
 }
 
+/// Build and serialise a FST of title-derived surface forms.
+///
+/// Keys are tab-separated surface form and entity pairs, and values are
+/// `Source` flags.
+pub fn build_fst_from_titles<I>(pairs: I, output_path: &Path) -> Result<(), Box<std::error::Error>>
+where
+    I: IntoIterator<Item = (String, u64)>,
+{
+    let mut pairs = pairs.into_iter().collect::<Vec<_>>();
+    pairs.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+
+    let file = File::create(output_path)?;
+    let buf = BufWriter::with_capacity(64 * 1024 * 1024, file);
+    let mut bld = MapBuilder::new(buf)?;
+
+    info!("Building titles FST...");
+    let now = Instant::now();
+
+    bld.extend_iter(pairs.into_iter())?;
+    bld.finish()?;
+
+    info!("Done in {} seconds", now.elapsed().as_secs());
+    Ok(())
+}
+
+/// Fetch all `surface\tentity` pairs for a surface form.
+fn pairs_for_query(map: &Map, query: &str) -> fst::Result<Vec<(String, u64)>> {
+    // Keys are stored as `surface\tentity`, so every entity for a surface form
+    // falls within [`surface\t`, `surface\n`).
+    map.range()
+        .ge(format!("{}\t", query))
+        .lt(format!("{}\n", query))
+        .into_stream()
+        .into_str_vec()
+}
+
+/// Fetch the smallest byte string greater than every string starting with
+/// `prefix`, if any.
+fn prefix_upper_bound(prefix: &str) -> Option<Vec<u8>> {
+    let mut upper = prefix.as_bytes().to_vec();
+    while let Some(last) = upper.pop() {
+        if last < 0xFF {
+            upper.push(last + 1);
+            return Some(upper);
+        }
+    }
+    None
+}
+
+/// Sum counts per surface form over a stream of `surface\tentity` keys,
+/// stopping after `limit` surface forms.
+fn group_surface_forms<S>(mut stream: S, limit: usize) -> Vec<(String, u64)>
+where
+    S: for<'a> Streamer<'a, Item = (&'a [u8], u64)>,
+{
+    let mut groups: Vec<(String, u64)> = vec![];
+    while let Some((key, count)) = stream.next() {
+        let key = String::from_utf8_lossy(key);
+        let surface = key.split('\t').next().unwrap_or("");
+        let same = groups
+            .last()
+            .map(|(last, _)| last == surface)
+            .unwrap_or(false);
+        if same {
+            groups.last_mut().unwrap().1 += count;
+        } else {
+            if groups.len() == limit {
+                break;
+            }
+            groups.push((surface.to_owned(), count));
+        }
+    }
+    groups
+}
+
+/// Automaton matching `surface\tentity` keys whose whole surface form is
+/// matched by another automaton, whatever the entity.
+struct SurfaceFormKey<A>(A);
+
+/// State of a `SurfaceFormKey` automaton.
+enum SurfaceFormKeyState<S> {
+    /// Within the surface form.
+    Surface(S),
+    /// Past the separator of a matching surface form.
+    Entity,
+    /// Past the separator of a surface form which does not match.
+    Rejected,
+}
+
+impl<A: Automaton> Automaton for SurfaceFormKey<A> {
+    type State = SurfaceFormKeyState<A::State>;
+
+    fn start(&self) -> Self::State {
+        SurfaceFormKeyState::Surface(self.0.start())
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        match state {
+            SurfaceFormKeyState::Entity => true,
+            _ => false,
+        }
+    }
+
+    fn can_match(&self, state: &Self::State) -> bool {
+        match state {
+            SurfaceFormKeyState::Surface(inner) => self.0.can_match(inner),
+            SurfaceFormKeyState::Entity => true,
+            SurfaceFormKeyState::Rejected => false,
+        }
+    }
+
+    fn will_always_match(&self, state: &Self::State) -> bool {
+        self.is_match(state)
+    }
+
+    fn accept(&self, state: &Self::State, byte: u8) -> Self::State {
+        match state {
+            SurfaceFormKeyState::Surface(inner) if byte == b'\t' => {
+                if self.0.is_match(inner) {
+                    SurfaceFormKeyState::Entity
+                } else {
+                    SurfaceFormKeyState::Rejected
+                }
+            }
+            SurfaceFormKeyState::Surface(inner) => {
+                SurfaceFormKeyState::Surface(self.0.accept(inner, byte))
+            }
+            SurfaceFormKeyState::Entity => SurfaceFormKeyState::Entity,
+            SurfaceFormKeyState::Rejected => SurfaceFormKeyState::Rejected,
+        }
+    }
+}
+
 pub struct WikiAnchors {
     anchors: Map,
+    titles: Option<Map>,
+    link_probabilities: Option<LinkProbabilities>,
 }
 
 impl WikiAnchors {
-    pub fn new(path: &str) -> fst::Result<Self> {
+    pub fn new<P: AsRef<Path>>(path: P) -> fst::Result<Self> {
         let anchors = unsafe { Map::from_path(path) }?;
-        Ok(WikiAnchors { anchors })
+        Ok(WikiAnchors {
+            anchors,
+            titles: None,
+            link_probabilities: None,
+        })
+    }
+
+    /// Add title, title-nv and redirect surface forms from a FST built by
+    /// `build_fst_from_titles`.
+    pub fn with_titles<P: AsRef<Path>>(mut self, path: P) -> fst::Result<Self> {
+        self.titles = Some(unsafe { Map::from_path(path) }?);
+        Ok(self)
+    }
+
+    /// Add precomputed link counts from a FST built by
+    /// `build_fst_from_link_counts`.
+    pub fn with_link_probabilities<P: AsRef<Path>>(mut self, path: P) -> fst::Result<Self> {
+        self.link_probabilities = Some(LinkProbabilities::new(path)?);
+        Ok(self)
+    }
+
+    /// Fetch precomputed link counts for a surface form, if loaded.
+    pub fn link_counts(&self, surface: &str) -> Option<LinkCounts> {
+        self.link_probabilities
+            .as_ref()
+            .and_then(|table| table.get(surface))
     }
 
     /// Fetch a map of entity, count for the surface form, if any
     pub fn entities_for_query(&self, query: &str) -> Result<SurfaceForm, Box<std::error::Error>> {
-        let re = Regex::new(&format!("{}\t.*", query))?;
-        // TODO: semantics of returning 'no match'?
-        let stream = self.anchors.search(&re).into_stream().into_str_vec()?;
-        Ok(SurfaceForm::from_paired_matches(query, stream))
+        let stream = pairs_for_query(&self.anchors, query)?;
+        let mut surface_form = SurfaceForm::from_paired_matches(query, stream);
+        if let Some(ref titles) = self.titles {
+            surface_form.add_flagged_matches(pairs_for_query(titles, query)?);
+        }
+        Ok(surface_form)
+    }
+
+    /// Fetch entity counts for all surface forms matching a regular expression.
+    pub fn search(&self, pattern: &str) -> Result<Vec<(String, u64)>, Box<std::error::Error>> {
+        let re = Regex::new(pattern)?;
+        Ok(self.anchors.search(&re).into_stream().into_str_vec()?)
+    }
+
+    /// Fetch total anchor counts for up to `limit` surface forms beginning with
+    /// a prefix.
+    pub fn surface_forms_with_prefix(&self, prefix: &str, limit: usize) -> Vec<(String, u64)> {
+        let range = self.anchors.range().ge(prefix);
+        match prefix_upper_bound(prefix) {
+            Some(upper) => group_surface_forms(range.lt(upper).into_stream(), limit),
+            None => group_surface_forms(range.into_stream(), limit),
+        }
+    }
+
+    /// Fetch total anchor counts for up to `limit` surface forms matching a
+    /// regular expression.
+    pub fn surface_forms_matching(
+        &self,
+        pattern: &str,
+        limit: usize,
+    ) -> Result<Vec<(String, u64)>, Box<std::error::Error>> {
+        let re = Regex::new(&format!("({})\t.*", pattern))?;
+        Ok(group_surface_forms(
+            self.anchors.search(&re).into_stream(),
+            limit,
+        ))
+    }
+
+    /// Fetch total anchor counts for up to `limit` surface forms within a
+    /// Levenshtein distance of the query. The whole surface form is compared,
+    /// so longer forms merely beginning with a near match are not returned.
+    pub fn surface_forms_within(
+        &self,
+        query: &str,
+        distance: u32,
+        limit: usize,
+    ) -> Result<Vec<(String, u64)>, Box<std::error::Error>> {
+        let lev = SurfaceFormKey(Levenshtein::new(query, distance)?);
+        Ok(group_surface_forms(
+            self.anchors.search(lev).into_stream(),
+            limit,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_surface_form_key() {
+        let map = Map::from_iter(vec![
+            ("europa\tEuropa (moon)", 2),
+            ("europa\tEuropa (mythology)", 1),
+            ("europe\tEurope", 3),
+            ("european union\tEuropean Union", 5),
+            ("eurpe\tEurope", 1),
+        ])
+        .unwrap();
+        let lev = SurfaceFormKey(Levenshtein::new("europe", 1).unwrap());
+        assert_eq!(
+            group_surface_forms(map.search(lev).into_stream(), 10),
+            vec![
+                ("europa".to_owned(), 3),
+                ("europe".to_owned(), 3),
+                ("eurpe".to_owned(), 1),
+            ]
+        );
     }
 }
@@ -1,7 +1,9 @@
 #![allow(dead_code)]
 pub mod fst;
+pub mod link_probability;
 pub mod page;
 pub mod qpt;
+pub mod relatedness;
 pub mod surface_form;
 pub mod tantivy;
 mod utils;
@@ -0,0 +1,166 @@
+use fst::{Map, MapBuilder};
+use log::info;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::tantivy::TantivyWikiIndex;
+
+/// Number of surface forms whose pages are counted at once.
+static BATCH_LEN: usize = 64 * 1024;
+
+/// Link and document counts for a surface form.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinkCounts {
+    /// Number of pages in which the surface form is used as anchor text.
+    pub links: u64,
+    /// Number of pages containing the surface form, linked or not.
+    pub docs: u64,
+}
+
+impl LinkCounts {
+    /// Pack both counts into a FST value, saturating at `u32::MAX`.
+    pub fn pack(self) -> u64 {
+        let max = u64::from(u32::max_value());
+        (self.links.min(max) << 32) | self.docs.min(max)
+    }
+
+    pub fn unpack(value: u64) -> Self {
+        LinkCounts {
+            links: value >> 32,
+            docs: value & u64::from(u32::max_value()),
+        }
+    }
+
+    /// Probability that a page containing the surface form links it.
+    ///
+    /// Every page linking the surface form should contain it, but the phrase
+    /// query may tokenise it differently, so the ratio is capped at one.
+    pub fn link_probability(self) -> f32 {
+        if self.links == 0 {
+            return 0.0;
+        }
+        (self.links as f32 / self.docs.max(1) as f32).min(1.0)
+    }
+}
+
+/// Count the pages containing each surface form in a batch.
+///
+/// Surface forms which cannot be expressed as a phrase query are given a
+/// document frequency of zero.
+fn count_links(batch: &[(Vec<u8>, u64)], index: &TantivyWikiIndex) -> Vec<LinkCounts> {
+    batch
+        .par_iter()
+        .map(|(surface, links)| {
+            let docs = index
+                .count_phrase(&String::from_utf8_lossy(surface))
+                .unwrap_or(0) as u64;
+            LinkCounts {
+                links: *links,
+                docs,
+            }
+        })
+        .collect()
+}
+
+/// Build and serialise a FST mapping surface forms to packed `LinkCounts`.
+///
+/// `linked` yields surface forms in sorted order along with the number of
+/// pages linking them, as merged from sorted runs. Pages containing each
+/// surface form are counted in the index a batch at a time, so that only one
+/// batch is held in memory.
+pub fn build_fst_from_link_counts<I>(
+    linked: I,
+    index: &TantivyWikiIndex,
+    output_path: &Path,
+) -> Result<(), Box<std::error::Error>>
+where
+    I: IntoIterator<Item = io::Result<(Vec<u8>, u64)>>,
+{
+    let file = File::create(output_path)?;
+    let buf = BufWriter::with_capacity(64 * 1024 * 1024, file);
+    let mut bld = MapBuilder::new(buf)?;
+
+    info!("Building link probability FST...");
+    let now = Instant::now();
+
+    let mut linked = linked.into_iter().peekable();
+    let mut total = 0;
+    while linked.peek().is_some() {
+        let batch = linked
+            .by_ref()
+            .take(BATCH_LEN)
+            .collect::<io::Result<Vec<_>>>()?;
+        let counts = count_links(&batch, index);
+        for ((surface, _), counts) in batch.iter().zip(counts) {
+            bld.insert(surface, counts.pack())?;
+        }
+        total += batch.len();
+        info!("Counted {} surface forms", total);
+    }
+    bld.finish()?;
+
+    info!("Done in {} seconds", now.elapsed().as_secs());
+    Ok(())
+}
+
+/// Precomputed link counts for surface forms.
+pub struct LinkProbabilities {
+    counts: Map,
+}
+
+impl LinkProbabilities {
+    pub fn new<P: AsRef<Path>>(path: P) -> fst::Result<Self> {
+        let counts = unsafe { Map::from_path(path) }?;
+        Ok(LinkProbabilities { counts })
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Fetch link counts for a surface form, if it is used as anchor text.
+    pub fn get(&self, surface: &str) -> Option<LinkCounts> {
+        self.counts.get(surface).map(LinkCounts::unpack)
+    }
+
+    /// Fetch the link probability of a surface form, or zero if it is never
+    /// used as anchor text.
+    pub fn link_probability(&self, surface: &str) -> f32 {
+        self.get(surface)
+            .map(LinkCounts::link_probability)
+            .unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_link_counts() {
+        let counts = LinkCounts {
+            links: 30,
+            docs: 120,
+        };
+        assert_eq!(LinkCounts::unpack(counts.pack()), counts);
+        assert_eq!(counts.link_probability(), 0.25);
+
+        let saturated = LinkCounts {
+            links: u64::max_value(),
+            docs: 1,
+        };
+        assert_eq!(
+            LinkCounts::unpack(saturated.pack()).links,
+            u64::from(u32::max_value())
+        );
+        assert_eq!(saturated.link_probability(), 1.0);
+        assert_eq!(LinkCounts::default().link_probability(), 0.0);
+    }
+}
@@ -1,12 +1,5 @@
-use lazy_static::lazy_static;
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-lazy_static! {
-    /// Check if a wikipedia anchor links to an external resource.
-    static ref EXT_LINK : Regex = Regex::new("^[A-Za-z]+:").unwrap();
-}
-
 /// Wikipedia anchor, representing a link between pages, optionally with a
 /// surface realisation.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -46,21 +39,6 @@ impl Anchor {
         }
     }
 
-    /// Extract the text of an anchor, given a start index within a string
-    /// slice.
-    pub fn pare_anchor_match(page: &str, begin: usize) -> Option<&str> {
-        let initial = &page[begin + 2..];
-        if initial.starts_with("#") {
-            return None;
-        }
-        if EXT_LINK.is_match(initial) {
-            return None;
-        }
-        page[begin..]
-            .find("]]")
-            .and_then(|end| Some(&page[begin + 2..begin + end]))
-    }
-
     /// Check if an anchor string points to a file.
     pub fn is_file(anchor: &str) -> bool {
         anchor.starts_with("File:") || anchor.starts_with("Image:")
@@ -1,12 +1,119 @@
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-/// Wikipedia category label.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Category(pub String);
+use crate::page::links::{LinkKind, LinkParser};
+
+lazy_static! {
+    /// Magic words setting the sort key of a page's categories.
+    static ref DEFAULTSORT: Regex =
+        Regex::new(r"(?i)\{\{\s*DEFAULT(?:SORT|SORTKEY|CATEGORYSORT)\s*:([^{}|]*)").unwrap();
+}
+
+/// Magic word hiding a category from the pages in it.
+pub const HIDDENCAT: &str = "__HIDDENCAT__";
+
+/// Membership of a page in a category, e.g. `[[Category:Greek gods|Orpheus]]`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Category {
+    /// Name of the category, without its namespace prefix.
+    pub name: String,
+    /// Key under which the page is sorted in the category, from the link or
+    /// else `{{DEFAULTSORT:...}}`. Pages without one sort by their title.
+    #[serde(default)]
+    pub sort_key: Option<String>,
+    /// Whether the page is added to the category by a template rather than
+    /// by its own text.
+    #[serde(default)]
+    pub from_template: bool,
+}
 
 impl Category {
     /// Get the fully qualified name of a category.
     pub fn fqn(&self) -> String {
-        format!("Category:{}", self.0)
+        format!("Category:{}", self.name)
+    }
+
+    /// Find the categories of a page in its text, in order of first appearance.
+    /// As in MediaWiki, a category given twice keeps its last sort key.
+    pub fn parse(text: &str, links: &LinkParser) -> Vec<Category> {
+        let default_key = default_sort_key(text);
+        let mut categories: Vec<Category> = vec![];
+        for link in links.parse(text) {
+            if link.kind != LinkKind::Category {
+                continue;
+            }
+            let name = match link.target.find(':') {
+                Some(index) => &link.target[index + 1..],
+                None => &link.target,
+            };
+            // Category links take no trail, so the brackets end the link.
+            let inner = &text[link.start + 2..link.end - 2];
+            // The key is the raw text after the pipe, so that a key of
+            // whitespace, e.g. `[[Category:Physics| ]]`, is kept.
+            let sort_key = match inner.find('|') {
+                Some(index) if index + 1 < inner.len() => Some(&inner[index + 1..]),
+                _ => default_key,
+            };
+            let sort_key = sort_key.map(|key| key.to_owned());
+            match categories.iter_mut().find(|category| category.name == name) {
+                Some(category) => category.sort_key = sort_key,
+                None => categories.push(Category {
+                    name: name.to_owned(),
+                    sort_key,
+                    from_template: false,
+                }),
+            }
+        }
+        categories
+    }
+}
+
+/// Sort key set by the last `{{DEFAULTSORT:...}}` in the text of a page.
+pub fn default_sort_key(text: &str) -> Option<&str> {
+    DEFAULTSORT
+        .captures_iter(text)
+        .last()
+        .and_then(|captures| captures.get(1))
+        .map(|key| key.as_str().trim())
+        .filter(|key| !key.is_empty())
+}
+
+/// Check if the text of a category page hides it with `__HIDDENCAT__`.
+pub fn is_hidden(text: &str) -> bool {
+    text.contains(HIDDENCAT)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_categories() {
+        let text = "'''Orpheus''' was a musician.{{DEFAULTSORT:Orpheus, legendary}}\n\
+                    [[Category:Greek gods]] [[category:thracians|Orpheus]] \
+                    [[:Category:Lyres]] [[Category:Greek gods|Orfeo]] [[Category:Musicians|]] \
+                    [[Category:Mythology| ]]";
+        let categories = Category::parse(text, &LinkParser::default());
+        let categories = categories
+            .iter()
+            .map(|category| {
+                (
+                    category.name.as_str(),
+                    category.sort_key.as_ref().unwrap().as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            categories,
+            vec![
+                ("Greek gods", "Orfeo"),
+                ("Thracians", "Orpheus"),
+                ("Musicians", "Orpheus, legendary"),
+                ("Mythology", " "),
+            ]
+        );
+        assert_eq!(default_sort_key("[[Category:Lyres]]"), None);
+        assert!(is_hidden("{{Tracking category}}__HIDDENCAT__"));
     }
 }
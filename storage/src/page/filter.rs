@@ -0,0 +1,366 @@
+use regex::{self, Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::page::namespace::{
+    NamespaceFilter, Namespaces, CATEGORY, DEFAULT_EXCLUDED, MAIN, TEMPLATE,
+};
+
+/// Metadata of a page, as read from the dump before its text.
+#[derive(Clone, Copy, Debug)]
+pub struct PageMeta<'a> {
+    pub title: &'a str,
+    /// Namespace ID from the page's `<ns>` element, if present.
+    pub ns: Option<i32>,
+    /// Whether the page is a redirect.
+    pub redirect: bool,
+    /// Timestamp of the page's revision, if read yet.
+    pub timestamp: Option<&'a str>,
+}
+
+impl<'a> PageMeta<'a> {
+    /// Metadata for a link target, for which only the title is known.
+    pub fn link(title: &'a str) -> Self {
+        PageMeta {
+            title,
+            ns: None,
+            redirect: false,
+            timestamp: None,
+        }
+    }
+}
+
+/// Decides which pages are yielded by the page iterators.
+pub trait PageFilter: Send + Sync {
+    /// Determine the namespace of a page.
+    ///
+    /// By default this is taken from `<ns>` alone; filters which know the
+    /// namespaces of the dump may fall back to the title prefix.
+    fn namespace(&self, page: &PageMeta) -> i32 {
+        page.ns.unwrap_or(MAIN)
+    }
+
+    /// Check if a page should be kept, given its metadata. Called before the
+    /// text of the page is read, and again for redirects.
+    fn accepts(&self, page: &PageMeta) -> bool;
+
+    /// Check if a page accepted by `accepts` should be kept, given its text.
+    fn accepts_text(&self, _page: &PageMeta, _text: &str) -> bool {
+        true
+    }
+
+    /// Check if links to a page should be kept when counting anchors.
+    fn accepts_link(&self, title: &str) -> bool {
+        self.accepts(&PageMeta::link(title))
+    }
+}
+
+impl PageFilter for NamespaceFilter {
+    fn namespace(&self, page: &PageMeta) -> i32 {
+        self.namespace_of(page.title, page.ns)
+    }
+
+    /// Skip redirects and pages in excluded namespaces.
+    fn accepts(&self, page: &PageMeta) -> bool {
+        !page.redirect && !self.is_excluded(page.title, page.ns)
+    }
+}
+
+/// Configuration for `RulesFilter`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PageRules {
+    /// Namespaces to keep; if empty, all namespaces not excluded are kept.
+    #[serde(default)]
+    pub include_namespaces: Vec<i32>,
+    /// Namespaces to skip.
+    #[serde(default = "PageRules::default_exclude_namespaces")]
+    pub exclude_namespaces: Vec<i32>,
+    /// Title patterns to keep; if empty, all titles not excluded are kept.
+    #[serde(default)]
+    pub include_titles: Vec<String>,
+    /// Title patterns to skip.
+    #[serde(default)]
+    pub exclude_titles: Vec<String>,
+    /// Keep redirect pages.
+    #[serde(default)]
+    pub redirects: bool,
+    /// Skip pages last revised before this ISO 8601 timestamp, e.g.
+    /// `2019-01-01T00:00:00Z`.
+    #[serde(default)]
+    pub min_timestamp: Option<String>,
+    /// Minimum length of page text, in characters.
+    #[serde(default)]
+    pub min_text_len: usize,
+    /// Skip pages transcluding any of `disambiguation_templates`.
+    #[serde(default)]
+    pub exclude_disambiguation: bool,
+    /// Names of templates marking disambiguation pages.
+    #[serde(default = "PageRules::default_disambiguation_templates")]
+    pub disambiguation_templates: Vec<String>,
+}
+
+impl PageRules {
+    /// User talk, project, file, file talk and template namespaces.
+    pub fn default_exclude_namespaces() -> Vec<i32> {
+        DEFAULT_EXCLUDED.to_vec()
+    }
+
+    pub fn default_disambiguation_templates() -> Vec<String> {
+        [
+            "disambiguation",
+            "disambig",
+            "dab",
+            "disamb",
+            "hndis",
+            "geodis",
+        ]
+        .iter()
+        .map(|name| name.to_string())
+        .collect()
+    }
+
+    /// Rules selecting template pages, including template redirects.
+    pub fn templates() -> Self {
+        PageRules {
+            include_namespaces: vec![TEMPLATE],
+            exclude_namespaces: vec![],
+            redirects: true,
+            ..Default::default()
+        }
+    }
+
+    /// Rules selecting category pages.
+    pub fn categories() -> Self {
+        PageRules {
+            include_namespaces: vec![CATEGORY],
+            exclude_namespaces: vec![],
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for PageRules {
+    fn default() -> Self {
+        PageRules {
+            include_namespaces: vec![],
+            exclude_namespaces: PageRules::default_exclude_namespaces(),
+            include_titles: vec![],
+            exclude_titles: vec![],
+            redirects: false,
+            min_timestamp: None,
+            min_text_len: 0,
+            exclude_disambiguation: false,
+            disambiguation_templates: PageRules::default_disambiguation_templates(),
+        }
+    }
+}
+
+/// Compile title patterns, returning `None` if there are none.
+fn regex_set(patterns: &[String]) -> Result<Option<RegexSet>, regex::Error> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    RegexSet::new(patterns).map(Some)
+}
+
+/// Page filter applying a set of `PageRules`.
+#[derive(Clone, Debug)]
+pub struct RulesFilter {
+    namespaces: Namespaces,
+    include_namespaces: Option<HashSet<i32>>,
+    exclude_namespaces: HashSet<i32>,
+    include_titles: Option<RegexSet>,
+    exclude_titles: Option<RegexSet>,
+    redirects: bool,
+    min_timestamp: Option<String>,
+    min_text_len: usize,
+    disambiguation: Option<Regex>,
+}
+
+impl RulesFilter {
+    /// Create a filter, compiling the title patterns of the rules.
+    ///
+    /// # Arguments
+    /// * `rules` - Rules to apply.
+    /// * `namespaces` - Namespaces of the dump, for pages without `<ns>`.
+    pub fn new(rules: &PageRules, namespaces: Namespaces) -> Result<Self, regex::Error> {
+        let disambiguation =
+            if rules.exclude_disambiguation && !rules.disambiguation_templates.is_empty() {
+                let names = rules
+                    .disambiguation_templates
+                    .iter()
+                    .map(|name| regex::escape(name))
+                    .collect::<Vec<_>>();
+                Some(Regex::new(&format!(
+                    r"(?i)\{{\{{\s*(?:{})\s*(?:\||\}}\}})",
+                    names.join("|")
+                ))?)
+            } else {
+                None
+            };
+        Ok(RulesFilter {
+            namespaces,
+            include_namespaces: if rules.include_namespaces.is_empty() {
+                None
+            } else {
+                Some(rules.include_namespaces.iter().cloned().collect())
+            },
+            exclude_namespaces: rules.exclude_namespaces.iter().cloned().collect(),
+            include_titles: regex_set(&rules.include_titles)?,
+            exclude_titles: regex_set(&rules.exclude_titles)?,
+            redirects: rules.redirects,
+            min_timestamp: rules.min_timestamp.clone(),
+            min_text_len: rules.min_text_len,
+            disambiguation,
+        })
+    }
+
+    fn accepts_namespace(&self, ns: i32) -> bool {
+        !self.exclude_namespaces.contains(&ns)
+            && match self.include_namespaces {
+                Some(ref include) => include.contains(&ns),
+                None => true,
+            }
+    }
+}
+
+impl PageFilter for RulesFilter {
+    fn namespace(&self, page: &PageMeta) -> i32 {
+        page.ns
+            .unwrap_or_else(|| self.namespaces.key_for_title(page.title))
+    }
+
+    fn accepts(&self, page: &PageMeta) -> bool {
+        if page.redirect && !self.redirects {
+            return false;
+        }
+        if !self.accepts_namespace(self.namespace(page)) {
+            return false;
+        }
+        // ISO 8601 timestamps in UTC order lexicographically.
+        if let (Some(ref min), Some(timestamp)) = (&self.min_timestamp, page.timestamp) {
+            if timestamp < min.as_str() {
+                return false;
+            }
+        }
+        if let Some(ref exclude) = self.exclude_titles {
+            if exclude.is_match(page.title) {
+                return false;
+            }
+        }
+        match self.include_titles {
+            Some(ref include) => include.is_match(page.title),
+            None => true,
+        }
+    }
+
+    fn accepts_text(&self, _page: &PageMeta, text: &str) -> bool {
+        if self.min_text_len > 0 && text.chars().count() < self.min_text_len {
+            return false;
+        }
+        match self.disambiguation {
+            Some(ref disambiguation) => !disambiguation.is_match(text),
+            None => true,
+        }
+    }
+
+    /// Links are filtered by namespace only, as title rules select the pages
+    /// to read rather than the pages which may be linked to.
+    fn accepts_link(&self, title: &str) -> bool {
+        self.accepts_namespace(self.namespaces.key_for_title(title))
+    }
+}
+
+/// Page filter accepting the pages accepted by any of several filters, e.g.
+/// articles along with category pages.
+pub struct AnyFilter(pub Vec<Arc<PageFilter>>);
+
+impl PageFilter for AnyFilter {
+    fn namespace(&self, page: &PageMeta) -> i32 {
+        match self.0.first() {
+            Some(filter) => filter.namespace(page),
+            None => page.ns.unwrap_or(MAIN),
+        }
+    }
+
+    fn accepts(&self, page: &PageMeta) -> bool {
+        self.0.iter().any(|filter| filter.accepts(page))
+    }
+
+    fn accepts_text(&self, page: &PageMeta, text: &str) -> bool {
+        self.0
+            .iter()
+            .any(|filter| filter.accepts(page) && filter.accepts_text(page, text))
+    }
+
+    fn accepts_link(&self, title: &str) -> bool {
+        self.0.iter().any(|filter| filter.accepts_link(title))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::page::PageIterator;
+    use std::io::BufReader;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_rules_filter() {
+        let xml = r#"
+<page><title>Orpheus</title><ns>0</ns><id>1</id><text>Orpheus was a legendary musician.</text></page>
+<page><title>Template:Infobox</title><ns>10</ns><id>2</id><text>{{{name}}}</text></page>
+<page><title>Orfeo</title><ns>0</ns><id>3</id><redirect title="Orpheus" /><text>#REDIRECT [[Orpheus]]</text></page>
+<page><title>Mercury</title><ns>0</ns><id>4</id><text>'''Mercury''' may refer to: {{disambiguation}}</text></page>
+<page><title>Stub</title><ns>0</ns><id>5</id><text>Short.</text></page>
+<page><title>List of musicians</title><ns>0</ns><id>6</id><text>A long enough list of musicians.</text></page>
+"#;
+        let titles = |rules: &PageRules| {
+            let filter = RulesFilter::new(rules, Namespaces::english()).unwrap();
+            PageIterator::new(BufReader::new(xml.as_bytes()))
+                .with_filter(Arc::new(filter))
+                .map(|page| page.title)
+                .collect::<Vec<_>>()
+        };
+
+        let rules = PageRules::default();
+        assert_eq!(
+            titles(&rules),
+            vec!["Orpheus", "Mercury", "Stub", "List of musicians"]
+        );
+
+        let rules = PageRules {
+            exclude_titles: vec!["^List of ".to_owned()],
+            min_text_len: 10,
+            exclude_disambiguation: true,
+            ..Default::default()
+        };
+        assert_eq!(titles(&rules), vec!["Orpheus"]);
+
+        let rules = PageRules {
+            include_namespaces: vec![MAIN],
+            exclude_namespaces: vec![],
+            include_titles: vec!["^Or".to_owned()],
+            redirects: true,
+            ..Default::default()
+        };
+        assert_eq!(titles(&rules), vec!["Orpheus", "Orfeo"]);
+
+        assert_eq!(titles(&PageRules::templates()), vec!["Template:Infobox"]);
+
+        let filter = AnyFilter(vec![
+            Arc::new(RulesFilter::new(&PageRules::templates(), Namespaces::english()).unwrap()),
+            Arc::new(RulesFilter::new(&rules, Namespaces::english()).unwrap()),
+        ]);
+        let any = PageIterator::new(BufReader::new(xml.as_bytes()))
+            .with_filter(Arc::new(filter))
+            .map(|page| page.title)
+            .collect::<Vec<_>>();
+        assert_eq!(any, vec!["Orpheus", "Template:Infobox", "Orfeo"]);
+
+        let filter = RulesFilter::new(&rules, Namespaces::english()).unwrap();
+        assert!(filter.accepts_link("Mercury"));
+        assert!(!filter.accepts_link("Template:Infobox"));
+    }
+}
@@ -1,14 +1,34 @@
-use crate::page::Page;
+use crate::page::{
+    filter::{PageFilter, PageMeta},
+    links::LinkParser,
+    namespace::NamespaceFilter,
+    revision::Revision,
+    Page,
+};
 use quick_xml::{self as qx, events::Event};
 use std::io::{BufReader, Read};
+use std::sync::Arc;
 
 /// Iterator yielding Page objects for an XML file.
+///
+/// Pages are skipped according to a `PageFilter`; by default, redirects and
+/// pages in the namespaces excluded by `NamespaceFilter::default` are skipped.
 pub struct PageIterator<R: Read> {
     reader: qx::Reader<BufReader<R>>,
     buf: Vec<u8>,
     page_buf: Vec<u8>,
     title: String,
     id: String,
+    ns: Option<i32>,
+    redirect: bool,
+    revision: Revision,
+    in_revision: bool,
+    in_contributor: bool,
+    /// Text of the current page, if accepted by the filter.
+    text: Option<String>,
+    filter: Arc<PageFilter>,
+    /// Parser for the links of yielded pages; English by default.
+    links: Arc<LinkParser>,
 }
 
 impl<R: Read> PageIterator<R> {
@@ -20,60 +40,133 @@ impl<R: Read> PageIterator<R> {
             page_buf: vec![],
             title: String::new(),
             id: String::new(),
+            ns: None,
+            redirect: false,
+            revision: Revision::default(),
+            in_revision: false,
+            in_contributor: false,
+            text: None,
+            filter: Arc::new(NamespaceFilter::default()),
+            links: Arc::new(LinkParser::default()),
         }
     }
 
-    /// Capture and set the title of the current page.
-    fn extract_title(&mut self) {
-        if let Ok(title) = self.reader.read_text(b"title", &mut self.page_buf) {
-            self.title = title;
-        }
+    /// Skip pages using the given filter rather than the default.
+    pub fn with_filter(mut self, filter: Arc<PageFilter>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Parse the links of pages using the given parser rather than the default.
+    pub fn with_links(mut self, links: Arc<LinkParser>) -> Self {
+        self.links = links;
+        self
+    }
+
+    /// Reset per-page state at the start of a new page.
+    fn start_page(&mut self) {
+        self.title.clear();
+        self.id.clear();
+        self.ns = None;
+        self.redirect = false;
+        self.revision = Revision::default();
+        self.in_revision = false;
+        self.in_contributor = false;
+        self.text = None;
+    }
+
+    /// Read the text content of the current element.
+    fn read_text(&mut self, tag: &[u8]) -> Option<String> {
+        self.reader.read_text(tag, &mut self.page_buf).ok()
     }
 
-    /// Capture and set the id of the current page.
+    /// Read the text content of the current element as an integer.
+    fn read_u64(&mut self, tag: &[u8]) -> Option<u64> {
+        self.read_text(tag)?.trim().parse::<u64>().ok()
+    }
+
+    /// Capture an `<id>`, which belongs to the page, revision or contributor
+    /// depending on where it appears.
     fn extract_id(&mut self) {
-        if let Ok(id) = self.reader.read_text(b"id", &mut self.page_buf) {
+        if self.in_contributor {
+            self.revision.contributor.id = self.read_u64(b"id");
+        } else if self.in_revision {
+            self.revision.id = self.read_u64(b"id").unwrap_or(0);
+        } else if let Some(id) = self.read_text(b"id") {
             self.id = id;
         }
     }
 
-    // TODO: use regex here, with custom filters.
-    fn is_filtered_title(&self) -> bool {
-        // Skip over files.
-        self.title.starts_with("File:")
-        // Skip over templates.
-        || self.title.starts_with("Template:")
-        // Skip over Wikipedia internal pages.
-        || self.title.starts_with("Wikipedia:")
-        // Skip over User talk.
-        || self.title.starts_with("User talk:")
-        // Skip over File talk.
-        || self.title.starts_with("File talk:")
+    /// Metadata of the current page.
+    fn meta(&self) -> PageMeta<'_> {
+        PageMeta {
+            title: &self.title,
+            ns: self.ns,
+            redirect: self.redirect,
+            timestamp: if self.revision.timestamp.is_empty() {
+                None
+            } else {
+                Some(&self.revision.timestamp)
+            },
+        }
     }
-}
-
-impl<R: Read> Iterator for PageIterator<R> {
-    type Item = Page;
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Read the next page accepted by the filter, returning its text once the
+    /// end of the page is reached.
+    fn next_text(&mut self) -> Option<String> {
         enum Tag {
+            Page,
+            Revision,
+            Contributor,
             Id,
-            Text,
+            Ns,
             Title,
+            ParentId,
+            Timestamp,
+            Username,
+            Ip,
+            Comment,
+            Model,
+            Format,
+            Sha1,
+            Text,
             Redirect,
+            Minor,
+            EndPage,
+            EndRevision,
+            EndContributor,
             None,
         }
         loop {
             let action = {
                 match self.reader.read_event(&mut self.buf) {
                     Ok(Event::Start(ref tag)) => match tag.name() {
-                        b"text" => Tag::Text,
+                        b"page" => Tag::Page,
+                        b"revision" => Tag::Revision,
+                        b"contributor" => Tag::Contributor,
                         b"id" => Tag::Id,
+                        b"ns" => Tag::Ns,
                         b"title" => Tag::Title,
+                        b"parentid" => Tag::ParentId,
+                        b"timestamp" => Tag::Timestamp,
+                        b"username" => Tag::Username,
+                        b"ip" => Tag::Ip,
+                        b"comment" => Tag::Comment,
+                        b"model" => Tag::Model,
+                        b"format" => Tag::Format,
+                        b"sha1" => Tag::Sha1,
+                        b"text" => Tag::Text,
                         _ => Tag::None,
                     },
                     Ok(Event::Empty(ref tag)) => match tag.name() {
                         b"redirect" => Tag::Redirect,
+                        b"minor" => Tag::Minor,
+                        _ => Tag::None,
+                    },
+                    Ok(Event::End(ref tag)) => match tag.name() {
+                        b"page" => Tag::EndPage,
+                        b"revision" => Tag::EndRevision,
+                        b"contributor" => Tag::EndContributor,
                         _ => Tag::None,
                     },
                     Ok(Event::Eof) => break,
@@ -81,34 +174,74 @@ impl<R: Read> Iterator for PageIterator<R> {
                     Err(_) => break,
                 }
             };
+            self.buf.clear();
             match action {
+                Tag::Page => self.start_page(),
+                Tag::Revision => self.in_revision = true,
+                Tag::Contributor => self.in_contributor = true,
+                Tag::EndRevision => self.in_revision = false,
+                Tag::EndContributor => self.in_contributor = false,
                 Tag::Id => self.extract_id(),
-                Tag::Title => self.extract_title(),
+                Tag::Ns => self.ns = self.read_text(b"ns").and_then(|ns| ns.trim().parse().ok()),
+                Tag::Title => self.title = self.read_text(b"title").unwrap_or_default(),
+                Tag::ParentId => self.revision.parent_id = self.read_u64(b"parentid"),
+                Tag::Timestamp => {
+                    self.revision.timestamp = self.read_text(b"timestamp").unwrap_or_default()
+                }
+                Tag::Username => self.revision.contributor.username = self.read_text(b"username"),
+                Tag::Ip => self.revision.contributor.ip = self.read_text(b"ip"),
+                Tag::Comment => self.revision.comment = self.read_text(b"comment"),
+                Tag::Model => self.revision.model = self.read_text(b"model"),
+                Tag::Format => self.revision.format = self.read_text(b"format"),
+                Tag::Sha1 => self.revision.sha1 = self.read_text(b"sha1"),
+                Tag::Minor => self.revision.minor = true,
                 Tag::Redirect => {
-                    // Skip over redirects; these are handled separately.
-                    self.reader
-                        .read_to_end(b"page", &mut self.page_buf)
-                        .unwrap();
+                    self.redirect = true;
+                    if !self.filter.accepts(&self.meta()) {
+                        self.reader
+                            .read_to_end(b"page", &mut self.page_buf)
+                            .unwrap();
+                    }
                 }
                 Tag::Text => {
-                    // Don't skip Portal pages for now.
-                    if self.is_filtered_title() {
+                    if !self.filter.accepts(&self.meta()) {
                         continue;
                     }
                     match self.reader.read_text(b"text", &mut self.page_buf) {
-                        Ok(page) => {
-                            return Some(Page::new(self.title.clone(), self.id.clone(), &page));
+                        Ok(text) => {
+                            if self.filter.accepts_text(&self.meta(), &text) {
+                                self.text = Some(text);
+                            }
                         }
                         Err(_) => return None,
                     }
                 }
-                _ => (),
+                Tag::EndPage => {
+                    // The text precedes the revision's sha1, so pages are
+                    // only complete at their end.
+                    if let Some(text) = self.text.take() {
+                        return Some(text);
+                    }
+                }
+                Tag::None => (),
             }
         }
         None
     }
 }
 
+impl<R: Read> Iterator for PageIterator<R> {
+    type Item = Page;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let text = self.next_text()?;
+        let mut page = Page::with_links(self.title.clone(), self.id.clone(), &text, &self.links);
+        page.ns = self.filter.namespace(&self.meta());
+        page.revision = self.revision.clone();
+        Some(page)
+    }
+}
+
 /// Iterator yielding String for each page in an XML file.
 pub struct RawPageIterator<R: Read>(pub PageIterator<R>);
 
@@ -116,37 +249,7 @@ impl<R: Read> Iterator for RawPageIterator<R> {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.0.reader.read_event(&mut self.0.buf) {
-                Ok(Event::Start(ref tag)) => match tag.name() {
-                    b"text" => {
-                        if self.0.is_filtered_title() {
-                            continue;
-                        }
-                        match self.0.reader.read_text(b"text", &mut self.0.page_buf) {
-                            Ok(page) => {
-                                return Some(page);
-                            }
-                            Err(_) => return None,
-                        }
-                    }
-                    _ => (),
-                },
-                Ok(Event::Empty(ref tag)) => match tag.name() {
-                    b"redirect" => {
-                        self.0
-                            .reader
-                            .read_to_end(b"page", &mut self.0.page_buf)
-                            .unwrap();
-                    }
-                    _ => (),
-                },
-                Ok(Event::Eof) => break,
-                Ok(_) => (),
-                Err(_) => break,
-            }
-        }
-        None
+        self.0.next_text()
     }
 }
 
@@ -157,38 +260,7 @@ impl<R: Read> Iterator for TantivyPageIterator<R> {
     type Item = (String, String, String);
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.0.reader.read_event(&mut self.0.buf) {
-                Ok(Event::Start(ref tag)) => match tag.name() {
-                    b"id" => self.0.extract_id(),
-                    b"title" => self.0.extract_title(),
-                    b"text" => {
-                        if self.0.is_filtered_title() {
-                            continue;
-                        }
-                        match self.0.reader.read_text(b"text", &mut self.0.page_buf) {
-                            Ok(page) => {
-                                return Some((self.0.id.clone(), self.0.title.clone(), page));
-                            }
-                            Err(_) => return None,
-                        }
-                    }
-                    _ => (),
-                },
-                Ok(Event::Empty(ref tag)) => match tag.name() {
-                    b"redirect" => {
-                        self.0
-                            .reader
-                            .read_to_end(b"page", &mut self.0.page_buf)
-                            .unwrap();
-                    }
-                    _ => (),
-                },
-                Ok(Event::Eof) => break,
-                Ok(_) => (),
-                Err(_) => break,
-            }
-        }
-        None
+        let text = self.0.next_text()?;
+        Some((self.0.id.clone(), self.0.title.clone(), text))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,417 @@
+use lazy_static::lazy_static;
+use regex::{self, Regex};
+
+use crate::page::anchor::Anchor;
+use crate::page::namespace::{CATEGORY, FILE, MAIN};
+use crate::page::title::TitleNormaliser;
+
+lazy_static! {
+    /// Parser for English wikitext, used when no other parser is given.
+    pub static ref DEFAULT_PARSER: LinkParser = LinkParser::default();
+}
+
+/// Link trail of the English Wikipedia.
+pub const ENGLISH_TRAIL: &str = "[a-z]+";
+
+/// Tags whose content is not parsed as wikitext.
+static VERBATIM_TAGS: &[&str] = &["nowiki", "pre", "math"];
+
+/// Kind of a wikilink, determined by the prefix of its target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LinkKind {
+    /// Link to a page, displayed inline.
+    Page,
+    /// Embedded file or image, e.g. `[[File:x.jpg|thumb|caption]]`.
+    File,
+    /// Category membership, e.g. `[[Category:Greek gods|Orpheus]]`.
+    Category,
+    /// Link to another wiki or language edition, e.g. `[[wikt:lyre]]`.
+    Interwiki,
+}
+
+/// A wikilink found in the text of a page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    /// Byte offset of the opening brackets.
+    pub start: usize,
+    /// Byte offset after the closing brackets and any link trail.
+    pub end: usize,
+    pub kind: LinkKind,
+    /// Namespace of the target page.
+    pub ns: i32,
+    /// Title of the target page in canonical form, without any section
+    /// fragment. Interwiki targets are kept as written.
+    pub target: String,
+    /// Section of the target page, from the text after '#'.
+    pub fragment: Option<String>,
+    /// Text displayed for the link, including any link trail. For files this
+    /// is the caption, and for categories the sort key. Links without a label
+    /// display their target as written.
+    pub surface: String,
+    /// Whether the link appears within a template call.
+    pub in_template: bool,
+}
+
+impl Link {
+    /// Check if the link points to an article, rather than a page in another
+    /// namespace, a file, category or another wiki.
+    pub fn is_article(&self) -> bool {
+        self.kind == LinkKind::Page && self.ns == MAIN && !self.target.is_empty()
+    }
+
+    /// Convert the link to an Anchor.
+    pub fn anchor(&self) -> Anchor {
+        let surface = self.surface.trim().trim_matches('\'');
+        if surface.is_empty() || surface == self.target {
+            Anchor::Direct(self.target.clone())
+        } else {
+            Anchor::Label {
+                surface: surface.to_owned(),
+                page: self.target.clone(),
+            }
+        }
+    }
+}
+
+/// Tokenizer for the wikilinks in a page.
+///
+/// Handles links nested in file captions, link trails, the pipe trick, links
+/// within templates, and skips comments and `<nowiki>` sections.
+#[derive(Clone, Debug)]
+pub struct LinkParser {
+    titles: TitleNormaliser,
+    trail: Option<Regex>,
+}
+
+impl Default for LinkParser {
+    fn default() -> Self {
+        LinkParser::new(TitleNormaliser::default(), ENGLISH_TRAIL).unwrap()
+    }
+}
+
+impl LinkParser {
+    /// Create a link parser.
+    ///
+    /// # Arguments
+    /// * `titles` - Normaliser for link targets, which also determines the
+    ///   namespaces recognised as files and categories.
+    /// * `trail` - Pattern matching the letters following a link which are
+    ///   displayed as part of it, e.g. `[a-z]+` for English. Languages without
+    ///   link trails, such as Japanese, use an empty pattern.
+    pub fn new(titles: TitleNormaliser, trail: &str) -> Result<Self, regex::Error> {
+        let trail = if trail.is_empty() {
+            None
+        } else {
+            Some(Regex::new(&format!("^(?:{})", trail))?)
+        };
+        Ok(LinkParser { titles, trail })
+    }
+
+    /// Normaliser for link targets.
+    pub fn titles(&self) -> &TitleNormaliser {
+        &self.titles
+    }
+
+    /// Find the links in a page, ordered by their start offset.
+    pub fn parse(&self, text: &str) -> Vec<Link> {
+        let bytes = text.as_bytes();
+        let mut links = vec![];
+        // Start offsets of unclosed links, and whether each is in a template.
+        let mut open: Vec<(usize, bool)> = vec![];
+        let mut templates = 0usize;
+        let mut index = 0;
+        while index < bytes.len() {
+            let pair = bytes.get(index + 1) == Some(&bytes[index]);
+            match bytes[index] {
+                b'<' => {
+                    if let Some(end) = skip_verbatim(text, index) {
+                        index = end;
+                        continue;
+                    }
+                }
+                b'{' if pair => {
+                    templates += 1;
+                    index += 2;
+                    continue;
+                }
+                b'}' if pair => {
+                    templates = templates.saturating_sub(1);
+                    index += 2;
+                    continue;
+                }
+                b'[' if pair => {
+                    open.push((index, templates > 0));
+                    index += 2;
+                    continue;
+                }
+                b']' if pair => {
+                    if let Some((start, in_template)) = open.pop() {
+                        if let Some(link) = self.link(text, start, index + 2, in_template) {
+                            links.push(link);
+                        }
+                    }
+                    index += 2;
+                    continue;
+                }
+                _ => (),
+            }
+            index += 1;
+        }
+        // Links nested in captions close before the link containing them.
+        links.sort_by_key(|link| link.start);
+        links
+    }
+
+    /// Parse the link between `start` and `end`, which span its brackets.
+    fn link(&self, text: &str, start: usize, end: usize, in_template: bool) -> Option<Link> {
+        let inner = &text[start + 2..end - 2];
+        let (target, label) = match inner.find('|') {
+            Some(index) => (&inner[..index], Some(&inner[index + 1..])),
+            None => (inner, None),
+        };
+        if target.contains(&['\n', '{', '[', '<'][..]) {
+            return None;
+        }
+        // A leading colon links to a file or category rather than embedding it.
+        let target = target.trim();
+        let (colon, target) = if target.starts_with(':') {
+            (true, target[1..].trim_start())
+        } else {
+            (false, target)
+        };
+        let (target, fragment) = match target.find('#') {
+            Some(index) => (target[..index].trim(), Some(target[index + 1..].trim())),
+            None => (target, None),
+        };
+        if target.is_empty() {
+            return None;
+        }
+
+        let (kind, ns, title) = self.classify(target)?;
+        let kind = match kind {
+            LinkKind::File | LinkKind::Category if colon => LinkKind::Page,
+            kind => kind,
+        };
+        let mut end = end;
+        let surface = match label {
+            None => target.to_owned(),
+            Some(label) if label.trim().is_empty() && kind == LinkKind::Page => {
+                pipe_trick(target).to_owned()
+            }
+            // The caption is the last option of a file link.
+            Some(label) if kind == LinkKind::File => caption(label).trim().to_owned(),
+            Some(label) => label.trim().to_owned(),
+        };
+        let surface = match self.trail {
+            Some(ref trail) if kind == LinkKind::Page => match trail.find(&text[end..]) {
+                Some(found) => {
+                    end += found.end();
+                    format!("{}{}", surface, found.as_str())
+                }
+                None => surface,
+            },
+            _ => surface,
+        };
+        Some(Link {
+            start,
+            end,
+            kind,
+            ns,
+            target: title,
+            fragment: fragment.map(|fragment| fragment.to_owned()),
+            surface,
+            in_template,
+        })
+    }
+
+    /// Determine the kind, namespace and normalised title of a link target,
+    /// returning `None` if the target is not a valid title.
+    fn classify(&self, target: &str) -> Option<(LinkKind, i32, String)> {
+        if let Some(index) = target.find(':') {
+            let prefix = &target[..index];
+            // Interwiki and language prefixes are written in lower case.
+            if !prefix.is_empty()
+                && prefix.bytes().all(|b| b.is_ascii_lowercase() || b == b'-')
+                && self.titles.namespaces().key_for_name(prefix).is_none()
+            {
+                return Some((LinkKind::Interwiki, MAIN, target.to_owned()));
+            }
+        }
+        let title = self.titles.parse(target)?;
+        let kind = match title.ns {
+            FILE | -2 => LinkKind::File,
+            CATEGORY => LinkKind::Category,
+            _ => LinkKind::Page,
+        };
+        Some((kind, title.ns, title.into_string()))
+    }
+}
+
+/// Find `needle` in `haystack`, ignoring ASCII case.
+pub(crate) fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// If a comment or verbatim tag starts at `index`, return the offset after it.
+/// Unterminated sections run to the end of the text.
+pub(crate) fn skip_verbatim(text: &str, index: usize) -> Option<usize> {
+    let rest = &text[index..];
+    if rest.starts_with("<!--") {
+        return Some(match rest.find("-->") {
+            Some(end) => index + end + 3,
+            None => text.len(),
+        });
+    }
+    let bytes = rest.as_bytes();
+    for tag in VERBATIM_TAGS {
+        let len = tag.len() + 1;
+        if bytes.len() <= len || !bytes[1..len].eq_ignore_ascii_case(tag.as_bytes()) {
+            continue;
+        }
+        match bytes[len] {
+            b'>' | b'/' | b' ' | b'\t' | b'\n' => (),
+            _ => continue,
+        }
+        let open = match rest.find('>') {
+            Some(open) => open,
+            None => return Some(text.len()),
+        };
+        if bytes[open - 1] == b'/' {
+            return Some(index + open + 1);
+        }
+        let close = format!("</{}>", tag);
+        return Some(match find_ignore_case(&rest[open..], &close) {
+            Some(end) => index + open + end + close.len(),
+            None => text.len(),
+        });
+    }
+    None
+}
+
+/// Text displayed by the pipe trick, e.g. `[[Orpheus (opera)|]]` is displayed
+/// as "Orpheus": any namespace, and a trailing parenthetical or else any text
+/// after the first comma, are removed.
+pub fn pipe_trick(target: &str) -> &str {
+    let title = match target.find(':') {
+        Some(index) => &target[index + 1..],
+        None => target,
+    };
+    let title = if title.ends_with(')') {
+        match title.rfind('(') {
+            Some(index) => &title[..index],
+            None => title,
+        }
+    } else {
+        match title.find(',') {
+            Some(index) => &title[..index],
+            None => title,
+        }
+    };
+    title.trim()
+}
+
+/// Find the caption among the options of a file link, skipping the options'
+/// own links.
+fn caption(options: &str) -> &str {
+    let bytes = options.as_bytes();
+    let (mut depth, mut last) = (0usize, 0);
+    let mut index = 0;
+    while index < bytes.len() {
+        let pair = bytes.get(index + 1) == Some(&bytes[index]);
+        match bytes[index] {
+            b'[' | b'{' if pair => {
+                depth += 1;
+                index += 1;
+            }
+            b']' | b'}' if pair => {
+                depth = depth.saturating_sub(1);
+                index += 1;
+            }
+            b'|' if depth == 0 => last = index + 1,
+            _ => (),
+        }
+        index += 1;
+    }
+    &options[last..]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_links() {
+        let parser = LinkParser::default();
+        let text = "The [[bus]]es of [[Orpheus (opera)|]] <!-- [[Hidden]] -->\
+                    [[File:Lyre.jpg|thumb|A [[lyre|kithara]] and [[plectrum]]s]]\
+                    <nowiki>[[Escaped]]</nowiki> {{Main|[[Eurydice#Myth|wife]]}}\
+                    [[:Category:Greek gods]] [[Category:Thracians|Orpheus]] [[wikt:lyre]]";
+        let links = parser.parse(text);
+        let summary = links
+            .iter()
+            .map(|link| (link.kind, link.target.as_str(), link.surface.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (LinkKind::Page, "Bus", "buses"),
+                (LinkKind::Page, "Orpheus (opera)", "Orpheus"),
+                (
+                    LinkKind::File,
+                    "File:Lyre.jpg",
+                    "A [[lyre|kithara]] and [[plectrum]]s"
+                ),
+                (LinkKind::Page, "Lyre", "kithara"),
+                (LinkKind::Page, "Plectrum", "plectrums"),
+                (LinkKind::Page, "Eurydice", "wife"),
+                (LinkKind::Page, "Category:Greek gods", "Category:Greek gods"),
+                (LinkKind::Category, "Category:Thracians", "Orpheus"),
+                (LinkKind::Interwiki, "wikt:lyre", "wikt:lyre"),
+            ]
+        );
+
+        assert_eq!(&text[links[0].start..links[0].end], "[[bus]]es");
+        assert_eq!(&text[links[4].start..links[4].end], "[[plectrum]]s");
+        assert!(links[5].in_template);
+        assert_eq!(links[5].fragment, Some("Myth".to_owned()));
+        assert_eq!(links[6].ns, CATEGORY);
+        assert_eq!(
+            links
+                .iter()
+                .filter(|link| link.is_article())
+                .map(Link::anchor)
+                .collect::<Vec<_>>(),
+            vec![
+                Anchor::Label {
+                    surface: "buses".to_owned(),
+                    page: "Bus".to_owned()
+                },
+                Anchor::Label {
+                    surface: "Orpheus".to_owned(),
+                    page: "Orpheus (opera)".to_owned()
+                },
+                Anchor::Label {
+                    surface: "kithara".to_owned(),
+                    page: "Lyre".to_owned()
+                },
+                Anchor::Label {
+                    surface: "plectrums".to_owned(),
+                    page: "Plectrum".to_owned()
+                },
+                Anchor::Label {
+                    surface: "wife".to_owned(),
+                    page: "Eurydice".to_owned()
+                },
+            ]
+        );
+
+        // Without link trails, as in Japanese.
+        let parser = LinkParser::new(TitleNormaliser::default(), "").unwrap();
+        let links = parser.parse("[[bus]]es");
+        assert_eq!(links[0].surface, "bus");
+        assert_eq!(links[0].end, 7);
+    }
+}
@@ -1,12 +1,27 @@
 pub mod anchor;
 pub mod category;
+pub mod filter;
 pub mod iter;
+pub mod links;
+pub mod namespace;
 pub mod page;
+pub mod plaintext;
+pub mod revision;
+pub mod template;
+pub mod title;
 pub mod writer;
 
 pub use self::{
     anchor::Anchor,
+    category::Category,
+    filter::{AnyFilter, PageFilter, PageMeta, PageRules, RulesFilter},
     iter::{PageIterator, RawPageIterator, TantivyPageIterator},
-    page::Page,
+    links::{Link, LinkKind, LinkParser},
+    namespace::{Namespace, NamespaceFilter, Namespaces, SiteInfo},
+    page::{strip_title, Page},
+    plaintext::{Annotated, Block, PlainText, Span},
+    revision::{Contributor, Revision},
+    template::{Arg, CallKind, TemplateCall, TemplateParser},
+    title::{Case, Title, TitleNormaliser},
     writer::PageWriter,
 };
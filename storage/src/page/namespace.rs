@@ -0,0 +1,300 @@
+use quick_xml::{self as qx, events::BytesStart, events::Event};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::BufRead;
+use std::str;
+
+/// Article namespace.
+pub const MAIN: i32 = 0;
+pub const USER_TALK: i32 = 3;
+pub const PROJECT: i32 = 4;
+pub const FILE: i32 = 6;
+pub const FILE_TALK: i32 = 7;
+pub const TEMPLATE: i32 = 10;
+pub const CATEGORY: i32 = 14;
+pub const PORTAL: i32 = 100;
+/// Lua modules, invoked with `{{#invoke:...}}`.
+pub const MODULE: i32 = 828;
+
+/// Canonical names, which MediaWiki accepts in titles regardless of the
+/// language of the wiki.
+static CANONICAL: &[(i32, &str)] = &[
+    (-2, "Media"),
+    (-1, "Special"),
+    (1, "Talk"),
+    (2, "User"),
+    (3, "User talk"),
+    (4, "Project"),
+    (5, "Project talk"),
+    (6, "File"),
+    (6, "Image"),
+    (7, "File talk"),
+    (7, "Image talk"),
+    (8, "MediaWiki"),
+    (9, "MediaWiki talk"),
+    (10, "Template"),
+    (11, "Template talk"),
+    (12, "Help"),
+    (13, "Help talk"),
+    (14, "Category"),
+    (15, "Category talk"),
+];
+
+/// Namespace names used by the English Wikipedia beyond the canonical names.
+static ENGLISH: &[(i32, &str)] = &[
+    (4, "Wikipedia"),
+    (5, "Wikipedia talk"),
+    (100, "Portal"),
+    (101, "Portal talk"),
+    (118, "Draft"),
+    (119, "Draft talk"),
+    (828, "Module"),
+    (829, "Module talk"),
+];
+
+/// A namespace declared in a dump's `<siteinfo>`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Namespace {
+    pub key: i32,
+    /// Localised name; empty for the article namespace.
+    pub name: String,
+}
+
+/// Normalise a namespace name for lookup.
+fn name_key(name: &str) -> String {
+    name.trim().replace('_', " ").to_lowercase()
+}
+
+/// Namespaces of a wiki, with lookup by ID and by name.
+#[derive(Clone, Debug, Default)]
+pub struct Namespaces {
+    by_key: BTreeMap<i32, Namespace>,
+    by_name: HashMap<String, i32>,
+}
+
+impl Namespaces {
+    /// Create namespaces with only the canonical names.
+    pub fn canonical() -> Self {
+        let mut namespaces = Namespaces::default();
+        namespaces.insert(MAIN, "");
+        for (key, name) in CANONICAL {
+            namespaces.add_alias(*key, name);
+        }
+        namespaces
+    }
+
+    /// Namespaces of the English Wikipedia.
+    pub fn english() -> Self {
+        let mut namespaces = Namespaces::canonical();
+        for (key, name) in ENGLISH {
+            namespaces.insert(*key, name);
+        }
+        namespaces
+    }
+
+    /// Declare a namespace, replacing the name of any namespace with the same
+    /// ID. The previous name remains recognised in titles.
+    pub fn insert(&mut self, key: i32, name: &str) {
+        self.by_key.insert(
+            key,
+            Namespace {
+                key,
+                name: name.to_owned(),
+            },
+        );
+        self.add_alias(key, name);
+    }
+
+    /// Recognise an additional name for a namespace in titles.
+    pub fn add_alias(&mut self, key: i32, name: &str) {
+        if !name.is_empty() {
+            self.by_name.insert(name_key(name), key);
+        }
+        self.by_key.entry(key).or_insert_with(|| Namespace {
+            key,
+            name: name.to_owned(),
+        });
+    }
+
+    pub fn get(&self, key: i32) -> Option<&Namespace> {
+        self.by_key.get(&key)
+    }
+
+    /// Find a namespace ID by name.
+    pub fn key_for_name(&self, name: &str) -> Option<i32> {
+        self.by_name.get(&name_key(name)).cloned()
+    }
+
+    /// Determine the namespace of a page from its title.
+    ///
+    /// Titles without a recognised namespace prefix are in the article
+    /// namespace.
+    pub fn key_for_title(&self, title: &str) -> i32 {
+        title
+            .find(':')
+            .and_then(|index| self.key_for_name(&title[..index]))
+            .unwrap_or(MAIN)
+    }
+
+    /// Names by which a namespace is recognised in titles.
+    pub fn names(&self, key: i32) -> Vec<&str> {
+        let mut names = self
+            .by_name
+            .iter()
+            .filter(|(_, k)| **k == key)
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Namespace> {
+        self.by_key.values()
+    }
+}
+
+/// Site metadata from a dump's `<siteinfo>`.
+#[derive(Clone, Debug, Default)]
+pub struct SiteInfo {
+    pub sitename: String,
+    pub base: String,
+    /// Case sensitivity of titles, e.g. `first-letter`.
+    pub case: String,
+    pub namespaces: Namespaces,
+}
+
+/// Fetch the `key` attribute of a `<namespace>` tag.
+fn namespace_key(tag: &BytesStart) -> Option<i32> {
+    tag.attributes()
+        .filter_map(|attr| attr.ok())
+        .find(|attr| attr.key == b"key")
+        .and_then(|attr| str::from_utf8(&attr.value).ok()?.parse::<i32>().ok())
+}
+
+impl SiteInfo {
+    /// Parse `<siteinfo>` from the start of a dump, returning `None` if the
+    /// first page is reached without finding it.
+    ///
+    /// Declared namespaces are added to the canonical namespaces.
+    pub fn parse<R: BufRead>(reader: R) -> Option<Self> {
+        let mut reader = qx::Reader::from_reader(reader);
+        let (mut buf, mut text_buf) = (vec![], vec![]);
+        let mut info = SiteInfo {
+            namespaces: Namespaces::canonical(),
+            ..Default::default()
+        };
+        let mut found = false;
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Start(ref tag)) => match tag.name() {
+                    b"siteinfo" => found = true,
+                    b"sitename" => {
+                        info.sitename = reader.read_text(b"sitename", &mut text_buf).ok()?
+                    }
+                    b"base" => info.base = reader.read_text(b"base", &mut text_buf).ok()?,
+                    b"case" => info.case = reader.read_text(b"case", &mut text_buf).ok()?,
+                    b"namespace" => {
+                        let key = namespace_key(tag);
+                        let name = reader.read_text(b"namespace", &mut text_buf).ok()?;
+                        if let Some(key) = key {
+                            info.namespaces.insert(key, &name);
+                        }
+                    }
+                    b"page" => return None,
+                    _ => (),
+                },
+                Ok(Event::Empty(ref tag)) => {
+                    if tag.name() == b"namespace" {
+                        if let Some(key) = namespace_key(tag) {
+                            info.namespaces.insert(key, "");
+                        }
+                    }
+                }
+                Ok(Event::End(ref tag)) => {
+                    if tag.name() == b"siteinfo" {
+                        break;
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => return None,
+                Ok(_) => (),
+            }
+            buf.clear();
+        }
+        if !found {
+            return None;
+        }
+        // The project namespace is also known by the site name.
+        if !info.sitename.is_empty() {
+            info.namespaces.add_alias(PROJECT, &info.sitename);
+        }
+        Some(info)
+    }
+}
+
+/// Namespaces excluded from page extraction by default: user talk, project,
+/// file, file talk and template pages.
+pub static DEFAULT_EXCLUDED: &[i32] = &[USER_TALK, PROJECT, FILE, FILE_TALK, TEMPLATE];
+
+/// Filter deciding which pages to extract by namespace ID.
+#[derive(Clone, Debug)]
+pub struct NamespaceFilter {
+    pub namespaces: Namespaces,
+    excluded: HashSet<i32>,
+}
+
+impl Default for NamespaceFilter {
+    fn default() -> Self {
+        NamespaceFilter::new(Namespaces::english(), DEFAULT_EXCLUDED)
+    }
+}
+
+impl NamespaceFilter {
+    pub fn new(namespaces: Namespaces, excluded: &[i32]) -> Self {
+        NamespaceFilter {
+            namespaces,
+            excluded: excluded.iter().cloned().collect(),
+        }
+    }
+
+    /// Determine the namespace of a page, preferring its `<ns>` element.
+    pub fn namespace_of(&self, title: &str, ns: Option<i32>) -> i32 {
+        ns.unwrap_or_else(|| self.namespaces.key_for_title(title))
+    }
+
+    /// Check if a page should be skipped.
+    pub fn is_excluded(&self, title: &str, ns: Option<i32>) -> bool {
+        self.excluded.contains(&self.namespace_of(title, ns))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::File;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_siteinfo() {
+        let file = File::open("../test_data/sample-jawiki-latest-pages-articles.xml").unwrap();
+        let info = SiteInfo::parse(BufReader::new(file)).unwrap();
+        assert_eq!(info.sitename, "Wikipedia");
+        assert_eq!(info.case, "first-letter");
+
+        let namespaces = &info.namespaces;
+        assert_eq!(namespaces.get(FILE).unwrap().name, "ファイル");
+        assert_eq!(namespaces.key_for_title("ファイル:Example.png"), FILE);
+        // Canonical names remain valid.
+        assert_eq!(namespaces.key_for_title("File:Example.png"), FILE);
+        assert_eq!(namespaces.key_for_title("user_talk:Kompek"), USER_TALK);
+        assert_eq!(namespaces.key_for_title("Wikipedia:Sandbox"), PROJECT);
+        assert_eq!(
+            namespaces.key_for_title("Re:ゼロから始める異世界生活"),
+            MAIN
+        );
+
+        let filter = NamespaceFilter::new(info.namespaces.clone(), DEFAULT_EXCLUDED);
+        assert!(filter.is_excluded("利用者‐会話:Kompek", None));
+        assert!(!filter.is_excluded("利用者‐会話:Kompek", Some(MAIN)));
+        assert!(!filter.is_excluded("Portal:Example", None));
+    }
+}
@@ -2,12 +2,35 @@ use serde::{Deserialize, Serialize};
 
 use crate::page::anchor::Anchor;
 use crate::page::category::Category;
+use crate::page::links::{Link, LinkParser, DEFAULT_PARSER};
+use crate::page::revision::Revision;
+
+/// Strip disambiguation qualifiers, i.e. all text after the first '(' or ',',
+/// from a title.
+///
+/// Titles consisting only of a qualifier are returned unchanged.
+pub fn strip_title(title: &str) -> &str {
+    let stripped = match title.find(|c: char| c == '(' || c == ',') {
+        Some(index) => title[..index].trim(),
+        None => title.trim(),
+    };
+    if stripped.is_empty() {
+        return title.trim();
+    }
+    stripped
+}
 
 /// Collection of Anchors and Categories for a Wikipedia page.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Page {
     pub title: String,
     pub id: String,
+    /// Namespace ID.
+    #[serde(default)]
+    pub ns: i32,
+    /// Metadata of the page's revision.
+    #[serde(default)]
+    pub revision: Revision,
     pub anchors: Vec<Anchor>,
     pub categories: Vec<Category>,
 }
@@ -16,52 +39,73 @@ impl Page {
     /// Create a new Page object, extracting links and categories from the text
     /// of the page.
     pub fn new(title: String, id: String, page: &str) -> Self {
+        Page::with_links(title, id, page, &DEFAULT_PARSER)
+    }
+
+    /// Create a new Page object, extracting links with the given parser.
+    pub fn with_links(title: String, id: String, page: &str, links: &LinkParser) -> Self {
         Page {
             title,
             id,
-            anchors: Page::extract_anchors(&page),
-            categories: Page::extract_categories(&page),
+            ns: 0,
+            revision: Revision::default(),
+            anchors: Page::extract_anchors_with(&page, links),
+            categories: Page::extract_categories_with(&page, links),
         }
     }
 
     /// Return the title with all text after the first '(' or ',' stripped.
     pub fn title_stripped(&self) -> String {
-        self.title.clone()
+        strip_title(&self.title).to_owned()
     }
 
     /// Extract category links from the text of a Wikipedia page, returning a
     /// Vec of Category objects.
     pub fn extract_categories(page: &str) -> Vec<Category> {
-        let page = match page.rfind("==References==") {
-            Some(index) => &page[index..],
-            None => page,
-        };
-        page.match_indices("[[")
-            .filter_map(|(begin, _)| {
-                let initial = &page[begin + 2..];
-                if initial.starts_with("Category:") {
-                    return initial
-                        .find("]]")
-                        .and_then(|end| match initial[..end].find('|') {
-                            Some(actual_end) => Some(&initial[9..actual_end]),
-                            None => Some(&initial[9..end]),
-                        });
-                }
-                None
-            })
-            .map(|cat| Category(cat.to_owned()))
-            .collect::<Vec<_>>()
+        Page::extract_categories_with(page, &DEFAULT_PARSER)
     }
+
+    /// Extract category links, with their sort keys, from the text of a
+    /// Wikipedia page using the given parser.
+    pub fn extract_categories_with(page: &str, links: &LinkParser) -> Vec<Category> {
+        Category::parse(page, links)
+    }
+
     /// Extract links from the text of a Wikipedia page, returning a Vec of
     /// Anchor objects.
     pub fn extract_anchors(page: &str) -> Vec<Anchor> {
+        Page::extract_anchors_with(page, &DEFAULT_PARSER)
+    }
+
+    /// Extract links to articles from the text of a Wikipedia page using the
+    /// given parser.
+    pub fn extract_anchors_with(page: &str, links: &LinkParser) -> Vec<Anchor> {
         let page = match page.rfind("==References==") {
             Some(index) => &page[..index],
             None => page,
         };
-        page.match_indices("[[")
-            .filter_map(|(begin, _)| Anchor::pare_anchor_match(page, begin))
-            .map(Anchor::parse)
+        links
+            .parse(page)
+            .iter()
+            .filter(|link| link.is_article())
+            .map(Link::anchor)
             .collect::<Vec<_>>()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_title_stripped() {
+        let page = |title: &str| Page {
+            title: title.to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(page("Mercury (planet)").title_stripped(), "Mercury");
+        assert_eq!(page("Paris, Texas").title_stripped(), "Paris");
+        assert_eq!(page("Orpheus").title_stripped(), "Orpheus");
+        assert_eq!(page("(Untitled)").title_stripped(), "(Untitled)");
+    }
+}
@@ -0,0 +1,653 @@
+use serde::{Deserialize, Serialize};
+use std::char;
+
+use crate::page::links::{find_ignore_case, LinkKind, LinkParser, DEFAULT_PARSER};
+use crate::page::title::decode_entity;
+
+/// Tags whose content is not displayed as article text.
+static DROPPED_TAGS: &[&str] = &[
+    "ref",
+    "references",
+    "gallery",
+    "math",
+    "chem",
+    "ce",
+    "score",
+    "timeline",
+    "imagemap",
+    "graph",
+    "hiero",
+    "mapframe",
+    "maplink",
+    "templatedata",
+    "syntaxhighlight",
+    "source",
+];
+
+/// Tags whose content is displayed without being parsed.
+static LITERAL_TAGS: &[&str] = &["nowiki", "pre"];
+
+/// Prefixes of bracketed external links, e.g. `[https://example.org label]`.
+static URL_SCHEMES: &[&str] = &["http://", "https://", "ftp://", "//", "mailto:", "irc://"];
+
+/// Marks the end of a link in annotated text. Links start with a character
+/// after this one, encoding the index of their target.
+const MARK_END: u32 = 0xF0000;
+/// Number of links which may be annotated in a page.
+const MAX_MARKS: usize = 0xFFFD;
+
+/// Check if a character is used to mark links, and so is dropped from text.
+fn is_mark(c: char) -> bool {
+    (MARK_END..=MARK_END + MAX_MARKS as u32).contains(&(c as u32))
+}
+
+/// A link in rendered text.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Span {
+    /// Offset of the first character of the link, in characters.
+    pub start: usize,
+    /// Offset after the last character of the link, in characters.
+    pub end: usize,
+    pub surface: String,
+    /// Title of the linked page.
+    pub target: String,
+}
+
+/// Plain text with the spans of the links to articles within it.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Annotated {
+    pub text: String,
+    pub spans: Vec<Span>,
+}
+
+/// Text rendered from wikitext, and the targets of the links marked in it.
+struct Output {
+    text: String,
+    targets: Option<Vec<String>>,
+}
+
+impl Output {
+    fn new(capacity: usize, annotate: bool) -> Self {
+        Output {
+            text: String::with_capacity(capacity),
+            targets: if annotate { Some(vec![]) } else { None },
+        }
+    }
+
+    /// Mark the start of a link to `target`, returning false if links are
+    /// not being annotated.
+    fn mark(&mut self, target: &str) -> bool {
+        match self.targets {
+            Some(ref mut targets) if targets.len() < MAX_MARKS => {
+                let mark = MARK_END + 1 + targets.len() as u32;
+                self.text.push(char::from_u32(mark).unwrap());
+                targets.push(target.to_owned());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A paragraph or section heading of rendered text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Block {
+    Heading { level: usize, text: String },
+    Paragraph(String),
+}
+
+impl Block {
+    pub fn text(&self) -> &str {
+        match self {
+            Block::Heading { text, .. } => text,
+            Block::Paragraph(text) => text,
+        }
+    }
+}
+
+/// Renderer of wikitext as plain text.
+///
+/// Templates, tables, references, comments, HTML tags, files and categories
+/// are removed, bold and italic markup is stripped and links are replaced by
+/// their surface text, while paragraphs and section headings are kept.
+pub struct PlainText<'a> {
+    links: &'a LinkParser,
+}
+
+impl Default for PlainText<'static> {
+    fn default() -> Self {
+        PlainText::new(&DEFAULT_PARSER)
+    }
+}
+
+impl<'a> PlainText<'a> {
+    /// Create a renderer, using the given parser to find the surface text of
+    /// links.
+    pub fn new(links: &'a LinkParser) -> Self {
+        PlainText { links }
+    }
+
+    /// Render wikitext as plain text, with blocks separated by blank lines.
+    pub fn render(&self, text: &str) -> String {
+        self.blocks(text)
+            .iter()
+            .map(Block::text)
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Render wikitext as a sequence of paragraphs and section headings.
+    ///
+    /// List items are rendered as paragraphs of their own, and sections left
+    /// without any text, such as those holding only references, are dropped.
+    pub fn blocks(&self, text: &str) -> Vec<Block> {
+        self.render_blocks(text, false).0
+    }
+
+    /// Render wikitext as plain text, as `render` does, along with the spans of
+    /// links to articles.
+    ///
+    /// Span offsets count characters rather than bytes, so that they index the
+    /// text in the same way as most consumers of JSON.
+    pub fn annotate(&self, text: &str) -> Annotated {
+        let (blocks, targets) = self.render_blocks(text, true);
+        let mut annotated = Annotated::default();
+        let mut chars = 0;
+        for block in blocks {
+            let (text, spans) = unmark(block.text(), &targets);
+            if text.is_empty() {
+                continue;
+            }
+            if !annotated.text.is_empty() {
+                annotated.text.push_str("\n\n");
+                chars += 2;
+            }
+            annotated.spans.extend(spans.into_iter().map(|span| Span {
+                start: span.start + chars,
+                end: span.end + chars,
+                ..span
+            }));
+            annotated.text.push_str(&text);
+            chars += text.chars().count();
+        }
+        annotated
+    }
+
+    /// Render blocks, marking links to articles if `annotate` is set and
+    /// returning the targets of the marked links.
+    fn render_blocks(&self, text: &str, annotate: bool) -> (Vec<Block>, Vec<String>) {
+        let mut out = Output::new(text.len(), annotate);
+        self.inline(text, &mut out);
+        let inline = out.text;
+
+        let mut blocks = vec![];
+        let mut paragraph = String::new();
+        let flush = |paragraph: &mut String, blocks: &mut Vec<Block>| {
+            let text = collapse_spaces(paragraph);
+            if !text.is_empty() {
+                blocks.push(Block::Paragraph(text));
+            }
+            paragraph.clear();
+        };
+        for line in inline.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("----") {
+                flush(&mut paragraph, &mut blocks);
+                continue;
+            }
+            if let Some((level, heading)) = heading(line) {
+                flush(&mut paragraph, &mut blocks);
+                blocks.push(Block::Heading {
+                    level,
+                    text: collapse_spaces(heading),
+                });
+                continue;
+            }
+            let item = line.trim_start_matches(|c| c == '*' || c == '#' || c == ':' || c == ';');
+            let is_item = item.len() != line.len();
+            if is_item {
+                flush(&mut paragraph, &mut blocks);
+            }
+            paragraph.push(' ');
+            paragraph.push_str(item);
+            if is_item {
+                flush(&mut paragraph, &mut blocks);
+            }
+        }
+        flush(&mut paragraph, &mut blocks);
+        (drop_empty_sections(blocks), out.targets.unwrap_or_default())
+    }
+
+    /// Render the inline markup of wikitext, leaving headings and list markers
+    /// to be handled line by line.
+    fn inline(&self, text: &str, out: &mut Output) {
+        let links = self.links.parse(text);
+        let mut next_link = 0;
+        let bytes = text.as_bytes();
+        let mut index = 0;
+        while index < bytes.len() {
+            let rest = &text[index..];
+            match bytes[index] {
+                b'<' if rest.starts_with("<!--") => {
+                    index = match rest.find("-->") {
+                        Some(end) => index + end + 3,
+                        None => text.len(),
+                    };
+                    continue;
+                }
+                b'<' => {
+                    if let Some(tag) = Tag::parse(rest) {
+                        index = self.tag(text, index, &tag, out);
+                        continue;
+                    }
+                }
+                b'{' if rest.starts_with("{{") => {
+                    index = skip_template(text, index);
+                    continue;
+                }
+                b'{' if rest.starts_with("{|") && at_line_start(text, index) => {
+                    index = skip_table(text, index);
+                    continue;
+                }
+                b'[' if rest.starts_with("[[") => {
+                    while next_link < links.len() && links[next_link].start < index {
+                        next_link += 1;
+                    }
+                    if let Some(link) = links.get(next_link).filter(|link| link.start == index) {
+                        match link.kind {
+                            LinkKind::Page => {
+                                let marked = link.is_article() && out.mark(&link.target);
+                                self.inline(&link.surface, out);
+                                if marked {
+                                    out.text.push(char::from_u32(MARK_END).unwrap());
+                                }
+                            }
+                            // Interwiki links without a label are usually
+                            // links to other language editions, which are
+                            // not displayed in the text.
+                            LinkKind::Interwiki if link.surface != link.target => {
+                                self.inline(&link.surface, out)
+                            }
+                            _ => (),
+                        }
+                        index = link.end;
+                        continue;
+                    }
+                }
+                b'[' => {
+                    if let Some(end) = self.external_link(text, index, out) {
+                        index = end;
+                        continue;
+                    }
+                }
+                b'\'' if rest.starts_with("''") => {
+                    let run = rest.len() - rest.trim_start_matches('\'').len();
+                    // Apostrophes beyond bold and italic markup are displayed.
+                    match run {
+                        4 => out.text.push('\''),
+                        run if run > 5 => out.text.extend(std::iter::repeat('\'').take(run - 5)),
+                        _ => (),
+                    }
+                    index += run;
+                    continue;
+                }
+                b'&' => {
+                    let entity = rest[1..]
+                        .find(';')
+                        .filter(|end| *end <= 10)
+                        .and_then(|end| decode_entity(&rest[1..1 + end]).map(|c| (c, end)));
+                    if let Some((c, end)) = entity {
+                        out.text.push(c);
+                        index += end + 2;
+                        continue;
+                    }
+                }
+                b'_' if rest.starts_with("__") => {
+                    // Behaviour switches such as __NOTOC__.
+                    let name = rest[2..].trim_start_matches(|c: char| c.is_ascii_uppercase());
+                    if name.len() < rest.len() - 2 && name.starts_with("__") {
+                        index = text.len() - name.len() + 2;
+                        continue;
+                    }
+                }
+                _ => (),
+            }
+            let c = rest.chars().next().unwrap();
+            if !is_mark(c) {
+                out.text.push(c);
+            }
+            index += c.len_utf8();
+        }
+    }
+
+    /// Render an HTML tag starting at `index`, returning the offset after the
+    /// tag, or after its element if the content is dropped or literal.
+    fn tag(&self, text: &str, index: usize, tag: &Tag, out: &mut Output) -> usize {
+        let after = index + tag.len;
+        if tag.name == "br" {
+            out.text.push(' ');
+        }
+        if tag.closing || tag.self_closing {
+            return after;
+        }
+        let literal = LITERAL_TAGS.contains(&tag.name.as_str());
+        if !literal && !DROPPED_TAGS.contains(&tag.name.as_str()) {
+            return after;
+        }
+        let (content_end, end) = match find_ignore_case(&text[after..], &format!("</{}", tag.name))
+        {
+            Some(close) => {
+                let close = after + close;
+                match text[close..].find('>') {
+                    Some(end) => (close, close + end + 1),
+                    None => (close, text.len()),
+                }
+            }
+            None => (text.len(), text.len()),
+        };
+        if literal {
+            let content = &text[after..content_end];
+            out.text.extend(content.chars().filter(|c| !is_mark(*c)));
+        }
+        end
+    }
+
+    /// Render a bracketed external link starting at `index` as its label,
+    /// returning the offset after it.
+    fn external_link(&self, text: &str, index: usize, out: &mut Output) -> Option<usize> {
+        let rest = &text[index + 1..];
+        let scheme = URL_SCHEMES.iter().any(|scheme| {
+            rest.len() >= scheme.len()
+                && rest.as_bytes()[..scheme.len()].eq_ignore_ascii_case(scheme.as_bytes())
+        });
+        if !scheme {
+            return None;
+        }
+        let close = rest
+            .find(|c| c == ']' || c == '\n')
+            .filter(|close| rest[*close..].starts_with(']'))?;
+        if let Some(space) = rest[..close].find(' ') {
+            self.inline(&rest[space + 1..close], out);
+        }
+        Some(index + close + 2)
+    }
+}
+
+/// An HTML tag in wikitext.
+struct Tag {
+    /// Lowercased tag name.
+    name: String,
+    /// Whether this is a closing tag, e.g. `</ref>`.
+    closing: bool,
+    /// Whether this is a self-closing tag, e.g. `<ref name="a" />`.
+    self_closing: bool,
+    /// Length of the tag in bytes.
+    len: usize,
+}
+
+impl Tag {
+    /// Parse a tag at the start of `text`, returning `None` if it does not
+    /// start with one.
+    ///
+    /// Tag names start with a letter and tags end on the line they start,
+    /// so that a `<` in text such as `x <3 y` or `a < b` is not taken for a
+    /// tag swallowing the text up to the next `>`.
+    fn parse(text: &str) -> Option<Tag> {
+        let bytes = text.as_bytes();
+        let closing = bytes.get(1) == Some(&b'/');
+        let name_start = if closing { 2 } else { 1 };
+        if !bytes.get(name_start)?.is_ascii_alphabetic() {
+            return None;
+        }
+        let name_len = text[name_start..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(text.len() - name_start);
+        let name_end = name_start + name_len;
+        match bytes.get(name_end) {
+            Some(b'>') | Some(b'/') | Some(b' ') | Some(b'\t') => (),
+            _ => return None,
+        }
+        let end = name_end
+            + text[name_end..]
+                .find(|c| c == '>' || c == '\n')
+                .filter(|end| bytes[name_end + end] == b'>')?;
+        Some(Tag {
+            name: text[name_start..name_end].to_ascii_lowercase(),
+            closing,
+            self_closing: bytes[end - 1] == b'/',
+            len: end + 1,
+        })
+    }
+}
+
+/// Check if `index` is at the start of a line, ignoring indentation.
+fn at_line_start(text: &str, index: usize) -> bool {
+    match text[..index]
+        .trim_end_matches(|c| c == ' ' || c == '\t')
+        .bytes()
+        .last()
+    {
+        Some(b'\n') | None => true,
+        _ => false,
+    }
+}
+
+/// Find the offset after the template starting at `index`.
+///
+/// Braces are balanced singly, so that template parameters such as
+/// `{{{1}}}` and tables within templates are skipped along with them.
+fn skip_template(text: &str, index: usize) -> usize {
+    let mut depth = 0usize;
+    for (offset, byte) in text[index..].bytes().enumerate() {
+        match byte {
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return index + offset + 1;
+                }
+            }
+            _ => (),
+        }
+    }
+    text.len()
+}
+
+/// Find the offset after the table starting at `index`, including any tables
+/// nested in it.
+fn skip_table(text: &str, index: usize) -> usize {
+    let mut depth = 0usize;
+    let mut offset = index;
+    for line in text[index..].split_terminator('\n') {
+        offset += line.len() + 1;
+        let line = line.trim_start();
+        if line.starts_with("{|") {
+            depth += 1;
+        } else if line.starts_with("|}") {
+            depth -= 1;
+            if depth == 0 {
+                return offset.min(text.len());
+            }
+        }
+    }
+    text.len()
+}
+
+/// Parse a section heading, e.g. `== History ==`, returning its level and text.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    if !line.starts_with('=') || !line.ends_with('=') {
+        return None;
+    }
+    let open = line.len() - line.trim_start_matches('=').len();
+    let close = line.len() - line.trim_end_matches('=').len();
+    let level = open.min(close).min(6);
+    if line.len() <= 2 * level {
+        return None;
+    }
+    Some((level, line[level..line.len() - level].trim()))
+}
+
+/// Replace runs of whitespace with single spaces, trimming the ends.
+fn collapse_spaces(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Remove link marks from the text of a block, returning the text and the
+/// spans of the marked links, trimmed of whitespace.
+fn unmark(marked: &str, targets: &[String]) -> (String, Vec<Span>) {
+    let mut text = String::with_capacity(marked.len());
+    let mut spans = vec![];
+    // Target index, and character and byte offsets, of each open link.
+    let mut open: Vec<(usize, usize, usize)> = vec![];
+    let mut chars = 0;
+    for c in marked.chars() {
+        if !is_mark(c) {
+            text.push(c);
+            chars += 1;
+        } else if c as u32 == MARK_END {
+            if let Some((target, start, byte_start)) = open.pop() {
+                let surface = &text[byte_start..];
+                let leading = surface.len() - surface.trim_start().len();
+                let trimmed = surface.trim();
+                if trimmed.is_empty() {
+                    continue;
+                }
+                let start = start + surface[..leading].chars().count();
+                spans.push(Span {
+                    start,
+                    end: start + trimmed.chars().count(),
+                    surface: trimmed.to_owned(),
+                    target: targets[target].clone(),
+                });
+            }
+        } else {
+            let target = (c as u32 - MARK_END - 1) as usize;
+            open.push((target, chars, text.len()));
+        }
+    }
+    spans.sort_by_key(|span| span.start);
+    (text, spans)
+}
+
+/// Remove headings of sections without any paragraphs, including those whose
+/// subsections are all empty.
+fn drop_empty_sections(blocks: Vec<Block>) -> Vec<Block> {
+    let mut kept: Vec<Block> = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        if let Block::Heading { level, .. } = block {
+            while let Some(Block::Heading { level: last, .. }) = kept.last() {
+                if *last < level {
+                    break;
+                }
+                kept.pop();
+            }
+        }
+        kept.push(block);
+    }
+    while let Some(Block::Heading { .. }) = kept.last() {
+        kept.pop();
+    }
+    kept
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let text = r#"{{Infobox person
+| name = Orpheus
+| image = {{{image|}}}
+}}
+'''Orpheus''' ({{IPA|ˈɔːrfiəs}}) was a [[Thrace|Thracian]] [[bard]]s and
+[[Lyre|lyre]]-player<ref name="ovid">Ovid, ''[[Metamorphoses]]''</ref> &ndash; a
+legendary musician.<!-- citation needed -->__NOTOC__
+[[File:Orpheus.jpg|thumb|Orpheus with his [[lyre]]]]
+
+== Myth ==
+{| class="wikitable"
+|-
+| Father || [[Oeagrus]]
+|}
+He married <span lang="grc">''[[Eurydice]]''</span>.<br/>See [https://example.org the sources] [https://example.org/1].
+* One of the [[Argonauts]]
+* <nowiki>[[Not a link]]</nowiki>
+
+== References ==
+{{Reflist}}
+=== Notes ===
+<references />
+
+[[Category:Greek mythology]]
+[[fr:Orphée]]"#;
+        let blocks = PlainText::default().blocks(text);
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Paragraph(
+                    "Orpheus () was a Thracian bards and lyre-player – a legendary musician."
+                        .to_owned()
+                ),
+                Block::Heading {
+                    level: 2,
+                    text: "Myth".to_owned()
+                },
+                Block::Paragraph("He married Eurydice. See the sources .".to_owned()),
+                Block::Paragraph("One of the Argonauts".to_owned()),
+                Block::Paragraph("[[Not a link]]".to_owned()),
+            ]
+        );
+        assert!(PlainText::default()
+            .render(text)
+            .starts_with("Orpheus () was a Thracian bards and lyre-player – a legendary musician.\n\nMyth\n\nHe married"));
+    }
+
+    #[test]
+    fn test_tag_parse() {
+        assert_eq!(
+            Tag::parse("<ref name=\"a\" />").map(|tag| tag.len),
+            Some(16)
+        );
+        assert!(Tag::parse("</ref>").unwrap().closing);
+        assert!(Tag::parse("<3 and 4> 2").is_none());
+        assert!(Tag::parse("< b, so c > d").is_none());
+        assert!(Tag::parse("<b\n> c").is_none());
+        assert!(Tag::parse("<b and\nc>").is_none());
+        assert_eq!(
+            PlainText::default().render("x <3 y, so <y and\nz> w"),
+            "x <3 y, so <y and z> w"
+        );
+    }
+
+    #[test]
+    fn test_annotate() {
+        let text = "'''Orpheus''' ({{lang|grc|Ὀρφεύς}}) played the [[Lyre|lyre]] for \
+                    [[eurydice]].\n\n== Myth ==\nHe joined the [[Argonauts]]<ref>[[Apollonius]]</ref> \
+                    and sailed to [[Colchis|]].\n[[Category:Greek mythology]]";
+        let annotated = PlainText::default().annotate(text);
+        assert_eq!(
+            annotated.text,
+            "Orpheus () played the lyre for eurydice.\n\nMyth\n\nHe joined the Argonauts and sailed to Colchis."
+        );
+        assert_eq!(annotated.text, PlainText::default().render(text));
+        let chars = annotated.text.chars().collect::<Vec<_>>();
+        for span in &annotated.spans {
+            let surface = chars[span.start..span.end].iter().collect::<String>();
+            assert_eq!(surface, span.surface);
+        }
+        assert_eq!(
+            annotated
+                .spans
+                .iter()
+                .map(|span| (span.surface.as_str(), span.target.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("lyre", "Lyre"),
+                ("eurydice", "Eurydice"),
+                ("Argonauts", "Argonauts"),
+                ("Colchis", "Colchis"),
+            ]
+        );
+    }
+}
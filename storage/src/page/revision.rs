@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+/// Author of a revision.
+///
+/// Registered users have a username and ID; anonymous edits have only an IP
+/// address. All fields are absent if the contributor has been suppressed.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Contributor {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+}
+
+/// Metadata of the revision of a page included in a dump.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Revision {
+    /// Revision ID.
+    #[serde(default)]
+    pub id: u64,
+    /// ID of the previous revision of the page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<u64>,
+    /// ISO 8601 UTC timestamp, e.g. `2019-01-10T12:00:00Z`.
+    #[serde(default)]
+    pub timestamp: String,
+    #[serde(default)]
+    pub contributor: Contributor,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Whether the revision was marked as a minor edit.
+    #[serde(default)]
+    pub minor: bool,
+    /// Content model, e.g. `wikitext`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Content format, e.g. `text/x-wiki`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// Base-36 SHA-1 of the revision text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::page::PageIterator;
+    use std::io::BufReader;
+
+    #[test]
+    fn test_revision() {
+        let xml = r#"
+<page>
+    <title>Orpheus</title>
+    <ns>0</ns>
+    <id>22</id>
+    <revision>
+        <id>880000001</id>
+        <parentid>879999999</parentid>
+        <timestamp>2019-01-10T12:00:00Z</timestamp>
+        <contributor>
+            <username>Kompek</username>
+            <id>1234</id>
+        </contributor>
+        <minor />
+        <comment>copyedit</comment>
+        <model>wikitext</model>
+        <format>text/x-wiki</format>
+        <text xml:space="preserve">[[Eurydice|his wife]]</text>
+        <sha1>phoiac9h4m842xq45sp7s6u21eteeq1</sha1>
+    </revision>
+</page>
+<page>
+    <title>Eurydice</title>
+    <ns>0</ns>
+    <id>23</id>
+    <revision>
+        <id>880000002</id>
+        <timestamp>2018-06-01T00:00:00Z</timestamp>
+        <contributor><ip>192.0.2.1</ip></contributor>
+        <text xml:space="preserve">Wife of [[Orpheus]].</text>
+    </revision>
+</page>"#;
+        let pages = PageIterator::new(BufReader::new(xml.as_bytes())).collect::<Vec<_>>();
+        assert_eq!(pages.len(), 2);
+
+        let page = &pages[0];
+        assert_eq!(page.id, "22");
+        assert_eq!(page.anchors.len(), 1);
+        assert_eq!(
+            page.revision,
+            Revision {
+                id: 880000001,
+                parent_id: Some(879999999),
+                timestamp: "2019-01-10T12:00:00Z".to_owned(),
+                contributor: Contributor {
+                    username: Some("Kompek".to_owned()),
+                    id: Some(1234),
+                    ip: None,
+                },
+                comment: Some("copyedit".to_owned()),
+                minor: true,
+                model: Some("wikitext".to_owned()),
+                format: Some("text/x-wiki".to_owned()),
+                sha1: Some("phoiac9h4m842xq45sp7s6u21eteeq1".to_owned()),
+            }
+        );
+
+        let page = &pages[1];
+        assert_eq!(page.id, "23");
+        assert_eq!(page.revision.id, 880000002);
+        assert_eq!(page.revision.parent_id, None);
+        assert!(!page.revision.minor);
+        assert_eq!(page.revision.contributor.ip, Some("192.0.2.1".to_owned()));
+        assert_eq!(page.revision.sha1, None);
+    }
+}
@@ -0,0 +1,519 @@
+use std::ops::Range;
+
+use crate::page::links::skip_verbatim;
+use crate::page::namespace::{MAIN, TEMPLATE};
+use crate::page::title::TitleNormaliser;
+
+/// Variables, written without arguments, e.g. `{{PAGENAME}}`. Case-sensitive.
+static VARIABLES: &[&str] = &[
+    "!",
+    "=",
+    "ARTICLEPAGENAME",
+    "BASEPAGENAME",
+    "CONTENTLANGUAGE",
+    "CURRENTDAY",
+    "CURRENTDAY2",
+    "CURRENTDAYNAME",
+    "CURRENTHOUR",
+    "CURRENTMONTH",
+    "CURRENTMONTHNAME",
+    "CURRENTTIME",
+    "CURRENTTIMESTAMP",
+    "CURRENTYEAR",
+    "DIRMARK",
+    "FULLPAGENAME",
+    "LOCALDAY",
+    "LOCALMONTH",
+    "LOCALTIMESTAMP",
+    "LOCALYEAR",
+    "NAMESPACE",
+    "NAMESPACENUMBER",
+    "NUMBEROFARTICLES",
+    "NUMBEROFPAGES",
+    "PAGEID",
+    "PAGENAME",
+    "PAGENAMEE",
+    "REVISIONDAY",
+    "REVISIONID",
+    "REVISIONMONTH",
+    "REVISIONTIMESTAMP",
+    "REVISIONYEAR",
+    "ROOTPAGENAME",
+    "SERVER",
+    "SERVERNAME",
+    "SITENAME",
+    "SUBJECTPAGENAME",
+    "SUBJECTSPACE",
+    "SUBPAGENAME",
+    "TALKPAGENAME",
+    "TALKSPACE",
+];
+
+/// Parser functions without a '#' prefix, written with a colon before their
+/// first argument, e.g. `{{lc:Text}}`. Case-insensitive.
+static FUNCTIONS: &[&str] = &[
+    "anchorencode",
+    "bidi",
+    "canonicalurl",
+    "DEFAULTSORT",
+    "DISPLAYTITLE",
+    "filepath",
+    "formatnum",
+    "fullurl",
+    "gender",
+    "grammar",
+    "int",
+    "lc",
+    "lcfirst",
+    "localurl",
+    "msg",
+    "ns",
+    "nse",
+    "padleft",
+    "padright",
+    "PAGESINCATEGORY",
+    "plural",
+    "raw",
+    "special",
+    "tag",
+    "uc",
+    "ucfirst",
+    "urlencode",
+];
+
+/// Prefixes of template calls which change how they are expanded, but not the
+/// template called.
+static MODIFIERS: &[&str] = &["subst:", "safesubst:", "msgnw:"];
+
+/// Kind of a `{{...}}` construct.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CallKind {
+    /// Transclusion of a page, usually in the template namespace.
+    Template,
+    /// Parser function, e.g. `{{#if:...}}` or `{{lc:...}}`.
+    ParserFunction,
+    /// Variable, e.g. `{{PAGENAME}}` or `{{!}}`.
+    Variable,
+    /// Template parameter, e.g. `{{{1|default}}}`.
+    Parameter,
+}
+
+/// An argument of a template call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arg {
+    /// Name of a named argument, or the position of a positional argument
+    /// counting from 1, e.g. "2" for the second positional argument.
+    pub name: String,
+    /// Whether the argument was written without a name.
+    pub positional: bool,
+    /// Value as written. Values of named arguments are trimmed of whitespace,
+    /// as MediaWiki does; positional values are not.
+    pub value: String,
+    /// Byte offsets of the value.
+    pub span: Range<usize>,
+}
+
+/// A template call, parser function, variable or parameter found in the text
+/// of a page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateCall {
+    pub kind: CallKind,
+    /// Namespace of the page transcluded by a template call.
+    pub ns: i32,
+    /// For template calls, the title of the page transcluded in canonical
+    /// form, e.g. `Template:Infobox person`. For parser functions, the name of
+    /// the function, e.g. `#if`; for variables and parameters, the name as
+    /// written.
+    pub name: String,
+    /// Arguments in the order written. The first argument of a parser
+    /// function is the text after its colon, and a parameter's only argument
+    /// is its default.
+    pub args: Vec<Arg>,
+    /// Byte offsets of the call, including its braces.
+    pub span: Range<usize>,
+    /// Byte offsets of the name as written, up to the first pipe. For parser
+    /// functions this includes the first argument.
+    pub head: Range<usize>,
+    /// Number of calls enclosing this one.
+    pub depth: usize,
+}
+
+impl TemplateCall {
+    /// Get the value of an argument by name or position, e.g. "1" for the
+    /// first positional argument. If an argument is given more than once, the
+    /// last value is used, as in MediaWiki.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.args
+            .iter()
+            .rev()
+            .find(|arg| arg.name == name)
+            .map(|arg| arg.value.as_str())
+    }
+
+    /// Title of the transcluded page without its namespace, e.g. `Infobox
+    /// person`.
+    pub fn template_name(&self) -> &str {
+        match self.kind {
+            CallKind::Template if self.ns != MAIN => match self.name.find(':') {
+                Some(index) => &self.name[index + 1..],
+                None => &self.name,
+            },
+            _ => &self.name,
+        }
+    }
+}
+
+/// An open run of braces or brackets while parsing.
+#[derive(Debug)]
+struct Frame {
+    brace: bool,
+    /// Offset of the first brace or bracket of the run.
+    start: usize,
+    /// Number of braces not yet matched.
+    count: usize,
+    /// Offsets of the pipes separating arguments.
+    pipes: Vec<usize>,
+    /// Offsets of equals signs which may separate argument names and values.
+    equals: Vec<usize>,
+}
+
+/// Parser for the template calls in a page.
+///
+/// Braces are matched as by the MediaWiki preprocessor, so that nested calls,
+/// parameters and runs such as `{{{{{1}}}}}` are found; pipes within links and
+/// nested calls do not separate arguments, and comments and `<nowiki>`
+/// sections are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct TemplateParser {
+    titles: TitleNormaliser,
+}
+
+impl TemplateParser {
+    /// Create a template parser, normalising the titles of templates called
+    /// with the given normaliser.
+    pub fn new(titles: TitleNormaliser) -> Self {
+        TemplateParser { titles }
+    }
+
+    /// Find the template calls in a page, ordered by their start offset.
+    ///
+    /// Calls nested in the arguments of others are included, with a `depth`
+    /// greater than zero.
+    pub fn parse(&self, text: &str) -> Vec<TemplateCall> {
+        let bytes = text.as_bytes();
+        let mut calls = vec![];
+        let mut stack: Vec<Frame> = vec![];
+        let mut index = 0;
+        while index < bytes.len() {
+            let c = bytes[index];
+            match c {
+                b'<' => {
+                    if let Some(end) = skip_verbatim(text, index) {
+                        index = end;
+                        continue;
+                    }
+                }
+                b'{' | b'[' => {
+                    let count = run_length(bytes, index);
+                    if count >= 2 {
+                        stack.push(Frame {
+                            brace: c == b'{',
+                            start: index,
+                            count,
+                            pipes: vec![],
+                            equals: vec![],
+                        });
+                    }
+                    index += count;
+                    continue;
+                }
+                b'}' => {
+                    let mut count = run_length(bytes, index);
+                    // Links left open within a call are literal text.
+                    if count >= 2 {
+                        if let Some(top) = stack.iter().rposition(|frame| frame.brace) {
+                            stack.truncate(top + 1);
+                        }
+                    }
+                    while count >= 2 && stack.last().map_or(false, |frame| frame.brace) {
+                        let mut frame = stack.pop().unwrap();
+                        let matched = if frame.count >= 3 && count >= 3 { 3 } else { 2 };
+                        frame.count -= matched;
+                        // The innermost braces of the run open the call.
+                        let element = Frame {
+                            brace: true,
+                            start: frame.start + frame.count,
+                            count: matched,
+                            pipes: std::mem::replace(&mut frame.pipes, vec![]),
+                            equals: std::mem::replace(&mut frame.equals, vec![]),
+                        };
+                        if frame.count >= 2 {
+                            stack.push(frame);
+                        }
+                        let depth = stack.iter().filter(|frame| frame.brace).count();
+                        calls.push(self.call(text, &element, index + matched, depth));
+                        index += matched;
+                        count -= matched;
+                    }
+                    index += count;
+                    continue;
+                }
+                b']' => {
+                    let count = run_length(bytes, index);
+                    if count >= 2 && stack.last().map_or(false, |frame| !frame.brace) {
+                        stack.pop();
+                        index += 2;
+                    } else {
+                        index += count;
+                    }
+                    continue;
+                }
+                b'|' | b'=' => {
+                    if let Some(frame) = stack.last_mut() {
+                        if frame.brace && c == b'|' {
+                            frame.pipes.push(index);
+                        } else if frame.brace {
+                            frame.equals.push(index);
+                        }
+                    }
+                }
+                _ => (),
+            }
+            index += 1;
+        }
+        calls.sort_by_key(|call| call.span.start);
+        calls
+    }
+
+    /// Build the call opened by `element` and closed at `end`.
+    fn call(&self, text: &str, element: &Frame, end: usize, depth: usize) -> TemplateCall {
+        let span = element.start..end;
+        let content = element.start + element.count..end - element.count;
+        let (pipes, equals) = (&element.pipes, &element.equals);
+        // Parts between pipes; the first is the name.
+        let mut parts = Vec::with_capacity(pipes.len() + 1);
+        let mut part_start = content.start;
+        for pipe in pipes.iter().chain(Some(&content.end)) {
+            parts.push(part_start..*pipe);
+            part_start = pipe + 1;
+        }
+        let head = parts.remove(0);
+        let head_text = &text[head.clone()];
+
+        if element.count == 3 {
+            let args = parts
+                .into_iter()
+                .take(1)
+                .map(|part| positional(text, part, 1))
+                .collect();
+            return TemplateCall {
+                kind: CallKind::Parameter,
+                ns: MAIN,
+                name: head_text.trim().to_owned(),
+                args,
+                span,
+                head,
+                depth,
+            };
+        }
+
+        let mut name_offset = head.start + (head_text.len() - head_text.trim_start().len());
+        let mut name = head_text.trim();
+        for modifier in MODIFIERS {
+            if name.len() > modifier.len()
+                && name.as_bytes()[..modifier.len()].eq_ignore_ascii_case(modifier.as_bytes())
+            {
+                let rest = &name[modifier.len()..];
+                name_offset += modifier.len() + (rest.len() - rest.trim_start().len());
+                name = rest.trim_start();
+            }
+        }
+
+        let (kind, ns, name, first) = match self.function(name) {
+            Some((function, colon)) => {
+                let first = name_offset + colon + 1..head.end;
+                (CallKind::ParserFunction, MAIN, function, Some(first))
+            }
+            None if parts.is_empty() && VARIABLES.contains(&name) => {
+                (CallKind::Variable, MAIN, name.to_owned(), None)
+            }
+            None => {
+                let (ns, title) = self.template_title(name);
+                (CallKind::Template, ns, title, None)
+            }
+        };
+
+        // Arguments of parser functions are positional, but for `#invoke` and
+        // the cases of `#switch`.
+        let named = kind == CallKind::Template || name == "#invoke" || name == "#switch";
+        let mut args = vec![];
+        let mut position = 0;
+        if let Some(first) = first {
+            position += 1;
+            args.push(positional(text, first, position));
+        }
+        for part in parts {
+            let equals = equals
+                .iter()
+                .find(|offset| part.start <= **offset && **offset < part.end);
+            match equals {
+                Some(equals) if named => {
+                    let key = text[part.start..*equals].trim();
+                    let value = trimmed(text, equals + 1..part.end);
+                    args.push(Arg {
+                        name: key.to_owned(),
+                        positional: false,
+                        value: text[value.clone()].to_owned(),
+                        span: value,
+                    });
+                }
+                _ => {
+                    position += 1;
+                    args.push(positional(text, part, position));
+                }
+            }
+        }
+        TemplateCall {
+            kind,
+            ns,
+            name,
+            args,
+            span,
+            head,
+            depth,
+        }
+    }
+
+    /// If `name` calls a parser function, return the function's name and the
+    /// offset of the colon after it.
+    fn function(&self, name: &str) -> Option<(String, usize)> {
+        let colon = name.find(':')?;
+        let prefix = name[..colon].trim();
+        if prefix.starts_with('#') {
+            return Some((prefix.to_lowercase(), colon));
+        }
+        if let Some(function) = FUNCTIONS
+            .iter()
+            .find(|function| function.eq_ignore_ascii_case(prefix))
+        {
+            return Some((function.to_string(), colon));
+        }
+        // Variables may take an argument, e.g. `{{PAGENAME:Orpheus}}`.
+        if VARIABLES.contains(&prefix) {
+            return Some((prefix.to_owned(), colon));
+        }
+        None
+    }
+
+    /// Namespace and canonical title of the page transcluded by a call. Names
+    /// without a namespace prefix are in the template namespace, except those
+    /// with a leading colon, which are articles. Names which are not valid
+    /// titles, such as those built from parameters, are kept as written.
+    pub fn template_title(&self, name: &str) -> (i32, String) {
+        let title = if name.starts_with(':') {
+            self.titles.parse(name)
+        } else {
+            match self.titles.parse(name) {
+                Some(ref title) if title.ns == MAIN => {
+                    let prefix = match self.titles.namespaces().get(TEMPLATE) {
+                        Some(namespace) => namespace.name.clone(),
+                        None => "Template".to_owned(),
+                    };
+                    self.titles.parse(&format!("{}:{}", prefix, name))
+                }
+                title => title,
+            }
+        };
+        match title {
+            Some(title) => (title.ns, title.into_string()),
+            None => (TEMPLATE, name.to_owned()),
+        }
+    }
+}
+
+/// Count the repeats of the byte at `index`.
+fn run_length(bytes: &[u8], index: usize) -> usize {
+    bytes[index..]
+        .iter()
+        .take_while(|b| **b == bytes[index])
+        .count()
+}
+
+/// Trim whitespace from the text within `range`, returning the trimmed range.
+fn trimmed(text: &str, range: Range<usize>) -> Range<usize> {
+    let slice = &text[range.clone()];
+    let start = range.start + (slice.len() - slice.trim_start().len());
+    let end = range.end - (slice.len() - slice.trim_end().len());
+    start..end.max(start)
+}
+
+/// A positional argument spanning `range`.
+fn positional(text: &str, range: Range<usize>, position: usize) -> Arg {
+    Arg {
+        name: position.to_string(),
+        positional: true,
+        value: text[range.clone()].to_owned(),
+        span: range,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_templates() {
+        let parser = TemplateParser::default();
+        let text = "{{Infobox musician | name = Orpheus <!-- | hidden = x --> \
+                    | image = [[File:Lyre.jpg|thumb]] | spouse = {{marriage|Eurydice|}}}}\
+                    {{#if: {{{1|}}} | yes | no }} {{PAGENAME}} {{lc:LYRE}} \
+                    {{subst:cite|a|b=c|d}} {{:Orpheus}} <nowiki>{{Escaped}}</nowiki>\
+                    {{{{{name}}}|x}}";
+        let calls = parser.parse(text);
+        let summary = calls
+            .iter()
+            .map(|call| (call.kind, call.name.as_str(), call.depth))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (CallKind::Template, "Template:Infobox musician", 0),
+                (CallKind::Template, "Template:Marriage", 1),
+                (CallKind::ParserFunction, "#if", 0),
+                (CallKind::Parameter, "1", 1),
+                (CallKind::Variable, "PAGENAME", 0),
+                (CallKind::ParserFunction, "lc", 0),
+                (CallKind::Template, "Template:Cite", 0),
+                (CallKind::Template, "Orpheus", 0),
+                (CallKind::Template, "{{{name}}}", 0),
+                (CallKind::Parameter, "name", 1),
+            ]
+        );
+
+        let infobox = &calls[0];
+        assert_eq!(infobox.template_name(), "Infobox musician");
+        assert_eq!(infobox.args.len(), 3);
+        assert_eq!(infobox.get("name"), Some("Orpheus <!-- | hidden = x -->"));
+        assert_eq!(infobox.get("image"), Some("[[File:Lyre.jpg|thumb]]"));
+        assert_eq!(infobox.get("spouse"), Some("{{marriage|Eurydice|}}"));
+        let spouse = &infobox.args[2];
+        assert_eq!(&text[spouse.span.clone()], "{{marriage|Eurydice|}}");
+        assert_eq!(&text[calls[1].span.clone()], "{{marriage|Eurydice|}}");
+        assert_eq!(calls[1].get("1"), Some("Eurydice"));
+        assert_eq!(calls[1].get("2"), Some(""));
+
+        let function = &calls[2];
+        assert_eq!(&text[function.head.clone()], "#if: {{{1|}}} ");
+        assert_eq!(function.get("1"), Some(" {{{1|}}} "));
+        assert_eq!(function.get("3"), Some(" no "));
+        assert_eq!(calls[3].get("1"), Some(""));
+        assert_eq!(calls[5].get("1"), Some("LYRE"));
+
+        let cite = &calls[6];
+        assert_eq!(cite.get("1"), Some("a"));
+        assert_eq!(cite.get("b"), Some("c"));
+        assert_eq!(cite.get("2"), Some("d"));
+        assert_eq!(calls[7].ns, MAIN);
+        assert_eq!(&text[calls[9].span.clone()], "{{{name}}}");
+    }
+}
@@ -0,0 +1,324 @@
+use lazy_static::lazy_static;
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::page::namespace::{Namespaces, SiteInfo, MAIN};
+
+lazy_static! {
+    /// Normaliser for English Wikipedia titles, used when the dump's siteinfo
+    /// is not at hand.
+    pub static ref DEFAULT_TITLES: TitleNormaliser = TitleNormaliser::default();
+}
+
+/// Named character references decoded in titles, beyond the numeric forms.
+static ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{a0}'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("hellip", '…'),
+    ("middot", '·'),
+    ("times", '×'),
+    ("eacute", 'é'),
+    ("egrave", 'è'),
+    ("aacute", 'á'),
+    ("oacute", 'ó'),
+    ("uuml", 'ü'),
+    ("ouml", 'ö'),
+    ("auml", 'ä'),
+    ("szlig", 'ß'),
+];
+
+/// Characters which may not appear in a title.
+static ILLEGAL: &[char] = &['[', ']', '{', '}', '|', '<', '>', '#'];
+
+/// Capitalisation rule of a wiki, from the `<case>` of its siteinfo.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Case {
+    /// The first letter of each title is capitalised, as on Wikipedia.
+    FirstLetter,
+    /// Titles are used as written.
+    Sensitive,
+}
+
+impl Case {
+    /// Parse a `<case>` value, defaulting to `FirstLetter`.
+    pub fn parse(case: &str) -> Self {
+        match case {
+            "case-sensitive" => Case::Sensitive,
+            _ => Case::FirstLetter,
+        }
+    }
+}
+
+impl Default for Case {
+    fn default() -> Self {
+        Case::FirstLetter
+    }
+}
+
+/// A page title in the canonical form used by MediaWiki, e.g. `Category:Greek
+/// gods` for `[[category:greek_gods]]`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Title {
+    pub ns: i32,
+    /// Full title, including any namespace prefix.
+    text: String,
+    /// Byte offset of the title within its namespace.
+    name_start: usize,
+}
+
+impl Title {
+    /// Full title, including any namespace prefix.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Title without its namespace prefix.
+    pub fn name(&self) -> &str {
+        &self.text[self.name_start..]
+    }
+
+    pub fn into_string(self) -> String {
+        self.text
+    }
+}
+
+impl fmt::Display for Title {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Canonicalises titles for a wiki, given its namespaces and capitalisation.
+#[derive(Clone, Debug)]
+pub struct TitleNormaliser {
+    namespaces: Namespaces,
+    case: Case,
+}
+
+impl Default for TitleNormaliser {
+    fn default() -> Self {
+        TitleNormaliser::new(Namespaces::english(), Case::FirstLetter)
+    }
+}
+
+impl TitleNormaliser {
+    pub fn new(namespaces: Namespaces, case: Case) -> Self {
+        TitleNormaliser { namespaces, case }
+    }
+
+    /// Create a normaliser for the wiki described by a dump's siteinfo.
+    pub fn from_site_info(info: &SiteInfo) -> Self {
+        TitleNormaliser::new(info.namespaces.clone(), Case::parse(&info.case))
+    }
+
+    pub fn namespaces(&self) -> &Namespaces {
+        &self.namespaces
+    }
+
+    /// Parse a title as written in a link or redirect, returning `None` if it
+    /// is empty or contains characters not allowed in titles.
+    ///
+    /// Percent-encoding and character references are decoded, underscores and
+    /// runs of whitespace become single spaces, any section fragment is
+    /// dropped, the namespace prefix is replaced by the namespace's name and
+    /// the first letter is capitalised if the wiki's case rule requires it.
+    pub fn parse(&self, raw: &str) -> Option<Title> {
+        let decoded = decode_entities(&decode_percent(raw)).into_owned();
+        let decoded = match decoded.find('#') {
+            Some(index) => &decoded[..index],
+            None => &decoded[..],
+        };
+        let text = collapse_whitespace(decoded);
+        let text = text.trim_start_matches(':').trim_start();
+
+        let (ns, name) = match text.find(':') {
+            Some(index) => match self.namespaces.key_for_name(&text[..index]) {
+                Some(ns) if ns != MAIN => (ns, text[index + 1..].trim_start()),
+                _ => (MAIN, text),
+            },
+            None => (MAIN, text),
+        };
+        if name.is_empty() || name.contains(ILLEGAL) {
+            return None;
+        }
+
+        let mut title = match self.namespaces.get(ns) {
+            Some(namespace) if !namespace.name.is_empty() => format!("{}:", namespace.name),
+            _ => String::new(),
+        };
+        let name_start = title.len();
+        match self.case {
+            Case::FirstLetter => {
+                let mut chars = name.chars();
+                if let Some(first) = chars.next() {
+                    title.extend(first.to_uppercase());
+                    title.push_str(chars.as_str());
+                }
+            }
+            Case::Sensitive => title.push_str(name),
+        }
+        Some(Title {
+            ns,
+            text: title,
+            name_start,
+        })
+    }
+
+    /// Normalise a title, returning it unchanged if it cannot be parsed.
+    pub fn normalise(&self, raw: &str) -> String {
+        match self.parse(raw) {
+            Some(title) => title.into_string(),
+            None => raw.to_owned(),
+        }
+    }
+}
+
+/// Decode `%XX` escapes, keeping the text as written if they do not decode to
+/// UTF-8.
+fn decode_percent(raw: &str) -> Cow<'_, str> {
+    if !raw.contains('%') {
+        return Cow::Borrowed(raw);
+    }
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%'
+            && index + 2 < bytes.len()
+            && bytes[index + 1..index + 3]
+                .iter()
+                .all(u8::is_ascii_hexdigit)
+        {
+            let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap();
+            decoded.push(u8::from_str_radix(hex, 16).unwrap());
+            index += 3;
+            continue;
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    match String::from_utf8(decoded) {
+        Ok(decoded) => Cow::Owned(decoded),
+        Err(_) => Cow::Borrowed(raw),
+    }
+}
+
+/// Decode a character reference, given the text between '&' and ';'.
+pub(crate) fn decode_entity(entity: &str) -> Option<char> {
+    if entity.starts_with("#x") || entity.starts_with("#X") {
+        u32::from_str_radix(&entity[2..], 16)
+            .ok()
+            .and_then(std::char::from_u32)
+    } else if entity.starts_with('#') {
+        entity[1..]
+            .parse::<u32>()
+            .ok()
+            .and_then(std::char::from_u32)
+    } else {
+        ENTITIES
+            .iter()
+            .find(|(name, _)| *name == entity)
+            .map(|(_, c)| *c)
+    }
+}
+
+/// Decode character references such as `&amp;`, `&#39;` and `&#x27;`.
+fn decode_entities(raw: &str) -> Cow<'_, str> {
+    if !raw.contains('&') {
+        return Cow::Borrowed(raw);
+    }
+    let mut decoded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| decode_entity(&rest[1..1 + end]).map(|c| (c, end)));
+        match entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 2..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+/// Replace underscores and runs of whitespace with single spaces, dropping
+/// directional marks and surrounding whitespace.
+fn collapse_whitespace(raw: &str) -> String {
+    let mut collapsed = String::with_capacity(raw.len());
+    let mut space = false;
+    for c in raw.chars() {
+        match c {
+            '\u{200e}' | '\u{200f}' => (),
+            c if c == '_' || c.is_whitespace() => space = true,
+            c => {
+                if space && !collapsed.is_empty() {
+                    collapsed.push(' ');
+                }
+                space = false;
+                collapsed.push(c);
+            }
+        }
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalise() {
+        let titles = TitleNormaliser::default();
+        for raw in &[
+            "united_States",
+            "United_States",
+            " United  States ",
+            "United%20States",
+            "United&nbsp;States",
+            "United States#History",
+        ] {
+            assert_eq!(titles.normalise(raw), "United States");
+        }
+        assert_eq!(titles.normalise("AT&amp;T"), "AT&T");
+        assert_eq!(titles.normalise("Caf%C3%A9"), "Café");
+        assert_eq!(titles.normalise("100%"), "100%");
+        assert_eq!(titles.normalise("éclair"), "Éclair");
+
+        let title = titles.parse("category:greek_gods").unwrap();
+        assert_eq!(title.as_str(), "Category:Greek gods");
+        assert_eq!(title.name(), "Greek gods");
+        assert_eq!(title.ns, crate::page::namespace::CATEGORY);
+        assert_eq!(titles.normalise("Image:Lyre.jpg"), "File:Lyre.jpg");
+        assert_eq!(titles.normalise("project : sandbox"), "Wikipedia:Sandbox");
+        assert_eq!(
+            titles.normalise("Star Wars: Episode I"),
+            "Star Wars: Episode I"
+        );
+        assert!(titles.parse("").is_none());
+        assert!(titles.parse("Category:").is_none());
+        assert!(titles.parse("a|b").is_none());
+
+        let titles = TitleNormaliser::new(Namespaces::english(), Case::Sensitive);
+        assert_eq!(titles.normalise("iPod_touch"), "iPod touch");
+    }
+}
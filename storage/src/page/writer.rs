@@ -1,4 +1,5 @@
 use crate::page::{Anchor, Page};
+use serde_json::{json, Value};
 use std::io::{self, Write};
 
 pub trait PageWriter {
@@ -15,7 +16,7 @@ impl PageWriter for CategoryWriterTSV {
         let categories = page
             .categories
             .into_iter()
-            .map(|cat| cat.0)
+            .map(|cat| cat.name)
             .map(|cat| format!(r#""{}""#, cat.replace("\"", "\\\"")))
             .collect::<Vec<_>>()
             .join(",");
@@ -23,20 +24,23 @@ impl PageWriter for CategoryWriterTSV {
     }
 }
 
+/// Build a JSON record of a page's title, IDs, namespace and revision, with
+/// the given list of items.
+fn page_record(page: Page, key: &str, items: Vec<String>) -> Value {
+    let mut record = json!({
+        "title": page.title,
+        "id": page.id.parse::<u64>().ok(),
+        "ns": page.ns,
+        "revision": page.revision,
+    });
+    record[key] = json!(items);
+    record
+}
+
 impl PageWriter for CategoryWriterJSONL {
     fn write<W: Write>(page: Page, writer: &mut W) -> io::Result<()> {
-        let categories = page
-            .categories
-            .into_iter()
-            .map(|cat| cat.0)
-            .map(|anchor| format!(r#""{}""#, anchor.replace("\"", "\\\"")))
-            .collect::<Vec<_>>()
-            .join(", ");
-        writeln!(
-            writer,
-            "{{ \"title\": \"{}\", \"id\": {}, \"categories\": [{}] }},",
-            page.title, page.id, categories
-        )
+        let categories = page.categories.iter().map(|cat| cat.name.clone()).collect();
+        writeln!(writer, "{}", page_record(page, "categories", categories))
     }
 }
 
@@ -64,18 +68,12 @@ impl PageWriter for AnchorWriterJSONL {
     fn write<W: Write>(page: Page, writer: &mut W) -> io::Result<()> {
         let anchors = page
             .anchors
-            .into_iter()
+            .iter()
             .map(|anchor| match anchor {
-                Anchor::Direct(name) => name,
-                Anchor::Label { page, .. } => page,
+                Anchor::Direct(name) => name.clone(),
+                Anchor::Label { page, .. } => page.clone(),
             })
-            .map(|anchor| format!(r#""{}""#, anchor.replace("\"", "\\\"")))
-            .collect::<Vec<_>>()
-            .join(", ");
-        writeln!(
-            writer,
-            "{{ \"title\": \"{}\", \"id\": {}, \"anchors\": [{}] }},",
-            page.title, page.id, anchors
-        )
+            .collect();
+        writeln!(writer, "{}", page_record(page, "anchors", anchors))
     }
 }
@@ -0,0 +1,184 @@
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::tantivy::TantivyWikiIndex;
+
+/// Calculate Milne-Witten relatedness from link counts.
+///
+/// This is one minus the normalised Google distance between the sets of
+/// pages linking to two entities, clamped to [0, 1]:
+///
+///     1 - (log(max(|A|, |B|)) - log(|A ∩ B|)) / (log(|W|) - log(min(|A|, |B|)))
+///
+/// # Arguments
+/// * `inlinks_a` - Number of pages linking to the first entity.
+/// * `inlinks_b` - Number of pages linking to the second entity.
+/// * `common` - Number of pages linking to both entities.
+/// * `total` - Number of pages in the collection.
+pub fn milne_witten(inlinks_a: u64, inlinks_b: u64, common: u64, total: u64) -> f32 {
+    if inlinks_a == 0 || inlinks_b == 0 || common == 0 {
+        return 0.0;
+    }
+    let (max, min) = if inlinks_a > inlinks_b {
+        (inlinks_a as f64, inlinks_b as f64)
+    } else {
+        (inlinks_b as f64, inlinks_a as f64)
+    };
+    let total = (total as f64).max(max);
+    if total <= min {
+        return 1.0;
+    }
+    let distance = (max.ln() - (common as f64).ln()) / (total.ln() - min.ln());
+    (1.0 - distance).max(0.0).min(1.0) as f32
+}
+
+/// Symmetric matrix of relatedness scores between a set of entities.
+#[derive(Clone, Debug)]
+pub struct RelatednessMatrix {
+    entities: Vec<String>,
+    positions: HashMap<String, usize>,
+    scores: Vec<f32>,
+}
+
+impl RelatednessMatrix {
+    /// Create a matrix from the scores of pairs of entities, given by their
+    /// positions. Each entity is fully related to itself, and pairs without a
+    /// score are unrelated.
+    pub fn from_pairs<I>(entities: &[&str], pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize, f32)>,
+    {
+        let n = entities.len();
+        let mut scores = vec![0.0; n * n];
+        for i in 0..n {
+            scores[i * n + i] = 1.0;
+        }
+        for (i, j, score) in pairs {
+            scores[i * n + j] = score;
+            scores[j * n + i] = score;
+        }
+        RelatednessMatrix {
+            entities: entities.iter().map(|e| e.to_string()).collect(),
+            positions: entities
+                .iter()
+                .enumerate()
+                .map(|(i, e)| (e.to_string(), i))
+                .collect(),
+            scores,
+        }
+    }
+
+    /// Entities in the matrix, in the order given.
+    pub fn entities(&self) -> &[String] {
+        &self.entities
+    }
+
+    /// Get the relatedness between the entities at two positions.
+    pub fn get(&self, i: usize, j: usize) -> f32 {
+        self.scores[i * self.entities.len() + j]
+    }
+
+    /// Get the relatedness between two entities, if both are in the matrix.
+    pub fn get_by_title(&self, a: &str, b: &str) -> Option<f32> {
+        match (self.positions.get(a), self.positions.get(b)) {
+            (Some(i), Some(j)) => Some(self.get(*i, *j)),
+            _ => None,
+        }
+    }
+}
+
+/// Milne-Witten relatedness over the links in a search index.
+///
+/// In-link counts are cached, so repeated queries over the same entities only
+/// require a conjunctive query for the shared in-links.
+pub struct Relatedness<'a> {
+    index: &'a TantivyWikiIndex,
+    total: u64,
+    inlinks: RwLock<HashMap<String, u64>>,
+}
+
+impl<'a> Relatedness<'a> {
+    /// Create a new relatedness measure over a search index.
+    pub fn new(index: &'a TantivyWikiIndex) -> Self {
+        Relatedness {
+            index,
+            total: index.len() as u64,
+            inlinks: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Number of pages in the index.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Get the number of pages linking to an entity.
+    pub fn inlinks(&self, entity: &str) -> u64 {
+        if let Some(count) = self.inlinks.read().unwrap().get(entity) {
+            return *count;
+        }
+        let count = self.index.count_inlinks(entity);
+        self.inlinks
+            .write()
+            .unwrap()
+            .insert(entity.to_owned(), count);
+        count
+    }
+
+    /// Get the number of pages linking to both entities.
+    pub fn common_inlinks(&self, a: &str, b: &str) -> u64 {
+        self.index.count_mutual_outlinks(&[a, b]) as u64
+    }
+
+    /// Calculate the relatedness between two entities.
+    pub fn milne_witten(&self, a: &str, b: &str) -> f32 {
+        if a == b {
+            return 1.0;
+        }
+        let (inlinks_a, inlinks_b) = (self.inlinks(a), self.inlinks(b));
+        if inlinks_a == 0 || inlinks_b == 0 {
+            return 0.0;
+        }
+        milne_witten(inlinks_a, inlinks_b, self.common_inlinks(a, b), self.total)
+    }
+
+    /// Calculate the relatedness between every pair of entities.
+    ///
+    /// Pairs are scored in parallel; each unordered pair is queried once.
+    pub fn pairwise(&self, entities: &[&str]) -> RelatednessMatrix {
+        let n = entities.len();
+        let pairs = (0..n)
+            .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+            .collect::<Vec<_>>();
+        let scored = pairs
+            .into_par_iter()
+            .map(|(i, j)| (i, j, self.milne_witten(entities[i], entities[j])))
+            .collect::<Vec<_>>();
+        RelatednessMatrix::from_pairs(entities, scored)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_milne_witten_disjoint() {
+        assert_eq!(milne_witten(10, 20, 0, 1000), 0.0);
+        assert_eq!(milne_witten(0, 20, 0, 1000), 0.0);
+    }
+
+    #[test]
+    fn test_milne_witten_identical_links() {
+        assert_eq!(milne_witten(50, 50, 50, 1000), 1.0);
+    }
+
+    #[test]
+    fn test_milne_witten_symmetric() {
+        let ab = milne_witten(30, 120, 12, 100_000);
+        let ba = milne_witten(120, 30, 12, 100_000);
+        assert_eq!(ab, ba);
+        assert!(ab > 0.0 && ab < 1.0);
+    }
+}
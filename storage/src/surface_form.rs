@@ -1,6 +1,45 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Source of an entity match for a surface form.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum Source {
+    /// Anchor text of a link to the entity.
+    Anchor,
+    /// Title of the entity's page.
+    Title,
+    /// Title of the entity's page with disambiguation qualifiers removed.
+    TitleNv,
+    /// Title of a page redirecting to the entity.
+    Redirect,
+}
+
+impl Source {
+    /// Sources derived from page titles rather than links.
+    pub fn title_sources() -> &'static [Source] {
+        &[Source::Title, Source::TitleNv, Source::Redirect]
+    }
+
+    /// Bit flag for the source, as stored in the titles FST.
+    pub fn flag(self) -> u64 {
+        match self {
+            Source::Anchor => 1,
+            Source::Title => 2,
+            Source::TitleNv => 4,
+            Source::Redirect => 8,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Anchor => "anchor",
+            Source::Title => "title",
+            Source::TitleNv => "title-nv",
+            Source::Redirect => "redirect",
+        }
+    }
+}
+
 /// Record for an individual surface form and associated anchor counts.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct SurfaceForm {
@@ -8,6 +47,9 @@ pub struct SurfaceForm {
     pub text: String,
     /// Map of page titles to counts.
     pub anchors: HashMap<String, f32>,
+    /// Map of page titles to counts for each title-derived source.
+    #[serde(default)]
+    pub matches: HashMap<Source, HashMap<String, f32>>,
     wiki_occurrences: f32,
 }
 
@@ -19,6 +61,7 @@ impl SurfaceForm {
         SurfaceForm {
             text: query.to_string(),
             anchors,
+            matches: HashMap::new(),
             wiki_occurrences,
         }
     }
@@ -34,6 +77,24 @@ impl SurfaceForm {
             .collect()
     }
 
+    /// Add title-derived matches from tab-separated FST search results whose
+    /// values are `Source` flags.
+    pub fn add_flagged_matches(&mut self, stream: Vec<(String, u64)>) {
+        for (pair, flags) in stream {
+            let entity = &pair[pair.find('\t').unwrap() + 1..];
+            for source in Source::title_sources() {
+                if flags & source.flag() != 0 {
+                    *self
+                        .matches
+                        .entry(*source)
+                        .or_default()
+                        .entry(entity.to_owned())
+                        .or_insert(0.0) += 1.0;
+                }
+            }
+        }
+    }
+
     pub fn wiki_occurrences(&self) -> f32 {
         self.wiki_occurrences
     }
@@ -44,17 +105,28 @@ impl SurfaceForm {
     }
 
     /// Fetch entity matches above the given commonness threshold.
+    ///
+    /// Commonness is taken over the counts of all sources combined, with each
+    /// title, title-nv or redirect match counted as one link, so that an
+    /// entity matched only by its title is as rare as a single link to it.
     pub fn get_wiki_matches(&self, commonness_threshold: f32) -> HashMap<String, f32> {
-        // calculate commonness for each entity and filter the ones below the commonness threshold.
-        let wiki_matches: HashMap<String, f32> = self
+        let mut counts: HashMap<&str, f32> = self
             .anchors
             .iter()
-            .map(|(key, count)| (key, (*count as f32) / self.wiki_occurrences))
-            .filter(|(_key, count)| *count >= commonness_threshold)
-            .map(|(key, count)| (key.clone(), count))
+            .map(|(entity, count)| (entity.as_str(), *count))
             .collect();
-        // TODO: calculate commonness for title, title-nv, redirect
-        wiki_matches
+        for matches in self.matches.values() {
+            for (entity, count) in matches {
+                *counts.entry(entity.as_str()).or_insert(0.0) += count;
+            }
+        }
+        let total: f32 = counts.values().sum();
+        counts
+            .into_iter()
+            .map(|(entity, count)| (entity, count / total))
+            .filter(|(_, commonness)| *commonness >= commonness_threshold)
+            .map(|(entity, commonness)| (entity.to_owned(), commonness))
+            .collect()
     }
 
     /// Calculate commonness for an entity.
@@ -70,7 +142,75 @@ impl SurfaceForm {
             .unwrap_or(0.0)
     }
 
+    /// Calculate commonness for an entity within a single source.
+    ///
+    /// Returns zero if the entity is not matched by the source.
+    pub fn source_commonness(&self, source: Source, en_uri: &str) -> f32 {
+        if source == Source::Anchor {
+            return self.calculate_commonness(en_uri);
+        }
+        self.matches
+            .get(&source)
+            .and_then(|matches| {
+                let total: f32 = matches.values().sum();
+                matches.get(en_uri).map(|count| count / total)
+            })
+            .unwrap_or(0.0)
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.anchors.is_empty()
+        self.anchors.is_empty() && self.matches.values().all(|matches| matches.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_combined_matches() {
+        let mut sf = SurfaceForm::from_paired_matches(
+            "mercury",
+            vec![
+                ("mercury\tMercury (planet)".to_owned(), 98),
+                ("mercury\tMercury (element)".to_owned(), 2),
+            ],
+        );
+        sf.add_flagged_matches(vec![
+            (
+                "mercury\tMercury (element)".to_owned(),
+                Source::TitleNv.flag(),
+            ),
+            (
+                "mercury\tMercury (mythology)".to_owned(),
+                Source::TitleNv.flag(),
+            ),
+            (
+                "mercury\tMercury (planet)".to_owned(),
+                Source::TitleNv.flag(),
+            ),
+            (
+                "mercury\tMercury".to_owned(),
+                Source::Title.flag() | Source::Redirect.flag(),
+            ),
+        ]);
+
+        assert_eq!(
+            sf.source_commonness(Source::TitleNv, "Mercury (mythology)"),
+            1.0 / 3.0
+        );
+        assert_eq!(sf.source_commonness(Source::Redirect, "Mercury"), 1.0);
+
+        // 100 links, 3 title-nv matches, and a title and redirect match.
+        let matches = sf.get_all_wiki_matches();
+        assert_eq!(matches.len(), 4);
+        assert_eq!(matches["Mercury (planet)"], 99.0 / 105.0);
+        assert_eq!(matches["Mercury (element)"], 3.0 / 105.0);
+        assert_eq!(matches["Mercury"], 2.0 / 105.0);
+
+        let matches = sf.get_wiki_matches(0.02);
+        let mut entities = matches.keys().collect::<Vec<_>>();
+        entities.sort();
+        assert_eq!(entities, vec!["Mercury (element)", "Mercury (planet)"]);
     }
 }
@@ -1,6 +1,8 @@
+use log::info;
 use rayon::prelude::*;
+use std::collections::BTreeSet;
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use tantivy::{
     collector::Count,
     directory::MmapDirectory,
@@ -9,7 +11,9 @@ use tantivy::{
     Index, IndexReader, IndexWriter, Term,
 };
 
-use crate::page::{anchor::Anchor, PageIterator, TantivyPageIterator};
+use crate::page::{
+    LinkParser, PageFilter, PageIterator, PlainText, TantivyPageIterator, TitleNormaliser,
+};
 use crate::utils::open_seek_bzip;
 
 /// Use tantivy to index content from a bzip2 multistream.
@@ -21,6 +25,8 @@ pub fn index_anchors(
     data_path: &Path,
     indexer: &Mutex<IndexWriter>,
     schema: &Schema,
+    filter: &Arc<PageFilter>,
+    links: &LinkParser,
 ) -> Result<(), Box<std::error::Error>> {
     let pbar = Mutex::new(pbr::ProgressBar::new(indices.len() as u64));
     indices.sort();
@@ -32,27 +38,27 @@ pub fn index_anchors(
         schema.get_field("outlinks").unwrap(),
     );
 
+    let plaintext = PlainText::new(links);
     indices
         .into_par_iter()
         .map(|index| {
             let store = open_seek_bzip(data_path, *index).unwrap();
-            TantivyPageIterator(PageIterator::new(store))
+            TantivyPageIterator(PageIterator::new(store).with_filter(filter.clone()))
                 .map(|(page_id, page_title, page_content)| {
                     let mut doc = Document::default();
                     doc.add_u64(id, page_id.parse::<u64>().unwrap());
                     doc.add_text(title, &page_title);
-                    doc.add_text(content, &page_content);
-                    let outlinks_content = page_content
-                        .match_indices("[[")
-                        .filter_map(|(begin, _)| Anchor::pare_anchor_match(&page_content, begin))
-                        .map(Anchor::parse)
-                        .map(|anchor| match anchor {
-                            Anchor::Direct(name) => name.replace(" ", "_"),
-                            Anchor::Label { page, .. } => page.replace(" ", "_"),
-                        })
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    doc.add_text(outlinks, &outlinks_content);
+                    doc.add_text(content, &plaintext.render(&page_content));
+                    // Each linked page is added once, however often it is
+                    // linked, so that term frequencies do not count links.
+                    links
+                        .parse(&page_content)
+                        .iter()
+                        .filter(|link| link.is_article())
+                        .map(|link| TantivyWikiIndex::outlink_term(links.titles(), &link.target))
+                        .collect::<BTreeSet<_>>()
+                        .iter()
+                        .for_each(|link| doc.add_text(outlinks, link));
                     doc
                 })
                 .collect::<Vec<_>>()
@@ -72,6 +78,73 @@ pub fn index_anchors(
     Ok(())
 }
 
+/// Open the search index in a directory, creating it if it does not exist.
+pub fn open_or_create_index<P: AsRef<Path>>(index_dir: P) -> tantivy::Result<Index> {
+    let index_dir = index_dir.as_ref();
+    if !index_dir.exists() {
+        std::fs::create_dir_all(index_dir)?;
+    }
+    let mmap_dir = MmapDirectory::open(index_dir)?;
+    if Index::exists(&mmap_dir) {
+        Index::open(mmap_dir)
+    } else {
+        Index::create(mmap_dir, TantivyWikiIndex::create_schema())
+    }
+}
+
+/// Index all pages in a set of dump streams, committing after every `chunk_len`
+/// streams.
+///
+/// # Arguments
+/// * `indices` - Offsets of the streams to index.
+/// * `data_path` - Path to a wikipedia bzip2 multistream.
+/// * `index_dir` - Search index directory; created if it does not exist.
+/// * `writer_heap` - Index writer heap size in bytes.
+/// * `chunk_len` - Number of streams to index between commits; at least 1.
+/// * `filter` - Filter for skipping pages.
+/// * `links` - Parser for the links of each page.
+pub fn build_index(
+    mut indices: Vec<&usize>,
+    data_path: &Path,
+    index_dir: &Path,
+    writer_heap: usize,
+    chunk_len: usize,
+    filter: &Arc<PageFilter>,
+    links: &LinkParser,
+) -> Result<(), Box<std::error::Error>> {
+    if chunk_len == 0 {
+        return Err("chunk size must be at least 1".into());
+    }
+    info!("Loading search index dir: {:?}", index_dir);
+    let index = open_or_create_index(index_dir).expect("Failed to open search index");
+    let schema = index.schema();
+    let index_writer = Mutex::new(index.writer(writer_heap).expect("Failed to create indexer"));
+
+    indices.sort();
+    let chunk_count = (indices.len() + chunk_len - 1) / chunk_len;
+    info!(
+        "Processing {} document chunks in blocks of {}",
+        indices.len(),
+        chunk_len
+    );
+    info!("Using index buffer size: {}", writer_heap);
+    for (index, chunk) in indices.chunks(chunk_len).enumerate() {
+        info!("Processing chunk {}/{}", index + 1, chunk_count);
+        index_anchors(
+            chunk.to_vec(),
+            data_path,
+            &index_writer,
+            &schema,
+            filter,
+            links,
+        )?;
+        let mut writer = index_writer.lock().expect("Failed to unlock indexer");
+        info!("Committing pending documents...");
+        writer.commit().expect("Failed to commit documents");
+    }
+    Ok(())
+}
+
 pub struct TantivyWikiIndex {
     index: Index,
     reader: IndexReader,
@@ -80,11 +153,13 @@ pub struct TantivyWikiIndex {
     content: Field,
     text_count_parser: QueryParser,
     out_link_parser: QueryParser,
+    titles: TitleNormaliser,
 }
 
 impl TantivyWikiIndex {
-    /// Open a Tantivy index at the given path.
-    pub fn new<P: AsRef<Path>>(index_dir: P) -> Self {
+    /// Open a Tantivy index at the given path, normalising queried titles
+    /// with the dump's `titles`.
+    pub fn new<P: AsRef<Path>>(index_dir: P, titles: TitleNormaliser) -> Self {
         let index = {
             let mmap_dir = MmapDirectory::open(index_dir).unwrap();
             Index::open(mmap_dir).unwrap()
@@ -108,6 +183,7 @@ impl TantivyWikiIndex {
             content,
             text_count_parser,
             out_link_parser,
+            titles,
         }
     }
 
@@ -121,8 +197,9 @@ impl TantivyWikiIndex {
     /// ## Fields
     /// * `id` - Page ID; FAST
     /// * `title` - Page title; STRING | STORED
-    /// * `content` - Page content; default tokenizer, indexed `WithFreqsAndPositions`.
-    /// * `outlinks` - Page links; default tokenizer, indexed `WithFreqs`.
+    /// * `content` - Page content as plain text; default tokenizer, indexed `WithFreqsAndPositions`.
+    /// * `outlinks` - Pages linked to, one value per page however often it is linked;
+    ///   raw tokenizer, indexed `WithFreqs`.
     pub fn create_schema() -> Schema {
         let mut schema_builder = Schema::builder();
 
@@ -137,13 +214,30 @@ impl TantivyWikiIndex {
         let options = TextOptions::default().set_indexing_options(
             TextFieldIndexing::default()
                 .set_index_option(IndexRecordOption::WithFreqs)
-                .set_tokenizer("default"),
+                .set_tokenizer("raw"),
         );
         schema_builder.add_text_field("outlinks", options);
 
         schema_builder.build()
     }
 
+    /// Convert a page title to the term stored in the `outlinks` field.
+    ///
+    /// Titles are normalised so that queries match however they are written;
+    /// link targets are already canonical when indexed.
+    pub fn outlink_term(titles: &TitleNormaliser, title: &str) -> String {
+        titles.normalise(title).replace(" ", "_")
+    }
+
+    /// Get the number of pages linking to the given page title.
+    ///
+    /// Linking pages are counted by matching documents, as by
+    /// `count_mutual_outlinks`, rather than by document frequency, so that
+    /// the two agree.
+    pub fn count_inlinks(&self, title: &str) -> u64 {
+        self.count_mutual_outlinks(&[title]) as u64
+    }
+
     pub fn count_matches_for_query(&self, query: &str) -> usize {
         let query = format!(r#""{}""#, query);
         let query = self.text_count_parser.parse_query(&query).unwrap();
@@ -151,10 +245,49 @@ impl TantivyWikiIndex {
         self.reader.searcher().search(&*query, &Count).unwrap()
     }
 
+    /// Get the number of pages containing a phrase, or `None` if the phrase
+    /// cannot be expressed as a phrase query.
+    pub fn count_phrase(&self, phrase: &str) -> Option<usize> {
+        if phrase.contains('"') {
+            return None;
+        }
+        let query = format!(r#""{}""#, phrase);
+        let query = self.text_count_parser.parse_query(&query).ok()?;
+        self.reader.searcher().search(&*query, &Count).ok()
+    }
+
+    /// Get the number of pages containing a phrase and linking to any of the
+    /// given page titles, or `None` if the phrase cannot be expressed as a
+    /// phrase query.
+    pub fn count_phrase_linking(&self, phrase: &str, titles: &[&str]) -> Option<usize> {
+        if phrase.contains('"') {
+            return None;
+        }
+        let query = format!(r#""{}""#, phrase);
+        let phrase = self.text_count_parser.parse_query(&query).ok()?;
+        let links = titles
+            .iter()
+            .map(|title| {
+                let term = TantivyWikiIndex::outlink_term(&self.titles, title);
+                let term = Term::from_field_text(self.outlinks, &term);
+                let term_query: Box<Query> =
+                    Box::new(TermQuery::new(term, IndexRecordOption::Basic));
+                (Occur::Should, term_query)
+            })
+            .collect::<Vec<_>>();
+        let links: Box<Query> = Box::new(BooleanQuery::from(links));
+        let query = BooleanQuery::from(vec![(Occur::Must, phrase), (Occur::Must, links)]);
+        self.reader.searcher().search(&query, &Count).ok()
+    }
+
+    /// Get the number of pages linking to all of the given page titles.
     pub fn count_mutual_outlinks(&self, query: &[&str]) -> usize {
         let terms = query
             .iter()
-            .map(|term| Term::from_field_text(self.outlinks, term))
+            .map(|title| {
+                let term = TantivyWikiIndex::outlink_term(&self.titles, title);
+                Term::from_field_text(self.outlinks, &term)
+            })
             .map(|term| {
                 let term_query: Box<Query> =
                     Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs));
@@ -165,14 +298,3 @@ impl TantivyWikiIndex {
         self.reader.searcher().search(&query, &Count).unwrap()
     }
 }
-                TermQuery::new(
-                    Term::from_field_text(self.outlinks, term),
-                    IndexRecordOption::Basic,
-                );
-            })
-            .collect::<Vec<_>>();
-        let query = BooleanQuery::new_multiterms_query(terms);
-        self.search(&*query, &Count).unwrap()
-    }
-}
-}
\ No newline at end of file
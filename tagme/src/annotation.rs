@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// An entity annotation for a span of query text.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Annotation {
+    /// Span text, as it appears in the query.
+    pub spot: String,
+    /// Byte offset of the start of the span.
+    pub start: usize,
+    /// Byte offset of the end of the span.
+    pub end: usize,
+    /// Character offset of the start of the span.
+    pub char_start: usize,
+    /// Character offset of the end of the span.
+    pub char_end: usize,
+    /// Title of the chosen entity.
+    pub entity: String,
+    /// Prior probability of the entity given the spot.
+    pub commonness: f32,
+    /// Probability of the spot occurring as a link.
+    pub link_probability: f32,
+    /// Relatedness vote received from the other spots in the query.
+    pub vote: f32,
+    /// Mean relatedness to the entities chosen for the other spots.
+    pub coherence: f32,
+    /// Final annotation score; mean of link probability and coherence.
+    pub rho: f32,
+}
@@ -0,0 +1,8 @@
+pub mod annotation;
+pub mod query;
+pub mod spot;
+
+pub use self::{
+    annotation::Annotation,
+    query::{SurfaceFormSource, TagMeConfig, TagMeQuery},
+};
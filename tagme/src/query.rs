@@ -0,0 +1,387 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use log::debug;
+
+use storage::fst::WikiAnchors;
+use storage::link_probability::LinkCounts;
+use storage::relatedness::{Relatedness, RelatednessMatrix};
+use storage::surface_form::SurfaceForm;
+use storage::tantivy::TantivyWikiIndex;
+
+use crate::annotation::Annotation;
+use crate::spot::{self, Candidate, Spot};
+
+/// Order candidates by commonness, treating NaN as equal to any score.
+fn by_commonness(a: &Candidate, b: &Candidate) -> Ordering {
+    a.commonness
+        .partial_cmp(&b.commonness)
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Source of surface forms for spotting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SurfaceFormSource {
+    /// Anchor text of links within Wikipedia.
+    Wiki,
+}
+
+/// Thresholds and limits for the TagMe pipeline.
+#[derive(Clone, Debug)]
+pub struct TagMeConfig {
+    /// Maximum number of tokens in a spot.
+    pub max_spot_tokens: usize,
+    /// Spots with a lower link probability are discarded.
+    pub link_probability_threshold: f32,
+    /// Candidate entities with a lower commonness are discarded.
+    pub commonness_threshold: f32,
+    /// Maximum number of candidate entities considered per spot.
+    pub max_candidates: usize,
+    /// Annotations with a lower rho score are discarded.
+    pub rho_threshold: f32,
+}
+
+impl Default for TagMeConfig {
+    fn default() -> Self {
+        TagMeConfig {
+            max_spot_tokens: 6,
+            link_probability_threshold: 0.01,
+            commonness_threshold: 0.02,
+            max_candidates: 10,
+            rho_threshold: 0.1,
+        }
+    }
+}
+
+/// TagMe entity-linking query over a piece of text.
+///
+/// See Ferragina & Scaiella, "TAGME: on-the-fly annotation of short text
+/// fragments (by Wikipedia entities)", CIKM 2010.
+pub struct TagMeQuery {
+    text: String,
+    epsilon: f32,
+    source: SurfaceFormSource,
+    config: TagMeConfig,
+    annotations: Vec<Annotation>,
+}
+
+impl TagMeQuery {
+    /// Create a new query.
+    ///
+    /// # Arguments
+    /// * `text` - Text to annotate.
+    /// * `epsilon` - Candidates whose vote is within this fraction of the best
+    ///     vote for a spot are disambiguated by commonness.
+    /// * `source` - Source of surface forms.
+    pub fn new(text: &str, epsilon: f32, source: SurfaceFormSource) -> Self {
+        TagMeQuery {
+            text: text.to_owned(),
+            epsilon,
+            source,
+            config: TagMeConfig::default(),
+            annotations: vec![],
+        }
+    }
+
+    /// Replace the default thresholds.
+    pub fn with_config(mut self, config: TagMeConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Annotations produced by the last call to `parse`.
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Annotate the query text.
+    pub fn parse(
+        &mut self,
+        anchors: &WikiAnchors,
+        index: &TantivyWikiIndex,
+    ) -> Result<&[Annotation], Box<std::error::Error>> {
+        let spots = self.spot(anchors, index)?;
+        debug!("Found {} spots", spots.len());
+
+        let mut entities = spots
+            .iter()
+            .flat_map(|spot| spot.candidates.iter().map(|c| c.entity.as_str()))
+            .collect::<Vec<_>>();
+        entities.sort();
+        entities.dedup();
+        let rel = Relatedness::new(index).pairwise(&entities);
+
+        let votes = TagMeQuery::vote(&spots, &rel);
+        let chosen = spots
+            .iter()
+            .zip(votes.iter())
+            .map(|(spot, votes)| self.disambiguate(spot, votes))
+            .collect::<Vec<_>>();
+        self.annotations = self.prune(&spots, &chosen, &rel);
+        Ok(&self.annotations)
+    }
+
+    /// Find spots in the text with at least one candidate entity, pruning by
+    /// link probability and resolving overlaps.
+    fn spot(
+        &self,
+        anchors: &WikiAnchors,
+        index: &TantivyWikiIndex,
+    ) -> Result<Vec<Spot>, Box<std::error::Error>> {
+        let tokens = spot::tokenize(&self.text);
+        let mut seen: HashMap<String, Option<(f32, Vec<Candidate>)>> = HashMap::new();
+        let mut spots = vec![];
+
+        for (start, end) in spot::ngrams(&tokens, self.config.max_spot_tokens) {
+            let text = spot::normalise(&self.text[start..end]);
+            if !seen.contains_key(&text) {
+                let found = match self.source {
+                    SurfaceFormSource::Wiki => anchors.entities_for_query(&text)?,
+                };
+                let entry = self.score_surface_form(&text, &found, anchors, index);
+                seen.insert(text.clone(), entry);
+            }
+            if let Some(Some((link_probability, candidates))) = seen.get(&text) {
+                spots.push(Spot {
+                    text,
+                    start,
+                    end,
+                    link_probability: *link_probability,
+                    candidates: candidates.clone(),
+                });
+            }
+        }
+        Ok(spot::resolve_overlaps(spots))
+    }
+
+    /// Calculate link probability and candidate entities for a surface form,
+    /// returning `None` if it should not be considered a spot.
+    ///
+    /// Precomputed link counts are used if loaded. Otherwise pages linking
+    /// the surface form are estimated from the search index as those
+    /// containing it which link to one of its candidates.
+    fn score_surface_form(
+        &self,
+        text: &str,
+        found: &SurfaceForm,
+        anchors: &WikiAnchors,
+        index: &TantivyWikiIndex,
+    ) -> Option<(f32, Vec<Candidate>)> {
+        if found.is_empty() {
+            return None;
+        }
+        let mut candidates = found
+            .get_wiki_matches(self.config.commonness_threshold)
+            .into_iter()
+            .map(|(entity, commonness)| Candidate { entity, commonness })
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return None;
+        }
+        candidates.sort_by(|a, b| by_commonness(b, a));
+        candidates.truncate(self.config.max_candidates);
+
+        let link_probability = match anchors.link_counts(text) {
+            Some(counts) => counts.link_probability(),
+            None => {
+                let entities = candidates
+                    .iter()
+                    .map(|candidate| candidate.entity.as_str())
+                    .collect::<Vec<_>>();
+                LinkCounts {
+                    links: index.count_phrase_linking(text, &entities)? as u64,
+                    docs: index.count_phrase(text)? as u64,
+                }
+                .link_probability()
+            }
+        };
+        if link_probability < self.config.link_probability_threshold {
+            return None;
+        }
+        Some((link_probability, candidates))
+    }
+
+    /// Calculate the vote each candidate of each spot receives from every
+    /// other spot.
+    ///
+    /// The vote of spot `b` for candidate `p` of spot `a` is the mean
+    /// relatedness of `p` to the candidates of `b`, weighted by their
+    /// commonness.
+    fn vote(spots: &[Spot], rel: &RelatednessMatrix) -> Vec<Vec<f32>> {
+        spots
+            .iter()
+            .enumerate()
+            .map(|(a, spot_a)| {
+                spot_a
+                    .candidates
+                    .iter()
+                    .map(|pa| {
+                        spots
+                            .iter()
+                            .enumerate()
+                            .filter(|(b, _)| *b != a)
+                            .map(|(_, spot_b)| {
+                                let total: f32 = spot_b
+                                    .candidates
+                                    .iter()
+                                    .map(|pb| {
+                                        rel.get_by_title(&pb.entity, &pa.entity).unwrap_or(0.0)
+                                            * pb.commonness
+                                    })
+                                    .sum();
+                                total / spot_b.candidates.len() as f32
+                            })
+                            .sum::<f32>()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Pick an entity for a spot.
+    ///
+    /// Of the candidates whose vote is within `epsilon` of the best vote, the
+    /// one with the highest commonness is chosen.
+    fn disambiguate(&self, spot: &Spot, votes: &[f32]) -> (usize, f32) {
+        let best = votes.iter().cloned().fold(0.0, f32::max);
+        let floor = best * (1.0 - self.epsilon);
+        spot.candidates
+            .iter()
+            .zip(votes.iter())
+            .enumerate()
+            .filter(|(_, (_, vote))| **vote >= floor)
+            .max_by(|(_, (a, _)), (_, (b, _))| by_commonness(a, b))
+            .map(|(i, (_, vote))| (i, *vote))
+            .unwrap_or((0, 0.0))
+    }
+
+    /// Score each chosen entity by coherence with the other chosen entities,
+    /// discarding annotations below the rho threshold.
+    fn prune(
+        &self,
+        spots: &[Spot],
+        chosen: &[(usize, f32)],
+        rel: &RelatednessMatrix,
+    ) -> Vec<Annotation> {
+        let entities = spots
+            .iter()
+            .zip(chosen.iter())
+            .map(|(spot, (i, _))| spot.candidates[*i].entity.as_str())
+            .collect::<Vec<_>>();
+
+        let mut annotations = vec![];
+        for (a, spot) in spots.iter().enumerate() {
+            let (i, vote) = chosen[a];
+            let candidate = &spot.candidates[i];
+            let coherence = if entities.len() > 1 {
+                let total: f32 = entities
+                    .iter()
+                    .enumerate()
+                    .filter(|(b, _)| *b != a)
+                    .map(|(_, entity)| rel.get_by_title(&candidate.entity, entity).unwrap_or(0.0))
+                    .sum();
+                total / (entities.len() - 1) as f32
+            } else {
+                0.0
+            };
+            let rho = (spot.link_probability + coherence) / 2.0;
+            if rho < self.config.rho_threshold {
+                debug!("Pruned {} -> {} (rho={})", spot.text, candidate.entity, rho);
+                continue;
+            }
+            annotations.push(Annotation {
+                spot: self.text[spot.start..spot.end].to_owned(),
+                start: spot.start,
+                end: spot.end,
+                char_start: self.text[..spot.start].chars().count(),
+                char_end: self.text[..spot.end].chars().count(),
+                entity: candidate.entity.clone(),
+                commonness: candidate.commonness,
+                link_probability: spot.link_probability,
+                vote,
+                coherence,
+                rho,
+            });
+        }
+        annotations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spot(text: &str, start: usize, link_probability: f32, candidates: &[(&str, f32)]) -> Spot {
+        Spot {
+            text: text.to_owned(),
+            start,
+            end: start + text.len(),
+            link_probability,
+            candidates: candidates
+                .iter()
+                .map(|(entity, commonness)| Candidate {
+                    entity: entity.to_string(),
+                    commonness: *commonness,
+                })
+                .collect(),
+        }
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_vote_disambiguate_prune() {
+        let query = TagMeQuery::new("orpheus lyre the", 0.5, SurfaceFormSource::Wiki);
+        let spots = vec![
+            spot(
+                "orpheus",
+                0,
+                0.5,
+                &[("Orpheus (opera)", 0.6), ("Orpheus", 0.4)],
+            ),
+            spot("lyre", 8, 0.3, &[("Lyre", 1.0)]),
+            spot("the", 13, 0.02, &[("The (band)", 1.0)]),
+        ];
+        let rel = RelatednessMatrix::from_pairs(
+            &["Orpheus (opera)", "Orpheus", "Lyre", "The (band)"],
+            vec![(0, 2, 0.2), (1, 2, 0.8)],
+        );
+
+        let votes = TagMeQuery::vote(&spots, &rel);
+        assert_near(votes[0][0], 0.2);
+        assert_near(votes[0][1], 0.8);
+        assert_near(votes[1][0], (0.2 * 0.6 + 0.8 * 0.4) / 2.0);
+        assert_near(votes[2][0], 0.0);
+
+        // The less common sense wins on relatedness to the other spots.
+        let chosen = spots
+            .iter()
+            .zip(votes.iter())
+            .map(|(spot, votes)| query.disambiguate(spot, votes))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            chosen.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            vec![1, 0, 0]
+        );
+
+        // "the" is unrelated to the other entities and rarely linked, so its
+        // rho falls below the threshold.
+        let annotations = query.prune(&spots, &chosen, &rel);
+        assert_eq!(
+            annotations
+                .iter()
+                .map(|annotation| (annotation.spot.as_str(), annotation.entity.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("orpheus", "Orpheus"), ("lyre", "Lyre")]
+        );
+        assert_near(annotations[0].coherence, 0.4);
+        assert_near(annotations[0].rho, 0.45);
+        assert_near(annotations[1].rho, 0.35);
+    }
+}
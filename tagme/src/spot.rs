@@ -0,0 +1,130 @@
+use std::cmp::Ordering;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// Match a single word token.
+    static ref TOKEN: Regex = Regex::new(r"\w+").unwrap();
+}
+
+/// Byte offsets of a word token within a query.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Token {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Split text into word tokens.
+pub fn tokenize(text: &str) -> Vec<Token> {
+    TOKEN
+        .find_iter(text)
+        .map(|m| Token {
+            start: m.start(),
+            end: m.end(),
+        })
+        .collect()
+}
+
+/// Byte spans of all token n-grams up to `max_len` tokens long.
+pub fn ngrams(tokens: &[Token], max_len: usize) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    for (i, first) in tokens.iter().enumerate() {
+        for last in tokens[i..].iter().take(max_len) {
+            spans.push((first.start, last.end));
+        }
+    }
+    spans
+}
+
+/// Normalise span text to the form used for surface form keys.
+///
+/// Anchor surface forms are stored lowercased and trimmed; runs of
+/// whitespace are collapsed here so that line breaks in the query text still
+/// match.
+pub fn normalise(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Candidate entity for a spot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub entity: String,
+    pub commonness: f32,
+}
+
+/// A span of query text matching a known surface form.
+#[derive(Clone, Debug)]
+pub struct Spot {
+    /// Normalised surface form.
+    pub text: String,
+    /// Byte offset of the start of the span.
+    pub start: usize,
+    /// Byte offset of the end of the span.
+    pub end: usize,
+    pub link_probability: f32,
+    /// Candidate entities, sorted by descending commonness.
+    pub candidates: Vec<Candidate>,
+}
+
+impl Spot {
+    /// Check if two spots share any bytes of the query.
+    pub fn overlaps(&self, other: &Spot) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// Remove overlapping spots, keeping those with the higher link probability.
+///
+/// Ties are broken in favour of the longer span. The surviving spots are
+/// returned in text order.
+pub fn resolve_overlaps(mut spots: Vec<Spot>) -> Vec<Spot> {
+    spots.sort_by(|a, b| {
+        b.link_probability
+            .partial_cmp(&a.link_probability)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| (b.end - b.start).cmp(&(a.end - a.start)))
+    });
+    let mut kept: Vec<Spot> = Vec::with_capacity(spots.len());
+    for spot in spots {
+        if !kept.iter().any(|k| k.overlaps(&spot)) {
+            kept.push(spot);
+        }
+    }
+    kept.sort_by_key(|spot| spot.start);
+    kept
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn spot(start: usize, end: usize, link_probability: f32) -> Spot {
+        Spot {
+            text: String::new(),
+            start,
+            end,
+            link_probability,
+            candidates: vec![],
+        }
+    }
+
+    #[test]
+    fn test_ngrams() {
+        let tokens = tokenize("Orpheus and Eurydice");
+        assert_eq!(tokens.len(), 3);
+        let spans = ngrams(&tokens, 2);
+        assert_eq!(spans, vec![(0, 7), (0, 11), (8, 11), (8, 20), (12, 20)]);
+    }
+
+    #[test]
+    fn test_resolve_overlaps() {
+        let spots = vec![spot(0, 7, 0.5), spot(0, 20, 0.8), spot(12, 20, 0.9)];
+        let kept = resolve_overlaps(spots);
+        let spans = kept.iter().map(|s| (s.start, s.end)).collect::<Vec<_>>();
+        assert_eq!(spans, vec![(0, 7), (12, 20)]);
+    }
+}